@@ -169,3 +169,4 @@ make_async!(calculate_mmr_roots(template: NewBlockTemplate) -> Block);
 make_async!(fetch_block(height: u64) -> HistoricalBlock);
 make_async!(rewind_to_height(height: u64) -> ());
 make_async!(fetch_mmr_proof(tree: MmrTree, pos: usize) -> MerkleProof);
+make_async!(fetch_mmr_proof_bytes(tree: MmrTree, leaf_pos: usize) -> Vec<u8>);
@@ -29,6 +29,7 @@ use crate::{
         ChainMetadata,
         HistoricalBlock,
     },
+    consensus::emission::EmissionSchedule,
     proof_of_work::Difficulty,
     validation::{Validation, Validator},
 };
@@ -41,12 +42,15 @@ use std::{
 };
 use tari_mmr::{Hash, MerkleCheckPoint, MerkleProof, MutableMmrLeafNodes};
 use tari_transactions::{
+    tari_amount::MicroTari,
     transaction::{TransactionInput, TransactionKernel, TransactionOutput},
-    types::{Commitment, HashOutput},
+    types::{Commitment, HashDigest, HashOutput},
 };
 use tari_utilities::{hex::Hex, Hashable};
 
 const LOG_TARGET: &str = "core::chain_storage::database";
+/// The version byte prefixed to MMR proofs serialized by [BlockchainDatabase::fetch_mmr_proof_bytes].
+pub const MMR_PROOF_SERIALIZATION_VERSION: u8 = 1;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum BlockAddResult {
@@ -63,6 +67,16 @@ pub struct MutableMmrState {
     pub leaf_nodes: MutableMmrLeafNodes,
 }
 
+/// The number of entries stored in each of the collections of a blockchain backend
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ChainCounts {
+    pub headers: usize,
+    pub utxos: usize,
+    pub stxos: usize,
+    pub kernels: usize,
+    pub orphans: usize,
+}
+
 /// A placeholder struct that contains the two validators that the database uses to decide whether or not a block is
 /// eligible to be added to the database. The `block` validator should perform a full consensus check. The `orphan`
 /// validator needs to check that the block is internally consistent, but can't know whether the PoW is sufficient,
@@ -109,6 +123,10 @@ pub trait BlockchainBackend: Send + Sync {
     /// Checks to see whether the given key exists in the back end. This function should only fail if there is an
     /// access or integrity issue with the back end.
     fn contains(&self, key: &DbKey) -> Result<bool, ChainStorageError>;
+    /// Checks whether each of the given keys exists in the backend and returns the results in the same order as the
+    /// keys. This is equivalent to calling `contains` for every key, but allows the backend to evaluate all the keys
+    /// under a single read lock.
+    fn contains_all(&self, keys: &[DbKey]) -> Result<Vec<bool>, ChainStorageError>;
     /// Fetches the merklish root for the MMR tree identified by the key. This function should only fail if there is an
     /// access or integrity issue with the back end.
     fn fetch_mmr_root(&self, tree: MmrTree) -> Result<HashOutput, ChainStorageError>;
@@ -128,8 +146,24 @@ pub trait BlockchainBackend: Send + Sync {
     /// added & deleted for the given Merkle tree. When a height is provided that is less than the pruning horizon, then
     /// a BeyondPruningHorizon error will be produced.
     fn fetch_mmr_checkpoint(&self, tree: MmrTree, height: u64) -> Result<MerkleCheckPoint, ChainStorageError>;
+    /// Returns the kernels that were added to the kernel MMR in the checkpoint at the given height, each with a Merkle
+    /// proof of its membership in the kernel MMR. The kernels are returned in the order in which they were added, so
+    /// they occupy consecutive leaf positions. The proofs are verified against the MMR-only root of the kernel MMR.
+    fn fetch_block_kernel_proofs(
+        &self,
+        height: u64,
+    ) -> Result<Vec<(TransactionKernel, MerkleProof)>, ChainStorageError>;
     /// Fetches the leaf node hash and its deletion status for the nth leaf node in the given MMR tree.
     fn fetch_mmr_node(&self, tree: MmrTree, pos: u32) -> Result<(Hash, bool), ChainStorageError>;
+    /// Fetches the leaf node hashes and deletion statuses for `count` consecutive leaf nodes in the given MMR tree,
+    /// starting at `start_pos`. An error naming the first missing position is returned if any of the leaf nodes
+    /// could not be found.
+    fn fetch_mmr_nodes(
+        &self,
+        tree: MmrTree,
+        start_pos: u32,
+        count: u32,
+    ) -> Result<Vec<(Hash, bool)>, ChainStorageError>;
     /// Fetches the MMR base state of the specified tree. The MMR base state consists of the state from the genesis
     /// block to the horizon block. The index is the n-th leaf node in the MMR. The count specifies the maximum number
     /// of leaf nodes that can be returned, starting with the node at the provided index.
@@ -141,6 +175,12 @@ pub trait BlockchainBackend: Send + Sync {
     ) -> Result<MutableMmrState, ChainStorageError>;
     /// Returns the number of leaf nodes in the base MMR of the specified tree.
     fn fetch_mmr_base_leaf_node_count(&self, tree: MmrTree) -> Result<usize, ChainStorageError>;
+    /// Returns the number of checkpoints of the specified tree that will be merged into the base MMR on the next
+    /// `CreateMmrCheckpoint`, because the checkpoint history would exceed `max_history_len`.
+    fn pending_pruned_checkpoints(&self, tree: MmrTree) -> Result<usize, ChainStorageError>;
+    /// Returns the sorted leaf indices that have been marked as deleted in the specified MMR. Only the UTXO MMR has
+    /// deletions, so this is always empty for the kernel and range proof MMRs.
+    fn fetch_deleted_leaf_indices(&self, tree: MmrTree) -> Result<Vec<u32>, ChainStorageError>;
     /// Resets and restores the state of the specified MMR tree using a set of leaf nodes.
     fn assign_mmr(&self, tree: MmrTree, base_state: MutableMmrLeafNodes) -> Result<(), ChainStorageError>;
     /// Performs the function F for each orphan block in the orphan pool.
@@ -148,10 +188,125 @@ pub trait BlockchainBackend: Send + Sync {
     where
         Self: Sized,
         F: FnMut(Result<(HashOutput, Block), ChainStorageError>);
+    /// Performs the function F for each unspent output in the UTXO set.
+    fn for_each_utxo<F>(&self, f: F) -> Result<(), ChainStorageError>
+    where
+        Self: Sized,
+        F: FnMut(Result<(HashOutput, TransactionOutput), ChainStorageError>);
+    /// Returns up to `limit` unspent outputs, starting at the `offset`-th output when the UTXO set is ordered by MMR
+    /// leaf index. The order is stable across calls, so the UTXO set can be fetched in pages. An empty list is returned
+    /// if the offset is beyond the end of the UTXO set.
+    fn fetch_utxos_paged(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(HashOutput, TransactionOutput)>, ChainStorageError>;
+    /// Removes every orphan block with a height strictly below `height` from the orphan pool and returns the number of
+    /// orphans that were removed.
+    fn prune_orphans_below(&self, height: u64) -> Result<usize, ChainStorageError>;
+    /// Returns the number of headers, UTXOs, STXOs, kernels and orphan blocks stored in the backend.
+    fn fetch_counts(&self) -> Result<ChainCounts, ChainStorageError>;
     /// Returns the height of earliest block that the backend can provide full data for.
     fn fetch_horizon_block_height(&self) -> Result<u64, ChainStorageError>;
     /// Returns the stored header with the highest corresponding height.
     fn fetch_last_header(&self) -> Result<Option<BlockHeader>, ChainStorageError>;
+    /// Returns up to `n` of the stored headers with the highest heights, ordered from the tip downwards. Fewer headers
+    /// are returned if less than `n` headers are stored.
+    fn fetch_last_n_headers(&self, n: usize) -> Result<Vec<BlockHeader>, ChainStorageError>;
+    /// Fetches the headers from height `start` up to and including height `end_inclusive`, sorted by height. A
+    /// `ValueNotFound` error naming the first missing height is returned if a header in the range is not stored.
+    fn fetch_headers(&self, start: u64, end_inclusive: u64) -> Result<Vec<BlockHeader>, ChainStorageError>;
+    /// Fetches the UTXO with the given hash together with its leaf index in the range proof MMR. `None` is returned if
+    /// the hash is not in the UTXO set.
+    fn fetch_utxo_with_position(
+        &self,
+        hash: &HashOutput,
+    ) -> Result<Option<(TransactionOutput, usize)>, ChainStorageError>;
+    /// Checks whether the given header builds on the current tip, i.e. whether its `prev_hash` is the hash of the
+    /// stored header with the highest height. Returns `false` if it does not connect or if no headers are stored.
+    fn header_connects_to_tip(&self, header: &BlockHeader) -> Result<bool, ChainStorageError> {
+        Ok(self
+            .fetch_last_header()?
+            .map(|tip| tip.hash() == header.prev_hash)
+            .unwrap_or(false))
+    }
+    /// Fetches the spent outputs (STXOs) with the given hashes. The results are returned in the same order as the
+    /// hashes, with `None` for every hash that is not in the STXO set.
+    fn fetch_spent_outputs(&self, hashes: &[HashOutput]) -> Result<Vec<Option<TransactionOutput>>, ChainStorageError> {
+        hashes
+            .iter()
+            .map(|hash| {
+                let key = DbKey::SpentOutput(hash.clone());
+                match self.fetch(&key)? {
+                    None => Ok(None),
+                    Some(DbValue::SpentOutput(output)) => Ok(Some(*output)),
+                    Some(other) => unexpected_result(key, other),
+                }
+            })
+            .collect()
+    }
+    /// Builds a block locator for the current chain: the hashes of the blocks at heights tip, tip-1, tip-2, tip-4,
+    /// tip-8, ... with the gap doubling each step, always ending with the genesis block. An empty locator is returned
+    /// if no headers are stored.
+    fn build_block_locator(&self) -> Result<Vec<HashOutput>, ChainStorageError> {
+        let mut height = match self.fetch_last_header()? {
+            Some(tip) => tip.height,
+            None => return Ok(Vec::new()),
+        };
+        let mut locator = Vec::new();
+        let mut step = 1;
+        loop {
+            let key = DbKey::BlockHeader(height);
+            match self.fetch(&key)? {
+                Some(DbValue::BlockHeader(header)) => locator.push(header.hash()),
+                Some(other) => return unexpected_result(key, other),
+                None => return Err(ChainStorageError::ValueNotFound(key)),
+            }
+            if height == 0 {
+                break;
+            }
+            height = height.saturating_sub(step);
+            if locator.len() > 1 {
+                step *= 2;
+            }
+        }
+        Ok(locator)
+    }
+    /// Calculates how many blocks would be rewound to switch from the current chain to the competing chain. The
+    /// competing headers must be ordered by height and may start with headers that are shared with the current chain.
+    /// The fork point is the stored header that the first unknown competing header builds on, and the returned depth
+    /// is the current tip height minus the fork height. Returns 0 if the competing chain extends the current tip
+    /// directly or contains no unknown headers.
+    fn compute_reorg_depth(&self, competing_headers: &[BlockHeader]) -> Result<u64, ChainStorageError> {
+        let tip_height = match self.fetch_last_header()? {
+            Some(tip) => tip.height,
+            None => return Err(ChainStorageError::InvalidQuery("No headers are stored".to_string())),
+        };
+        for header in competing_headers {
+            if self.contains(&DbKey::BlockHash(header.hash()))? {
+                continue;
+            }
+            let key = DbKey::BlockHash(header.prev_hash.clone());
+            return match self.fetch(&key)? {
+                Some(DbValue::BlockHash(fork_header)) => Ok(tip_height.saturating_sub(fork_header.height)),
+                Some(other) => unexpected_result(key, other),
+                None => Err(ChainStorageError::InvalidQuery(
+                    "The competing chain does not connect to the current chain".to_string(),
+                )),
+            };
+        }
+        Ok(0)
+    }
+    /// Calculates the supply that the given emission schedule emits from the genesis block up to the current tip. This
+    /// is not a sum of the stored coinbase outputs: their values are hidden by their commitments, so it is derived from
+    /// the tip height and the schedule alone. Transaction fees that are claimed by coinbases are not newly emitted and
+    /// are not included. Zero is returned if no headers are stored.
+    fn calculate_emission_at_tip(&self, emission_schedule: &EmissionSchedule) -> Result<MicroTari, ChainStorageError> {
+        Ok(self
+            .fetch_last_header()?
+            .map(|tip| emission_schedule.supply_at_block(tip.height))
+            .unwrap_or_else(|| MicroTari::from(0)))
+    }
 }
 
 // Private macro that pulls out all the boiler plate of extracting a DB query result from its variants
@@ -412,6 +567,36 @@ where T: BlockchainBackend
         self.db.fetch_mmr_proof(tree, pos)
     }
 
+    /// Checks that the leaf node at the given position in the MMR has the given hash, by fetching the Merkle proof for
+    /// the leaf and verifying it against the current MMR root of the tree. `false` is returned if the proof does not
+    /// verify, while an error is returned if the proof could not be constructed, e.g. because the leaf does not exist.
+    pub fn verify_mmr_proof(
+        &self,
+        tree: MmrTree,
+        leaf_pos: usize,
+        leaf_hash: &[u8],
+    ) -> Result<bool, ChainStorageError>
+    {
+        let proof = self.db.fetch_mmr_proof(tree.clone(), leaf_pos)?;
+        // Proofs are constructed from the full MMR, so they are verified against the root without the deleted bitmap
+        let root = self.db.fetch_mmr_only_root(tree)?;
+        Ok(proof.verify_leaf::<HashDigest>(&root, leaf_hash, leaf_pos).is_ok())
+    }
+
+    /// Fetch a Merklish proof for the given tree and leaf position in the MMR, serialized in a portable format that can
+    /// be sent to light clients.
+    ///
+    /// The first byte is the serialization version, currently [MMR_PROOF_SERIALIZATION_VERSION]. For version 1 the
+    /// remaining bytes are the bincode encoding of the `MerkleProof`: the MMR size as a little-endian u64, followed by
+    /// the path and the peaks, each encoded as a little-endian u64 length followed by that many length-prefixed hashes.
+    /// Use [deserialize_mmr_proof] to decode the bytes.
+    pub fn fetch_mmr_proof_bytes(&self, tree: MmrTree, leaf_pos: usize) -> Result<Vec<u8>, ChainStorageError> {
+        let proof = self.db.fetch_mmr_proof(tree, leaf_pos)?;
+        let mut buf = vec![MMR_PROOF_SERIALIZATION_VERSION];
+        bincode::serialize_into(&mut buf, &proof).map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        Ok(buf)
+    }
+
     /// Fetches the MMR base state of the specified tree. The MMR base state consists of the state from the genesis
     /// block to the horizon block. The index is the n-th leaf node in the MMR. The count specifies the maximum number
     /// of leaf nodes that can be returned, starting with the node at the provided index.
@@ -834,6 +1019,19 @@ where T: BlockchainBackend
     }
 }
 
+/// Decode an MMR proof serialized by [BlockchainDatabase::fetch_mmr_proof_bytes].
+pub fn deserialize_mmr_proof(bytes: &[u8]) -> Result<MerkleProof, ChainStorageError> {
+    match bytes.split_first() {
+        Some((&MMR_PROOF_SERIALIZATION_VERSION, proof_bytes)) => bincode::deserialize(proof_bytes)
+            .map_err(|e| ChainStorageError::InvalidMmrProofBytes(format!("Could not decode MMR proof: {}", e))),
+        Some((version, _)) => Err(ChainStorageError::InvalidMmrProofBytes(format!(
+            "Unsupported MMR proof serialization version {}",
+            version
+        ))),
+        None => Err(ChainStorageError::InvalidMmrProofBytes("Empty MMR proof".to_string())),
+    }
+}
+
 fn unexpected_result<T>(req: DbKey, res: DbValue) -> Result<T, ChainStorageError> {
     let msg = format!("Unexpected result for database query {}. Response: {}", req, res);
     error!(target: LOG_TARGET, "{}", msg);
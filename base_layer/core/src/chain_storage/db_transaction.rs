@@ -136,6 +136,11 @@ impl DbTransaction {
         )));
     }
 
+    /// Discards the uncommitted changes to the given MMR, resetting its state to that of the last checkpoint.
+    pub fn reset_mmr(&mut self, tree: MmrTree) {
+        self.operations.push(WriteOperation::ResetMmr(tree));
+    }
+
     /// Rewinds the Kernel MMR state by the given number of Checkpoints.
     pub fn rewind_kernel_mmr(&mut self, steps_back: usize) {
         self.operations
@@ -155,6 +160,91 @@ impl DbTransaction {
     }
 }
 
+/// A builder that allows a [DbTransaction] to be constructed fluently, e.g.
+/// `DbTransactionBuilder::new().insert_header(header).insert_utxo(utxo, true).commit_block().build()`
+#[derive(Debug, Default)]
+pub struct DbTransactionBuilder {
+    txn: DbTransaction,
+}
+
+impl DbTransactionBuilder {
+    /// Creates a new builder containing an empty transaction.
+    pub fn new() -> Self {
+        DbTransactionBuilder::default()
+    }
+
+    /// Inserts a block header into the transaction.
+    pub fn insert_header(mut self, header: BlockHeader) -> Self {
+        self.txn.insert_header(header);
+        self
+    }
+
+    /// Inserts a transaction kernel into the transaction.
+    pub fn insert_kernel(mut self, kernel: TransactionKernel, update_mmr: bool) -> Self {
+        self.txn.insert_kernel(kernel, update_mmr);
+        self
+    }
+
+    /// Adds a UTXO into the transaction.
+    pub fn insert_utxo(mut self, utxo: TransactionOutput, update_mmr: bool) -> Self {
+        self.txn.insert_utxo(utxo, update_mmr);
+        self
+    }
+
+    /// Stores an orphan block.
+    pub fn insert_orphan(mut self, orphan: Block) -> Self {
+        self.txn.insert_orphan(orphan);
+        self
+    }
+
+    /// Moves a UTXO to the STXO set.
+    pub fn spend_utxo(mut self, utxo_hash: HashOutput) -> Self {
+        self.txn.spend_utxo(utxo_hash);
+        self
+    }
+
+    /// Moves a STXO back to the UTXO set.
+    pub fn unspend_stxo(mut self, stxo_hash: HashOutput) -> Self {
+        self.txn.unspend_stxo(stxo_hash);
+        self
+    }
+
+    /// Deletes the value stored under the given key.
+    pub fn delete(mut self, key: DbKey) -> Self {
+        self.txn.delete(key);
+        self
+    }
+
+    /// Creates a checkpoint on the given MMR.
+    pub fn create_checkpoint(mut self, tree: MmrTree) -> Self {
+        self.txn.operations.push(WriteOperation::CreateMmrCheckpoint(tree));
+        self
+    }
+
+    /// Creates a checkpoint on all the MMRs, see [DbTransaction::commit_block].
+    pub fn commit_block(mut self) -> Self {
+        self.txn.commit_block();
+        self
+    }
+
+    /// Resets the given MMR to its last checkpoint, see [DbTransaction::reset_mmr].
+    pub fn reset(mut self, tree: MmrTree) -> Self {
+        self.txn.reset_mmr(tree);
+        self
+    }
+
+    /// Rewinds the given MMR by the given number of checkpoints.
+    pub fn rewind(mut self, tree: MmrTree, steps_back: usize) -> Self {
+        self.txn.operations.push(WriteOperation::RewindMmr(tree, steps_back));
+        self
+    }
+
+    /// Returns the constructed transaction.
+    pub fn build(self) -> DbTransaction {
+        self.txn
+    }
+}
+
 #[derive(Debug)]
 pub enum WriteOperation {
     Insert(DbKeyValuePair),
@@ -162,6 +252,10 @@ pub enum WriteOperation {
     Spend(DbKey),
     UnSpend(DbKey),
     CreateMmrCheckpoint(MmrTree),
+    /// Discard the uncommitted changes to the MMR
+    ResetMmr(MmrTree),
+    /// Rewind the MMR by the given number of checkpoints, which must be at least one. Use `ResetMmr` to only discard
+    /// the uncommitted changes.
     RewindMmr(MmrTree, usize),
 }
 
@@ -182,7 +276,7 @@ pub enum MmrTree {
     RangeProof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MetadataKey {
     ChainHeight,
     BestBlock,
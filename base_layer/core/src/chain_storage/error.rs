@@ -60,10 +60,24 @@ pub enum ChainStorageError {
     ValueNotFound(DbKey),
     MerkleMountainRangeError(MerkleMountainRangeError),
     MerkleProofError(MerkleProofError),
+    // The serialized MMR proof could not be decoded
+    #[error(msg_embedded, non_std, no_from)]
+    InvalidMmrProofBytes(String),
     ValidationError(ValidationError),
     // An MMR root in the provided block header did not match the MMR root in the database
     #[error(non_std, no_from)]
     MismatchedMmrRoot(MmrTree),
     // An invalid block was submitted to the database
     InvalidBlock,
+    // A value could not be inserted because the key already exists in the database
+    #[error(non_std, no_from)]
+    KeyExists {
+        key: String,
+    },
+    // The operation at the given (zero-based) index of a DbTransaction failed to be applied
+    #[error(non_std, no_from)]
+    OperationFailed {
+        index: usize,
+        source: Box<ChainStorageError>,
+    },
 }
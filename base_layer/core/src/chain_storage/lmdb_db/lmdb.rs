@@ -89,6 +89,30 @@ where
     }
 }
 
+/// Fetches the values for the given keys within a single read transaction. `None` is returned for every key that is
+/// not in the database.
+pub fn lmdb_get_many<K, V>(env: &Environment, db: &Database, keys: &[K]) -> Result<Vec<Option<V>>, ChainStorageError>
+where
+    K: Serialize,
+    V: DeserializeOwned,
+{
+    let txn = ReadTransaction::new(env).map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+    let access = txn.access();
+    keys.iter()
+        .map(|key| {
+            let key_buf = serialize(key)?;
+            match access.get(&db, &key_buf).to_opt() {
+                Ok(None) => Ok(None),
+                Err(e) => Err(ChainStorageError::AccessError(e.to_string())),
+                Ok(Some(v)) => match deserialize(v) {
+                    Ok(val) => Ok(Some(val)),
+                    Err(e) => Err(ChainStorageError::AccessError(e.to_string())),
+                },
+            }
+        })
+        .collect()
+}
+
 pub fn lmdb_exists<K>(env: &Environment, db: &Database, key: &K) -> Result<bool, ChainStorageError>
 where K: Serialize {
     let txn = ReadTransaction::new(env).map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
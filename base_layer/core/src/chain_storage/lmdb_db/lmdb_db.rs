@@ -23,11 +23,11 @@
 use crate::{
     blocks::{blockheader::BlockHeader, Block},
     chain_storage::{
-        blockchain_database::{BlockchainBackend, MutableMmrState},
+        blockchain_database::{BlockchainBackend, ChainCounts, MutableMmrState},
         db_transaction::{DbKey, DbKeyValuePair, DbTransaction, DbValue, MetadataValue, MmrTree, WriteOperation},
         error::ChainStorageError,
         lmdb_db::{
-            lmdb::{lmdb_delete, lmdb_exists, lmdb_for_each, lmdb_get, lmdb_insert, lmdb_len},
+            lmdb::{lmdb_delete, lmdb_exists, lmdb_for_each, lmdb_get, lmdb_get_many, lmdb_insert, lmdb_len},
             LMDBVec,
             LMDB_DB_BLOCK_HASHES,
             LMDB_DB_HEADERS,
@@ -67,7 +67,7 @@ use tari_transactions::{
     transaction::{TransactionKernel, TransactionOutput},
     types::{HashDigest, HashOutput},
 };
-use tari_utilities::hash::Hashable;
+use tari_utilities::{hash::Hashable, hex::Hex};
 
 type DatabaseRef = Arc<Database<'static>>;
 
@@ -201,9 +201,9 @@ where D: Digest + Send + Sync
         })
     }
 
-    // Applies all MMR transactions excluding CreateMmrCheckpoint and RewindMmr on the header_mmr, utxo_mmr,
-    // range_proof_mmr and kernel_mmr. CreateMmrCheckpoint and RewindMmr txns will be performed after the the storage
-    // txns have been successfully applied.
+    // Applies all MMR transactions excluding CreateMmrCheckpoint, ResetMmr and RewindMmr on the header_mmr, utxo_mmr,
+    // range_proof_mmr and kernel_mmr. CreateMmrCheckpoint, ResetMmr and RewindMmr txns will be performed after the the
+    // storage txns have been successfully applied.
     fn apply_mmr_txs(&self, tx: &DbTransaction) -> Result<(), ChainStorageError> {
         for op in tx.operations.iter() {
             match op {
@@ -247,61 +247,66 @@ where D: Digest + Send + Sync
                     },
                     _ => return Err(ChainStorageError::InvalidOperation("Only UTXOs can be spent".into())),
                 },
+                WriteOperation::RewindMmr(_, 0) => {
+                    return Err(ChainStorageError::InvalidOperation(
+                        "An MMR cannot be rewound by zero checkpoints, use ResetMmr instead".into(),
+                    ));
+                },
                 _ => {},
             }
         }
         Ok(())
     }
 
-    // Perform the RewindMmr and CreateMmrCheckpoint operations after MMR txns and storage txns have been applied.
+    // Perform the ResetMmr, RewindMmr and CreateMmrCheckpoint operations after MMR txns and storage txns have been
+    // applied.
     fn commit_mmrs(&self, tx: DbTransaction) -> Result<(), ChainStorageError> {
         for op in tx.operations.into_iter() {
             match op {
+                WriteOperation::ResetMmr(tree) => match tree {
+                    MmrTree::Kernel => {
+                        self.kernel_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .reset()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+                    },
+                    MmrTree::Utxo => {
+                        self.utxo_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .reset()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+                    },
+                    MmrTree::RangeProof => {
+                        self.range_proof_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .reset()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+                    },
+                },
                 WriteOperation::RewindMmr(tree, steps_back) => match tree {
                     MmrTree::Kernel => {
-                        if steps_back == 0 {
-                            self.kernel_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            self.kernel_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
+                        self.kernel_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .rewind(steps_back)
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
                     },
                     MmrTree::Utxo => {
-                        if steps_back == 0 {
-                            self.utxo_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            self.utxo_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
+                        self.utxo_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .rewind(steps_back)
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
                     },
                     MmrTree::RangeProof => {
-                        if steps_back == 0 {
-                            self.range_proof_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            self.range_proof_mmr
-                                .write()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
+                        self.range_proof_mmr
+                            .write()
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                            .rewind(steps_back)
+                            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
                     },
                 },
                 WriteOperation::CreateMmrCheckpoint(tree) => match tree {
@@ -368,21 +373,30 @@ where D: Digest + Send + Sync
                         },
                         DbKeyValuePair::UnspentOutput(k, v, _) => {
                             let proof_hash = v.proof().hash();
-                            if let Some(index) = self
+                            // A UTXO without a range proof leaf would be missing from the UTXO set while its hash is
+                            // in the UTXO MMR, so the transaction is rejected (and the MMRs are reset by `write`)
+                            let index = self
                                 .range_proof_mmr
                                 .read()
                                 .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
                                 .find_leaf_index(&proof_hash)?
-                            {
-                                lmdb_insert(&txn, &self.utxos_db, &k, &v)?;
-                                lmdb_insert(&txn, &self.txos_hash_to_index_db, &k, &index)?;
-                            }
+                                .ok_or_else(|| {
+                                    ChainStorageError::UnexpectedResult(format!(
+                                        "The range proof of {} was not found in the range proof MMR",
+                                        DbKey::UnspentOutput(k.clone())
+                                    ))
+                                })?;
+                            lmdb_insert(&txn, &self.utxos_db, &k, &v)?;
+                            lmdb_insert(&txn, &self.txos_hash_to_index_db, &k, &index)?;
                         },
                         DbKeyValuePair::TransactionKernel(k, v, _) => {
                             lmdb_insert(&txn, &self.kernels_db, &k, &v)?;
                         },
                         DbKeyValuePair::OrphanBlock(k, v) => {
-                            lmdb_insert(&txn, &self.orphans_db, &k, &v)?;
+                            // Orphans are content-addressed, so inserting an orphan that is already stored is a no-op
+                            if !lmdb_exists(&self.env, &self.orphans_db, &k)? {
+                                lmdb_insert(&txn, &self.orphans_db, &k, &v)?;
+                            }
                         },
                     },
                     WriteOperation::Delete(delete) => match delete {
@@ -554,6 +568,10 @@ where D: Digest + Send + Sync
         Ok(result)
     }
 
+    fn contains_all(&self, keys: &[DbKey]) -> Result<Vec<bool>, ChainStorageError> {
+        keys.iter().map(|key| self.contains(key)).collect()
+    }
+
     fn fetch_mmr_root(&self, tree: MmrTree) -> Result<Vec<u8>, ChainStorageError> {
         let root = match tree {
             MmrTree::Utxo => self
@@ -628,8 +646,12 @@ where D: Digest + Send + Sync
         }
         if let MmrTree::Utxo = tree {
             for hash in deletions {
-                if let Some(index) = lmdb_get(&self.env, &self.txos_hash_to_index_db, &hash)? {
-                    pruned_mmr.delete(index);
+                // A deletion target that cannot be found usually indicates a bug in the caller, so it is not ignored
+                match lmdb_get(&self.env, &self.txos_hash_to_index_db, &hash)? {
+                    Some(index) => {
+                        pruned_mmr.delete(index);
+                    },
+                    None => return Err(ChainStorageError::ValueNotFound(DbKey::UnspentOutput(hash))),
                 }
             }
         }
@@ -668,6 +690,36 @@ where D: Digest + Send + Sync
         Ok(proof)
     }
 
+    fn fetch_block_kernel_proofs(
+        &self,
+        height: u64,
+    ) -> Result<Vec<(TransactionKernel, MerkleProof)>, ChainStorageError>
+    {
+        let checkpoint = self.fetch_mmr_checkpoint(MmrTree::Kernel, height)?;
+        let hashes = checkpoint.nodes_added();
+        let kernels: Vec<Option<TransactionKernel>> = lmdb_get_many(&self.env, &self.kernels_db, hashes)?;
+        let kernel_mmr = self
+            .kernel_mmr
+            .read()
+            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        hashes
+            .iter()
+            .zip(kernels)
+            .map(|(hash, kernel)| {
+                let kernel =
+                    kernel.ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::TransactionKernel(hash.clone())))?;
+                let index = kernel_mmr.find_leaf_index(hash)?.ok_or_else(|| {
+                    ChainStorageError::UnexpectedResult(format!(
+                        "The leaf node of {} was not found in the kernel MMR",
+                        DbKey::TransactionKernel(hash.clone())
+                    ))
+                })?;
+                let proof = MerkleProof::for_leaf_node(&kernel_mmr.mmr(), index)?;
+                Ok((kernel, proof))
+            })
+            .collect()
+    }
+
     fn fetch_mmr_checkpoint(&self, tree: MmrTree, height: u64) -> Result<MerkleCheckPoint, ChainStorageError> {
         let pruning_horizon = self.fetch_horizon_block_height()?;
         if height < pruning_horizon {
@@ -721,6 +773,36 @@ where D: Digest + Send + Sync
         Ok((hash, deleted))
     }
 
+    fn fetch_mmr_nodes(
+        &self,
+        tree: MmrTree,
+        start_pos: u32,
+        count: u32,
+    ) -> Result<Vec<(Vec<u8>, bool)>, ChainStorageError>
+    {
+        let mmr = match tree {
+            MmrTree::Kernel => &self.kernel_mmr,
+            MmrTree::Utxo => &self.utxo_mmr,
+            MmrTree::RangeProof => &self.range_proof_mmr,
+        }
+        .read()
+        .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        (start_pos..start_pos.saturating_add(count))
+            .map(|pos| {
+                let (hash, deleted) = mmr.get_leaf_status(pos)?;
+                let hash = hash
+                    .ok_or_else(|| {
+                        ChainStorageError::UnexpectedResult(format!(
+                            "The leaf node hash at position {} in the {} MMR tree was not found",
+                            pos, tree
+                        ))
+                    })?
+                    .clone();
+                Ok((hash, deleted))
+            })
+            .collect()
+    }
+
     fn fetch_mmr_base_leaf_nodes(
         &self,
         tree: MmrTree,
@@ -802,6 +884,40 @@ where D: Digest + Send + Sync
         Ok(mmr_state)
     }
 
+    fn pending_pruned_checkpoints(&self, tree: MmrTree) -> Result<usize, ChainStorageError> {
+        let count = match tree {
+            MmrTree::Kernel => self
+                .kernel_mmr
+                .read()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                .pending_pruned_checkpoints()?,
+            MmrTree::Utxo => self
+                .utxo_mmr
+                .read()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                .pending_pruned_checkpoints()?,
+            MmrTree::RangeProof => self
+                .range_proof_mmr
+                .read()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                .pending_pruned_checkpoints()?,
+        };
+        Ok(count)
+    }
+
+    fn fetch_deleted_leaf_indices(&self, tree: MmrTree) -> Result<Vec<u32>, ChainStorageError> {
+        let indices = match tree {
+            MmrTree::Utxo => self
+                .utxo_mmr
+                .read()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
+                .deleted()
+                .to_vec(),
+            MmrTree::Kernel | MmrTree::RangeProof => Vec::new(),
+        };
+        Ok(indices)
+    }
+
     fn assign_mmr(&self, tree: MmrTree, base_state: MutableMmrLeafNodes) -> Result<(), ChainStorageError> {
         match tree {
             MmrTree::Kernel => self
@@ -829,6 +945,79 @@ where D: Digest + Send + Sync
         lmdb_for_each::<F, HashOutput, Block>(&self.env, &self.orphans_db, f)
     }
 
+    /// Iterate over all the stored unspent outputs and execute the function `f` for each output.
+    fn for_each_utxo<F>(&self, f: F) -> Result<(), ChainStorageError>
+    where F: FnMut(Result<(HashOutput, TransactionOutput), ChainStorageError>) {
+        lmdb_for_each::<F, HashOutput, TransactionOutput>(&self.env, &self.utxos_db, f)
+    }
+
+    fn fetch_counts(&self) -> Result<ChainCounts, ChainStorageError> {
+        Ok(ChainCounts {
+            headers: lmdb_len(&self.env, &self.headers_db)?,
+            utxos: lmdb_len(&self.env, &self.utxos_db)?,
+            stxos: lmdb_len(&self.env, &self.stxos_db)?,
+            kernels: lmdb_len(&self.env, &self.kernels_db)?,
+            orphans: lmdb_len(&self.env, &self.orphans_db)?,
+        })
+    }
+
+    fn fetch_utxos_paged(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(HashOutput, TransactionOutput)>, ChainStorageError>
+    {
+        let mut utxos = Vec::new();
+        let mut result = Ok(());
+        lmdb_for_each::<_, HashOutput, TransactionOutput>(&self.env, &self.utxos_db, |pair| match pair {
+            Ok(utxo) => utxos.push(utxo),
+            Err(e) => result = Err(e),
+        })?;
+        result?;
+
+        let hashes = utxos.iter().map(|(hash, _)| hash.clone()).collect::<Vec<_>>();
+        let indices: Vec<Option<usize>> = lmdb_get_many(&self.env, &self.txos_hash_to_index_db, &hashes)?;
+        let mut indexed_utxos = utxos
+            .into_iter()
+            .zip(indices)
+            .map(|((hash, utxo), index)| {
+                let index = index.ok_or_else(|| {
+                    ChainStorageError::CorruptedDatabase(format!("No MMR leaf index stored for UTXO {}", hash.to_hex()))
+                })?;
+                Ok((index, hash, utxo))
+            })
+            .collect::<Result<Vec<_>, ChainStorageError>>()?;
+        indexed_utxos.sort_by_key(|(index, _, _)| *index);
+        Ok(indexed_utxos
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(_, hash, utxo)| (hash, utxo))
+            .collect())
+    }
+
+    fn prune_orphans_below(&self, height: u64) -> Result<usize, ChainStorageError> {
+        let mut keys = Vec::new();
+        let mut result = Ok(());
+        lmdb_for_each::<_, HashOutput, Block>(&self.env, &self.orphans_db, |pair| match pair {
+            Ok((key, block)) => {
+                if block.header.height < height {
+                    keys.push(key);
+                }
+            },
+            Err(e) => result = Err(e),
+        })?;
+        result?;
+
+        let txn = WriteTransaction::new(self.env.clone()).map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        for key in keys.iter() {
+            lmdb_delete(&txn, &self.orphans_db, key)?;
+        }
+        txn.commit()
+            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        Ok(keys.len())
+    }
+
     fn fetch_horizon_block_height(&self) -> Result<u64, ChainStorageError> {
         let tip_height = lmdb_len(&self.env, &self.headers_db)?;
         let checkpoint_count = self
@@ -836,7 +1025,8 @@ where D: Digest + Send + Sync
             .read()
             .map_err(|e| ChainStorageError::AccessError(e.to_string()))?
             .checkpoint_count()?;
-        Ok((tip_height - checkpoint_count) as u64)
+        // Checkpoints can be created before their headers are stored, so the horizon cannot be below the genesis block
+        Ok(tip_height.saturating_sub(checkpoint_count) as u64)
     }
 
     fn fetch_last_header(&self) -> Result<Option<BlockHeader>, ChainStorageError> {
@@ -848,4 +1038,50 @@ where D: Digest + Send + Sync
             Ok(None)
         }
     }
+
+    fn fetch_last_n_headers(&self, n: usize) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        let header_count = lmdb_len(&self.env, &self.headers_db)? as u64;
+        let heights = (header_count.saturating_sub(n as u64)..header_count)
+            .rev()
+            .collect::<Vec<_>>();
+        let headers: Vec<Option<BlockHeader>> = lmdb_get_many(&self.env, &self.headers_db, &heights)?;
+        heights
+            .into_iter()
+            .zip(headers)
+            .map(|(height, header)| header.ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::BlockHeader(height))))
+            .collect()
+    }
+
+    fn fetch_headers(&self, start: u64, end_inclusive: u64) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        if start > end_inclusive {
+            return Err(ChainStorageError::InvalidQuery(
+                "The start height of the header range is greater than the end height".to_string(),
+            ));
+        }
+        let heights = (start..=end_inclusive).collect::<Vec<_>>();
+        let headers: Vec<Option<BlockHeader>> = lmdb_get_many(&self.env, &self.headers_db, &heights)?;
+        heights
+            .into_iter()
+            .zip(headers)
+            .map(|(height, header)| header.ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::BlockHeader(height))))
+            .collect()
+    }
+
+    fn fetch_utxo_with_position(
+        &self,
+        hash: &HashOutput,
+    ) -> Result<Option<(TransactionOutput, usize)>, ChainStorageError>
+    {
+        let utxo: Option<TransactionOutput> = lmdb_get(&self.env, &self.utxos_db, hash)?;
+        match utxo {
+            Some(utxo) => {
+                let index: Option<usize> = lmdb_get(&self.env, &self.txos_hash_to_index_db, hash)?;
+                let index = index.ok_or_else(|| {
+                    ChainStorageError::CorruptedDatabase(format!("No MMR leaf index stored for UTXO {}", hash.to_hex()))
+                })?;
+                Ok(Some((utxo, index)))
+            },
+            None => Ok(None),
+        }
+    }
 }
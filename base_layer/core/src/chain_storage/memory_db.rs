@@ -25,15 +25,35 @@
 use crate::{
     blocks::{Block, BlockHeader},
     chain_storage::{
-        blockchain_database::{BlockchainBackend, MutableMmrState},
-        db_transaction::{DbKey, DbKeyValuePair, DbTransaction, DbValue, MetadataValue, MmrTree, WriteOperation},
+        blockchain_database::{BlockchainBackend, ChainCounts, MutableMmrState},
+        db_transaction::{
+            DbKey,
+            DbKeyValuePair,
+            DbTransaction,
+            DbValue,
+            MetadataKey,
+            MetadataValue,
+            MmrTree,
+            WriteOperation,
+        },
         error::ChainStorageError,
     },
 };
 use digest::Digest;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    fmt,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+        RwLock,
+        RwLockReadGuard,
+        RwLockWriteGuard,
+    },
+    time::{Duration, Instant},
 };
 use tari_mmr::{
     functions::prune_mutable_mmr,
@@ -45,20 +65,71 @@ use tari_mmr::{
     MutableMmr,
     MutableMmrLeafNodes,
 };
+use tari_storage::IterationResult;
 use tari_transactions::{
     transaction::{TransactionKernel, TransactionOutput},
     types::HashOutput,
 };
 use tari_utilities::hash::Hashable;
+use tokio::sync::watch;
 
 /// A generic struct for storing node objects in the BlockchainDB that also form part of an MMR. The index field makes
 /// reverse lookups (find by hash) possible.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct MerkleNode<T> {
     index: usize,
     value: T,
 }
 
+/// A hook that transforms a hash before it is pushed into an MMR
+pub type LeafTransform = Arc<dyn Fn(&HashOutput) -> HashOutput + Send + Sync>;
+
+/// Configuration for a [MemoryDatabase](struct.MemoryDatabase.html)
+#[derive(Clone)]
+pub struct MemoryDatabaseConfig {
+    /// The change tracker configuration used for all the MMRs
+    pub mct_config: MerkleChangeTrackerConfig,
+    /// An optional transform that is applied to every hash before it is pushed into an MMR. If `None`, hashes are
+    /// pushed as is. This is an advanced testing hook for experimenting with alternative commitment schemes.
+    pub leaf_transform: Option<LeafTransform>,
+}
+
+impl MemoryDatabaseConfig {
+    pub fn new(mct_config: MerkleChangeTrackerConfig) -> Self {
+        Self {
+            mct_config,
+            leaf_transform: None,
+        }
+    }
+}
+
+impl fmt::Debug for MemoryDatabaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryDatabaseConfig")
+            .field("mct_config", &self.mct_config)
+            .field("leaf_transform", &self.leaf_transform.as_ref().map(|_| "<transform>"))
+            .finish()
+    }
+}
+
+#[derive(Default, Clone)]
+struct MmrLeafTransform(Option<LeafTransform>);
+
+impl MmrLeafTransform {
+    fn apply(&self, hash: &HashOutput) -> HashOutput {
+        match &self.0 {
+            Some(transform) => transform(hash),
+            None => hash.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for MmrLeafTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", if self.0.is_some() { "Some(<transform>)" } else { "None" })
+    }
+}
+
 #[derive(Debug)]
 struct InnerDatabase<D>
 where D: Digest
@@ -74,6 +145,104 @@ where D: Digest
     utxo_mmr: MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>,
     kernel_mmr: MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>,
     range_proof_mmr: MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>,
+    leaf_transform: MmrLeafTransform,
+    // Failure injection for tests: pushes to this MMR fail
+    #[cfg(test)]
+    failing_mmr: Option<MmrTree>,
+}
+
+/// A serializable snapshot of the contents of a [MemoryDatabase](struct.MemoryDatabase.html). It contains the
+/// headers, UTXOs, STXOs, kernels, orphans and metadata, as well as the leaf nodes and deletion bitmaps of the MMRs.
+/// MMR checkpoints are not included; the MMRs of an imported snapshot start from the exported state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbSnapshot {
+    metadata: Vec<(u32, MetadataValue)>,
+    headers: Vec<(u64, BlockHeader)>,
+    utxos: Vec<(HashOutput, MerkleNode<TransactionOutput>)>,
+    stxos: Vec<(HashOutput, MerkleNode<TransactionOutput>)>,
+    kernels: Vec<(HashOutput, TransactionKernel)>,
+    orphans: Vec<(HashOutput, Block)>,
+    utxo_mmr: MutableMmrLeafNodes,
+    kernel_mmr: MutableMmrLeafNodes,
+    range_proof_mmr: MutableMmrLeafNodes,
+}
+
+/// A snapshot of the write counters of a [MemoryDatabase](struct.MemoryDatabase.html)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// The number of `DbTransaction`s that have been written
+    pub transactions: u64,
+    /// The number of `WriteOperation`s that have been applied
+    pub operations: u64,
+}
+
+#[derive(Default, Debug)]
+struct WriteCounters {
+    transactions: AtomicU64,
+    operations: AtomicU64,
+}
+
+/// The height and hash of the chain tip
+type ChainTip = (u64, HashOutput);
+
+/// The source of the current time used to record chain tip updates
+type Clock = Arc<dyn Fn() -> Instant + Send + Sync>;
+
+/// Publishes the chain tip to the receivers handed out by [MemoryDatabase::tip_watch] and records when it last changed
+struct TipWatch {
+    sender: Mutex<watch::Sender<ChainTip>>,
+    receiver: watch::Receiver<ChainTip>,
+    updated_at: Mutex<Option<Instant>>,
+    clock: Clock,
+}
+
+impl TipWatch {
+    fn with_clock(clock: Clock) -> Self {
+        let (sender, receiver) = watch::channel((0, Vec::new()));
+        Self {
+            sender: Mutex::new(sender),
+            receiver,
+            updated_at: Mutex::new(None),
+            clock,
+        }
+    }
+
+    fn update(&self, tip: ChainTip) {
+        if *self.receiver.get_ref() == tip {
+            return;
+        }
+        if let Ok(mut updated_at) = self.updated_at.lock() {
+            *updated_at = Some((self.clock)());
+        }
+        if let Ok(mut sender) = self.sender.lock() {
+            // The watch holds a receiver, so the broadcast cannot fail because there are no receivers
+            let _ = sender.broadcast(tip);
+        }
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        let updated_at = (*self.updated_at.lock().ok()?)?;
+        Some((self.clock)().saturating_duration_since(updated_at))
+    }
+}
+
+impl Default for TipWatch {
+    fn default() -> Self {
+        Self::with_clock(Arc::new(Instant::now))
+    }
+}
+
+impl fmt::Debug for TipWatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("TipWatch");
+        s.field("tip", &*self.receiver.get_ref());
+        // Formatting must never panic or block, so a contended or poisoned lock is shown as a placeholder
+        match self.updated_at.try_lock() {
+            Ok(updated_at) => s.field("updated_at", &*updated_at),
+            Err(_) => s.field("updated_at", &"<locked>"),
+        };
+        s.finish()
+    }
 }
 
 /// A memory-backed blockchain database. The data is stored in RAM; and so all data will be lost when the program
@@ -84,21 +253,86 @@ pub struct MemoryDatabase<D>
 where D: Digest
 {
     db: Arc<RwLock<InnerDatabase<D>>>,
+    write_counters: Arc<WriteCounters>,
+    tip_watch: Arc<TipWatch>,
 }
 
 impl<D> MemoryDatabase<D>
 where D: Digest
 {
     pub fn new(mct_config: MerkleChangeTrackerConfig) -> Self {
+        Self::new_with_metadata(mct_config, HashMap::default())
+    }
+
+    /// Creates a new memory database using the given configuration
+    pub fn new_with_config(config: MemoryDatabaseConfig) -> Self {
+        Self::create(config, HashMap::default())
+    }
+
+    /// Creates a new memory database with a separate change tracker configuration for each of the MMRs
+    pub fn with_configs(
+        utxo: MerkleChangeTrackerConfig,
+        kernel: MerkleChangeTrackerConfig,
+        range_proof: MerkleChangeTrackerConfig,
+    ) -> Self
+    {
+        Self::create_with_mct_configs(utxo, kernel, range_proof, None, HashMap::default())
+    }
+
+    /// Creates a new memory database with its metadata seeded from the given metadata map. This is handy for tests
+    /// that need to start from a specific chain tip.
+    pub fn new_with_metadata(
+        mct_config: MerkleChangeTrackerConfig,
+        metadata: HashMap<MetadataKey, MetadataValue>,
+    ) -> Self
+    {
+        Self::create(MemoryDatabaseConfig::new(mct_config), metadata)
+    }
+
+    /// Creates a new memory database that contains the given genesis block. The genesis header, coinbase UTXOs and
+    /// kernels are inserted and added to their MMRs, the initial MMR checkpoint is created and the chain metadata
+    /// points at the genesis block as the chain tip.
+    pub fn with_genesis(mct_config: MerkleChangeTrackerConfig, genesis: Block) -> Result<Self, ChainStorageError> {
+        let mut metadata = HashMap::new();
+        metadata.insert(MetadataKey::ChainHeight, MetadataValue::ChainHeight(Some(0)));
+        metadata.insert(MetadataKey::BestBlock, MetadataValue::BestBlock(Some(genesis.hash())));
+        let db = Self::new_with_metadata(mct_config, metadata);
+        let (header, _, outputs, kernels) = genesis.dissolve();
+        let mut txn = DbTransaction::new();
+        txn.insert_header(header);
+        for utxo in outputs {
+            txn.insert_utxo(utxo, true);
+        }
+        for kernel in kernels {
+            txn.insert_kernel(kernel, true);
+        }
+        txn.commit_block();
+        db.write(txn)?;
+        Ok(db)
+    }
+
+    fn create(config: MemoryDatabaseConfig, metadata: HashMap<MetadataKey, MetadataValue>) -> Self {
+        let mct_config = config.mct_config;
+        Self::create_with_mct_configs(mct_config, mct_config, mct_config, config.leaf_transform, metadata)
+    }
+
+    fn create_with_mct_configs(
+        utxo_config: MerkleChangeTrackerConfig,
+        kernel_config: MerkleChangeTrackerConfig,
+        range_proof_config: MerkleChangeTrackerConfig,
+        leaf_transform: Option<LeafTransform>,
+        metadata: HashMap<MetadataKey, MetadataValue>,
+    ) -> Self
+    {
         let utxo_mmr =
-            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), mct_config).unwrap();
+            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), utxo_config).unwrap();
         let kernel_mmr =
-            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), mct_config).unwrap();
+            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), kernel_config).unwrap();
         let range_proof_mmr =
-            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), mct_config).unwrap();
+            MerkleChangeTracker::<D, _, _>::new(MutableMmr::new(Vec::new()), Vec::new(), range_proof_config).unwrap();
         Self {
             db: Arc::new(RwLock::new(InnerDatabase {
-                metadata: HashMap::default(),
+                metadata: metadata.into_iter().map(|(k, v)| (k as u32, v)).collect(),
                 headers: HashMap::default(),
                 block_hashes: HashMap::default(),
                 utxos: HashMap::default(),
@@ -108,8 +342,261 @@ where D: Digest
                 utxo_mmr,
                 kernel_mmr,
                 range_proof_mmr,
+                leaf_transform: MmrLeafTransform(leaf_transform),
+                #[cfg(test)]
+                failing_mmr: None,
             })),
+            write_counters: Arc::new(WriteCounters::default()),
+            tip_watch: Arc::new(TipWatch::default()),
+        }
+    }
+
+    /// Exports the contents of this database to a [DbSnapshot](struct.DbSnapshot.html) that can be serialized and
+    /// later restored with [import_snapshot](#method.import_snapshot).
+    pub fn export_snapshot(&self) -> Result<DbSnapshot, ChainStorageError> {
+        let db = self.db_access()?;
+        Ok(DbSnapshot {
+            metadata: db.metadata.iter().map(|(k, v)| (*k, v.clone())).collect(),
+            headers: db.headers.iter().map(|(k, v)| (*k, v.clone())).collect(),
+            utxos: db.utxos.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            stxos: db.stxos.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            kernels: db.kernels.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            orphans: db.orphans.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            utxo_mmr: mmr_leaf_nodes(&db.utxo_mmr)?,
+            kernel_mmr: mmr_leaf_nodes(&db.kernel_mmr)?,
+            range_proof_mmr: mmr_leaf_nodes(&db.range_proof_mmr)?,
+        })
+    }
+
+    /// Creates a new memory database from a snapshot produced by [export_snapshot](#method.export_snapshot). The
+    /// block hash index is rebuilt from the headers and the MMRs are assigned the exported leaf nodes, so their roots
+    /// match those of the exported database.
+    pub fn import_snapshot(
+        mct_config: MerkleChangeTrackerConfig,
+        snapshot: DbSnapshot,
+    ) -> Result<Self, ChainStorageError>
+    {
+        let db = Self::new(mct_config);
+        {
+            let mut inner = db
+                .db
+                .write()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+            inner.metadata = snapshot.metadata.into_iter().collect();
+            inner.block_hashes = snapshot.headers.iter().map(|(k, v)| (v.hash(), *k)).collect();
+            inner.headers = snapshot.headers.into_iter().collect();
+            inner.utxos = snapshot.utxos.into_iter().collect();
+            inner.stxos = snapshot.stxos.into_iter().collect();
+            inner.kernels = snapshot.kernels.into_iter().collect();
+            inner.orphans = snapshot.orphans.into_iter().collect();
+            inner.utxo_mmr.assign(snapshot.utxo_mmr)?;
+            inner.kernel_mmr.assign(snapshot.kernel_mmr)?;
+            inner.range_proof_mmr.assign(snapshot.range_proof_mmr)?;
+            db.tip_watch.update(chain_tip(&inner));
+        }
+        Ok(db)
+    }
+
+    /// Creates a copy of this database that shares no state with the original, so that the two can diverge. In
+    /// contrast, `clone` returns another handle to the same database. The copy starts with fresh write counters and
+    /// its own chain tip watch.
+    pub fn deep_clone(&self) -> Result<Self, ChainStorageError> {
+        let inner = (*self.db_access()?).clone();
+        let tip_watch = TipWatch::with_clock(self.tip_watch.clock.clone());
+        tip_watch.update(chain_tip(&inner));
+        Ok(Self {
+            db: Arc::new(RwLock::new(inner)),
+            write_counters: Arc::new(WriteCounters::default()),
+            tip_watch: Arc::new(tip_watch),
+        })
+    }
+
+    /// Replaces the state of the given MMR with `base_state`, as received during horizon sync. When replacing the UTXO
+    /// MMR, `outputs` must contain the output of every leaf node, in leaf order. The UTXO and STXO sets are then
+    /// replaced so that they match the deletion bitmap of the new state. The range proof of every output must already
+    /// be in the range proof MMR, so the range proof MMR must be replaced first. Outputs may only be provided for the
+    /// UTXO MMR. The MMR and output sets are replaced in a single locked operation; if the outputs do not match the
+    /// leaf nodes, the database is left unchanged.
+    pub fn fast_sync_replace(
+        &self,
+        tree: MmrTree,
+        base_state: MutableMmrLeafNodes,
+        outputs: Vec<TransactionOutput>,
+    ) -> Result<(), ChainStorageError>
+    {
+        if tree != MmrTree::Utxo && !outputs.is_empty() {
+            return Err(ChainStorageError::InvalidOperation(format!(
+                "Outputs can only be provided when replacing the UTXO MMR, not the {} MMR",
+                tree
+            )));
+        }
+        let mut db = self
+            .db
+            .write()
+            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        match tree {
+            MmrTree::Kernel => db.kernel_mmr.assign(base_state)?,
+            MmrTree::RangeProof => db.range_proof_mmr.assign(base_state)?,
+            MmrTree::Utxo => {
+                if outputs.len() != base_state.leaf_hashes.len() {
+                    return Err(ChainStorageError::InvalidOperation(format!(
+                        "{} outputs were provided for {} UTXO MMR leaf nodes",
+                        outputs.len(),
+                        base_state.leaf_hashes.len()
+                    )));
+                }
+                let mut utxos = HashMap::new();
+                let mut stxos = HashMap::new();
+                for (index, (output, leaf_hash)) in outputs.into_iter().zip(base_state.leaf_hashes.iter()).enumerate() {
+                    let hash = output.hash();
+                    if db.leaf_transform.apply(&hash) != *leaf_hash {
+                        return Err(ChainStorageError::InvalidOperation(format!(
+                            "The output at position {} does not match the UTXO MMR leaf node hash",
+                            index
+                        )));
+                    }
+                    // Like every other output node, the node records the leaf index of the output's range proof
+                    let proof_hash = db.leaf_transform.apply(&output.proof().hash());
+                    let proof_index = db.range_proof_mmr.find_leaf_index(&proof_hash)?.ok_or_else(|| {
+                        ChainStorageError::InvalidOperation(format!(
+                            "The range proof of the output at position {} is not in the range proof MMR",
+                            index
+                        ))
+                    })?;
+                    let node = MerkleNode {
+                        index: proof_index,
+                        value: output,
+                    };
+                    if base_state.deleted.contains(index as u32) {
+                        stxos.insert(hash, node);
+                    } else {
+                        utxos.insert(hash, node);
+                    }
+                }
+                db.utxo_mmr.assign(base_state)?;
+                db.utxos = utxos;
+                db.stxos = stxos;
+            },
+        };
+        Ok(())
+    }
+
+    /// Returns a rough estimate of the memory used by this database, in bytes. The number of headers, UTXOs, STXOs,
+    /// kernels and orphans are multiplied by the size of their entries and every MMR leaf node is counted as a
+    /// 32-byte hash. Heap allocations of the entries (e.g. the transactions of orphan blocks) are not accounted for,
+    /// but the estimate only grows as data is added.
+    pub fn approx_memory_bytes(&self) -> Result<usize, ChainStorageError> {
+        const HASH_SIZE: usize = 32;
+        let db = self.db_access()?;
+        // A header is stored by height and indexed by its hash
+        let header_size = mem::size_of::<(u64, BlockHeader)>() + mem::size_of::<(HashOutput, u64)>();
+        let output_size = mem::size_of::<(HashOutput, MerkleNode<TransactionOutput>)>();
+        let kernel_size = mem::size_of::<(HashOutput, TransactionKernel)>();
+        let orphan_size = mem::size_of::<(HashOutput, Block)>();
+        let mmr_leaf_count =
+            db.utxo_mmr.get_leaf_count() + db.kernel_mmr.get_leaf_count() + db.range_proof_mmr.get_leaf_count();
+        Ok(db.headers.len() * header_size +
+            (db.utxos.len() + db.stxos.len()) * output_size +
+            db.kernels.len() * kernel_size +
+            db.orphans.len() * orphan_size +
+            mmr_leaf_count * HASH_SIZE)
+    }
+
+    /// Returns the number of write calls made and the number of write operations applied to this database so far.
+    /// Clones of this database share the same counters.
+    pub fn write_stats(&self) -> WriteStats {
+        WriteStats {
+            transactions: self.write_counters.transactions.load(Ordering::SeqCst),
+            operations: self.write_counters.operations.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Checks that the UTXO and STXO sets are in sync with the UTXO MMR. The number of UTXOs must equal the number of
+    /// live (not deleted) leaf nodes in the UTXO MMR and the number of STXOs must equal the number of deleted leaf
+    /// nodes. A `CorruptedDatabase` error describing the mismatch is returned if this is not the case.
+    pub fn assert_utxo_consistency(&self) -> Result<(), ChainStorageError> {
+        let db = self.db_access()?;
+        let live_leaf_count = db.utxo_mmr.len() as usize;
+        let deleted_leaf_count = db.utxo_mmr.get_leaf_count() - live_leaf_count;
+        if db.utxos.len() != live_leaf_count {
+            return Err(ChainStorageError::CorruptedDatabase(format!(
+                "The UTXO set contains {} entries, but the UTXO MMR contains {} live leaf nodes",
+                db.utxos.len(),
+                live_leaf_count
+            )));
+        }
+        if db.stxos.len() != deleted_leaf_count {
+            return Err(ChainStorageError::CorruptedDatabase(format!(
+                "The STXO set contains {} entries, but the UTXO MMR contains {} deleted leaf nodes",
+                db.stxos.len(),
+                deleted_leaf_count
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the block hash index from the stored block headers. The existing index is discarded and every stored
+    /// header is re-hashed. This is a recovery tool for when the index has drifted from the stored headers. The number
+    /// of index entries rebuilt is returned.
+    pub fn rebuild_block_hash_index(&self) -> Result<usize, ChainStorageError> {
+        let mut db = self
+            .db
+            .write()
+            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        let block_hashes: HashMap<_, _> = db.headers.iter().map(|(k, v)| (v.hash(), *k)).collect();
+        let count = block_hashes.len();
+        db.block_hashes = block_hashes;
+        Ok(count)
+    }
+
+    /// Calls `f` with each stored header, starting at the tip and moving down to the genesis block. The iteration stops
+    /// as soon as `f` returns `IterationResult::Break`. The database read lock is held for the duration of the
+    /// iteration, so `f` must not access this database.
+    pub fn for_each_header_desc<F>(&self, mut f: F) -> Result<(), ChainStorageError>
+    where F: FnMut(BlockHeader) -> IterationResult {
+        let db = self.db_access()?;
+        for height in (0..db.headers.len() as u64).rev() {
+            let header = db
+                .headers
+                .get(&height)
+                .cloned()
+                .ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::BlockHeader(height)))?;
+            if let IterationResult::Break = f(header) {
+                break;
+            }
         }
+        Ok(())
+    }
+
+    /// Returns the orphan blocks that can be connected to the chain, i.e. the orphans whose parent is the block with
+    /// the given tip hash or any other block with a stored header. The orphans are sorted by height. This is used to
+    /// resolve orphans after a block has been added.
+    pub fn fetch_connectable_orphans(&self, new_tip_hash: &HashOutput) -> Result<Vec<Block>, ChainStorageError> {
+        let db = self.db_access()?;
+        let mut orphans = db
+            .orphans
+            .values()
+            .filter(|orphan| {
+                orphan.header.prev_hash == *new_tip_hash || db.block_hashes.contains_key(&orphan.header.prev_hash)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        orphans.sort_by_key(|orphan| orphan.header.height);
+        Ok(orphans)
+    }
+
+    /// Returns a watch channel receiver that is updated with the height and hash of the chain tip whenever a header is
+    /// inserted or removed. The tip is `(0, [])` while no headers are stored. Clones of this database share the same
+    /// watch channel.
+    pub fn tip_watch(&self) -> watch::Receiver<ChainTip> {
+        self.tip_watch.receiver.clone()
+    }
+
+    /// Returns how long ago the chain tip last changed, i.e. since a header insert or removal moved the tip. A
+    /// monitor can use this to detect a stalled chain. Returns `None` if the tip has not changed since the database
+    /// was created.
+    pub fn time_since_tip_update(&self) -> Option<Duration> {
+        self.tip_watch.elapsed()
     }
 
     pub(self) fn db_access(&self) -> Result<RwLockReadGuard<InnerDatabase<D>>, ChainStorageError> {
@@ -127,145 +614,34 @@ where D: Digest + Send + Sync
             .db
             .write()
             .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-        // Not **really** atomic, but..
-        // Hashmap insertions don't typically fail and b) MemoryDB should not be used for production anyway.
-        for op in tx.operations.into_iter() {
-            match op {
-                WriteOperation::Insert(insert) => match insert {
-                    DbKeyValuePair::Metadata(k, v) => {
-                        let key = k as u32;
-                        if db.metadata.contains_key(&key) {
-                            return Err(ChainStorageError::InvalidOperation("Duplicate key".to_string()));
-                        }
-                        db.metadata.insert(key, v);
-                    },
-                    DbKeyValuePair::BlockHeader(k, v) => {
-                        if db.headers.contains_key(&k) {
-                            return Err(ChainStorageError::InvalidOperation("Duplicate key".to_string()));
-                        }
-                        db.block_hashes.insert(v.hash(), k);
-                        db.headers.insert(k, *v);
-                    },
-                    DbKeyValuePair::UnspentOutput(k, v, update_mmr) => {
-                        if db.utxos.contains_key(&k) {
-                            return Err(ChainStorageError::InvalidOperation("Duplicate key".to_string()));
-                        }
-                        let proof_hash = v.proof().hash();
-                        if update_mmr {
-                            db.utxo_mmr.push(&k)?;
-                            db.range_proof_mmr.push(&proof_hash)?;
-                        }
-                        if let Some(index) = db.range_proof_mmr.find_leaf_index(&proof_hash)? {
-                            let v = MerkleNode { index, value: *v };
-                            db.utxos.insert(k, v);
-                        }
-                    },
-                    DbKeyValuePair::TransactionKernel(k, v, update_mmr) => {
-                        if db.kernels.contains_key(&k) {
-                            return Err(ChainStorageError::InvalidOperation("Duplicate key".to_string()));
-                        }
-                        if update_mmr {
-                            db.kernel_mmr.push(&k)?;
-                        }
-                        db.kernels.insert(k, *v);
-                    },
-                    DbKeyValuePair::OrphanBlock(k, v) => {
-                        if db.orphans.contains_key(&k) {
-                            return Err(ChainStorageError::InvalidOperation("Duplicate key".to_string()));
-                        }
-                        db.orphans.insert(k, *v);
-                    },
-                },
-                WriteOperation::Delete(delete) => match delete {
-                    DbKey::Metadata(_) => {}, // no-op
-                    DbKey::BlockHeader(k) => {
-                        db.headers.remove(&k).and_then(|v| db.block_hashes.remove(&v.hash()));
-                    },
-                    DbKey::BlockHash(hash) => {
-                        db.block_hashes.remove(&hash).and_then(|i| db.headers.remove(&i));
-                    },
-                    DbKey::UnspentOutput(k) => {
-                        db.utxos.remove(&k);
-                    },
-                    DbKey::SpentOutput(k) => {
-                        db.stxos.remove(&k);
-                    },
-                    DbKey::TransactionKernel(k) => {
-                        db.kernels.remove(&k);
-                    },
-                    DbKey::OrphanBlock(k) => {
-                        db.orphans.remove(&k);
-                    },
-                },
-                WriteOperation::Spend(key) => match key {
-                    DbKey::UnspentOutput(hash) => {
-                        let moved = spend_utxo(&mut db, hash);
-                        if !moved {
-                            return Err(ChainStorageError::UnspendableInput);
-                        }
-                    },
-                    _ => return Err(ChainStorageError::InvalidOperation("Only UTXOs can be spent".into())),
-                },
-                WriteOperation::UnSpend(key) => match key {
-                    DbKey::SpentOutput(hash) => {
-                        let moved = unspend_stxo(&mut db, hash);
-                        if !moved {
-                            return Err(ChainStorageError::UnspendError);
-                        }
-                    },
-                    _ => return Err(ChainStorageError::InvalidOperation("Only STXOs can be unspent".into())),
-                },
-                WriteOperation::CreateMmrCheckpoint(tree) => match tree {
-                    MmrTree::Kernel => db
-                        .kernel_mmr
-                        .commit()
-                        .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
-                    MmrTree::Utxo => db
-                        .utxo_mmr
-                        .commit()
-                        .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
-                    MmrTree::RangeProof => db
-                        .range_proof_mmr
-                        .commit()
-                        .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
-                },
-                WriteOperation::RewindMmr(tree, steps_back) => match tree {
-                    MmrTree::Kernel => {
-                        if steps_back == 0 {
-                            db.kernel_mmr
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            db.kernel_mmr
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
-                    },
-                    MmrTree::Utxo => {
-                        if steps_back == 0 {
-                            db.utxo_mmr
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            db.utxo_mmr
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
-                    },
-                    MmrTree::RangeProof => {
-                        if steps_back == 0 {
-                            db.range_proof_mmr
-                                .reset()
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        } else {
-                            db.range_proof_mmr
-                                .rewind(steps_back)
-                                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
-                        }
-                    },
-                },
+        self.write_counters.transactions.fetch_add(1, Ordering::SeqCst);
+        // The operations are applied in place. The previous values of the entries changed by each operation, and the
+        // state of each MMR the transaction touches, are recorded so that they can be restored if any operation
+        // fails. This leaves the database in its pre-transaction state, as is the case for LMDB.
+        let mut rollback = WriteRollback::default();
+        let num_operations = tx.operations.len();
+        let updates_headers = tx.operations.iter().any(|op| match op {
+            WriteOperation::Insert(DbKeyValuePair::BlockHeader(_, _)) |
+            WriteOperation::Delete(DbKey::BlockHeader(_)) |
+            WriteOperation::Delete(DbKey::BlockHash(_)) => true,
+            _ => false,
+        });
+        for (index, op) in tx.operations.into_iter().enumerate() {
+            rollback.record(&db, &op);
+            if let Err(e) = apply_write_operation(&mut db, op) {
+                rollback.restore(&mut db);
+                return Err(ChainStorageError::OperationFailed {
+                    index,
+                    source: Box::new(e),
+                });
             }
         }
+        self.write_counters
+            .operations
+            .fetch_add(num_operations as u64, Ordering::SeqCst);
+        if updates_headers {
+            self.tip_watch.update(chain_tip(&db));
+        }
         Ok(())
     }
 
@@ -298,16 +674,12 @@ where D: Digest + Send + Sync
 
     fn contains(&self, key: &DbKey) -> Result<bool, ChainStorageError> {
         let db = self.db_access()?;
-        let result = match key {
-            DbKey::Metadata(_) => true,
-            DbKey::BlockHeader(k) => db.headers.contains_key(k),
-            DbKey::BlockHash(h) => db.block_hashes.contains_key(h),
-            DbKey::UnspentOutput(k) => db.utxos.contains_key(k),
-            DbKey::SpentOutput(k) => db.stxos.contains_key(k),
-            DbKey::TransactionKernel(k) => db.kernels.contains_key(k),
-            DbKey::OrphanBlock(k) => db.orphans.contains_key(k),
-        };
-        Ok(result)
+        Ok(contains_key(&db, key))
+    }
+
+    fn contains_all(&self, keys: &[DbKey]) -> Result<Vec<bool>, ChainStorageError> {
+        let db = self.db_access()?;
+        Ok(keys.iter().map(|key| contains_key(&db, key)).collect())
     }
 
     fn fetch_mmr_root(&self, tree: MmrTree) -> Result<Vec<u8>, ChainStorageError> {
@@ -344,14 +716,18 @@ where D: Digest + Send + Sync
             MmrTree::RangeProof => prune_mutable_mmr(&db.range_proof_mmr)?,
         };
         for hash in additions {
-            pruned_mmr.push(&hash)?;
+            pruned_mmr.push(&db.leaf_transform.apply(&hash))?;
         }
         if let MmrTree::Utxo = tree {
-            deletions.iter().for_each(|hash| {
-                if let Some(node) = db.utxos.get(hash) {
-                    pruned_mmr.delete(node.index as u32);
+            for hash in deletions {
+                // A deletion target that cannot be found usually indicates a bug in the caller, so it is not ignored
+                match db.utxos.get(&hash) {
+                    Some(node) => {
+                        pruned_mmr.delete(node.index as u32);
+                    },
+                    None => return Err(ChainStorageError::ValueNotFound(DbKey::UnspentOutput(hash))),
                 }
-            })
+            }
         }
         Ok(pruned_mmr.get_merkle_root()?)
     }
@@ -368,6 +744,38 @@ where D: Digest + Send + Sync
         Ok(proof)
     }
 
+    fn fetch_block_kernel_proofs(
+        &self,
+        height: u64,
+    ) -> Result<Vec<(TransactionKernel, MerkleProof)>, ChainStorageError>
+    {
+        let checkpoint = self.fetch_mmr_checkpoint(MmrTree::Kernel, height)?;
+        let db = self.db_access()?;
+        // The checkpoint contains the kernel MMR leaves, which are the transformed kernel hashes
+        let kernels_by_leaf: HashMap<_, _> = db
+            .kernels
+            .iter()
+            .map(|(hash, kernel)| (db.leaf_transform.apply(hash), kernel))
+            .collect();
+        checkpoint
+            .nodes_added()
+            .iter()
+            .map(|leaf| {
+                let kernel = kernels_by_leaf
+                    .get(leaf)
+                    .ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::TransactionKernel(leaf.clone())))?;
+                let index = db.kernel_mmr.find_leaf_index(leaf)?.ok_or_else(|| {
+                    ChainStorageError::UnexpectedResult(format!(
+                        "The leaf node of {} was not found in the kernel MMR",
+                        DbKey::TransactionKernel(leaf.clone())
+                    ))
+                })?;
+                let proof = MerkleProof::for_leaf_node(&db.kernel_mmr.mmr(), index)?;
+                Ok(((*kernel).clone(), proof))
+            })
+            .collect()
+    }
+
     fn fetch_mmr_checkpoint(&self, tree: MmrTree, height: u64) -> Result<MerkleCheckPoint, ChainStorageError> {
         let db = self.db_access()?;
         let horizon_block = self.fetch_horizon_block_height()?;
@@ -399,6 +807,35 @@ where D: Digest + Send + Sync
         Ok((hash, deleted))
     }
 
+    fn fetch_mmr_nodes(
+        &self,
+        tree: MmrTree,
+        start_pos: u32,
+        count: u32,
+    ) -> Result<Vec<(Vec<u8>, bool)>, ChainStorageError>
+    {
+        let db = self.db_access()?;
+        let mmr = match tree {
+            MmrTree::Kernel => &db.kernel_mmr,
+            MmrTree::Utxo => &db.utxo_mmr,
+            MmrTree::RangeProof => &db.range_proof_mmr,
+        };
+        (start_pos..start_pos.saturating_add(count))
+            .map(|pos| {
+                let (hash, deleted) = mmr.get_leaf_status(pos)?;
+                let hash = hash
+                    .ok_or_else(|| {
+                        ChainStorageError::UnexpectedResult(format!(
+                            "The leaf node hash at position {} in the {} MMR tree was not found",
+                            pos, tree
+                        ))
+                    })?
+                    .clone();
+                Ok((hash, deleted))
+            })
+            .collect()
+    }
+
     fn fetch_mmr_base_leaf_nodes(
         &self,
         tree: MmrTree,
@@ -434,6 +871,25 @@ where D: Digest + Send + Sync
         Ok(mmr_state)
     }
 
+    fn pending_pruned_checkpoints(&self, tree: MmrTree) -> Result<usize, ChainStorageError> {
+        let db = self.db_access()?;
+        let count = match tree {
+            MmrTree::Kernel => db.kernel_mmr.pending_pruned_checkpoints()?,
+            MmrTree::Utxo => db.utxo_mmr.pending_pruned_checkpoints()?,
+            MmrTree::RangeProof => db.range_proof_mmr.pending_pruned_checkpoints()?,
+        };
+        Ok(count)
+    }
+
+    fn fetch_deleted_leaf_indices(&self, tree: MmrTree) -> Result<Vec<u32>, ChainStorageError> {
+        let db = self.db_access()?;
+        let indices = match tree {
+            MmrTree::Utxo => db.utxo_mmr.deleted().to_vec(),
+            MmrTree::Kernel | MmrTree::RangeProof => Vec::new(),
+        };
+        Ok(indices)
+    }
+
     fn assign_mmr(&self, tree: MmrTree, base_state: MutableMmrLeafNodes) -> Result<(), ChainStorageError> {
         let mut db = self
             .db
@@ -457,12 +913,68 @@ where D: Digest + Send + Sync
         Ok(())
     }
 
+    /// Iterate over all the stored unspent outputs and execute the function `f` for each output.
+    fn for_each_utxo<F>(&self, mut f: F) -> Result<(), ChainStorageError>
+    where F: FnMut(Result<(HashOutput, TransactionOutput), ChainStorageError>) {
+        let db = self.db_access()?;
+        for (key, val) in db.utxos.iter() {
+            f(Ok((key.clone(), val.value.clone())));
+        }
+        Ok(())
+    }
+
+    fn fetch_counts(&self) -> Result<ChainCounts, ChainStorageError> {
+        let db = self.db_access()?;
+        Ok(ChainCounts {
+            headers: db.headers.len(),
+            utxos: db.utxos.len(),
+            stxos: db.stxos.len(),
+            kernels: db.kernels.len(),
+            orphans: db.orphans.len(),
+        })
+    }
+
+    fn fetch_utxos_paged(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(HashOutput, TransactionOutput)>, ChainStorageError>
+    {
+        let db = self.db_access()?;
+        let mut utxos = db.utxos.iter().collect::<Vec<_>>();
+        utxos.sort_by_key(|(_, node)| node.index);
+        Ok(utxos
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(hash, node)| (hash.clone(), node.value.clone()))
+            .collect())
+    }
+
+    fn prune_orphans_below(&self, height: u64) -> Result<usize, ChainStorageError> {
+        let mut db = self
+            .db
+            .write()
+            .map_err(|e| ChainStorageError::AccessError(e.to_string()))?;
+        let keys = db
+            .orphans
+            .iter()
+            .filter(|(_, block)| block.header.height < height)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in keys.iter() {
+            db.orphans.remove(key);
+        }
+        Ok(keys.len())
+    }
+
     /// The horizon block is the earliest block that we can return all data to reconstruct a full block
     fn fetch_horizon_block_height(&self) -> Result<u64, ChainStorageError> {
         let db = self.db_access()?;
         let tip_height = db.headers.len();
         let checkpoint_count = db.kernel_mmr.checkpoint_count()?;
-        Ok((tip_height - checkpoint_count) as u64)
+        // Checkpoints can be created before their headers are stored, so the horizon cannot be below the genesis block
+        Ok(tip_height.saturating_sub(checkpoint_count) as u64)
     }
 
     fn fetch_last_header(&self) -> Result<Option<BlockHeader>, ChainStorageError> {
@@ -475,13 +987,82 @@ where D: Digest + Send + Sync
             Ok(None)
         }
     }
+
+    fn fetch_last_n_headers(&self, n: usize) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        let db = self.db_access()?;
+        let header_count = db.headers.len() as u64;
+        let start = header_count.saturating_sub(n as u64);
+        (start..header_count)
+            .rev()
+            .map(|height| {
+                db.headers
+                    .get(&height)
+                    .cloned()
+                    .ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::BlockHeader(height)))
+            })
+            .collect()
+    }
+
+    fn fetch_headers(&self, start: u64, end_inclusive: u64) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        if start > end_inclusive {
+            return Err(ChainStorageError::InvalidQuery(
+                "The start height of the header range is greater than the end height".to_string(),
+            ));
+        }
+        let db = self.db_access()?;
+        (start..=end_inclusive)
+            .map(|height| {
+                db.headers
+                    .get(&height)
+                    .cloned()
+                    .ok_or_else(|| ChainStorageError::ValueNotFound(DbKey::BlockHeader(height)))
+            })
+            .collect()
+    }
+
+    fn fetch_utxo_with_position(
+        &self,
+        hash: &HashOutput,
+    ) -> Result<Option<(TransactionOutput, usize)>, ChainStorageError>
+    {
+        let db = self.db_access()?;
+        Ok(db.utxos.get(hash).map(|node| (node.value.clone(), node.index)))
+    }
 }
 
 impl<D> Clone for MemoryDatabase<D>
 where D: Digest
+{
+    /// Returns another handle to the same database; changes made through either handle are visible to both. Use
+    /// [MemoryDatabase::deep_clone] for an independent copy.
+    fn clone(&self) -> Self {
+        MemoryDatabase {
+            db: self.db.clone(),
+            write_counters: self.write_counters.clone(),
+            tip_watch: self.tip_watch.clone(),
+        }
+    }
+}
+
+impl<D> Clone for InnerDatabase<D>
+where D: Digest
 {
     fn clone(&self) -> Self {
-        MemoryDatabase { db: self.db.clone() }
+        Self {
+            metadata: self.metadata.clone(),
+            headers: self.headers.clone(),
+            block_hashes: self.block_hashes.clone(),
+            utxos: self.utxos.clone(),
+            stxos: self.stxos.clone(),
+            kernels: self.kernels.clone(),
+            orphans: self.orphans.clone(),
+            utxo_mmr: self.utxo_mmr.clone(),
+            kernel_mmr: self.kernel_mmr.clone(),
+            range_proof_mmr: self.range_proof_mmr.clone(),
+            leaf_transform: self.leaf_transform.clone(),
+            #[cfg(test)]
+            failing_mmr: self.failing_mmr.clone(),
+        }
     }
 }
 
@@ -510,8 +1091,417 @@ where D: Digest
             utxo_mmr,
             kernel_mmr,
             range_proof_mmr,
+            leaf_transform: MmrLeafTransform::default(),
+            #[cfg(test)]
+            failing_mmr: None,
+        }
+    }
+}
+
+// This is a private helper function. When it is called, we are guaranteed to have a write lock on self.db
+// Returns the complete set of leaf nodes of the MMR. The leaf hashes of the base MMR are combined with those added to
+// the pruned MMR since, along with the current deletion bitmap.
+fn mmr_leaf_nodes<D: Digest>(
+    mmr: &MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>,
+) -> Result<MutableMmrLeafNodes, ChainStorageError> {
+    let base_leaf_count = mmr.get_base_leaf_count();
+    let mut leaf_hashes = if base_leaf_count > 0 {
+        mmr.to_base_leaf_nodes(0, base_leaf_count)?.leaf_hashes
+    } else {
+        Vec::new()
+    };
+    for pos in base_leaf_count..mmr.get_leaf_count() {
+        let (hash, _) = mmr.get_leaf_status(pos as u32)?;
+        let hash = hash.ok_or_else(|| {
+            ChainStorageError::UnexpectedResult(format!("The leaf node hash at position {} was not found", pos))
+        })?;
+        leaf_hashes.push(hash);
+    }
+    Ok(MutableMmrLeafNodes::new(leaf_hashes, mmr.deleted().clone()))
+}
+
+fn contains_key<D: Digest>(db: &InnerDatabase<D>, key: &DbKey) -> bool {
+    match key {
+        DbKey::Metadata(_) => true,
+        DbKey::BlockHeader(k) => db.headers.contains_key(k),
+        DbKey::BlockHash(h) => db.block_hashes.contains_key(h),
+        DbKey::UnspentOutput(k) => db.utxos.contains_key(k),
+        DbKey::SpentOutput(k) => db.stxos.contains_key(k),
+        DbKey::TransactionKernel(k) => db.kernels.contains_key(k),
+        DbKey::OrphanBlock(k) => db.orphans.contains_key(k),
+    }
+}
+
+fn chain_tip<D: Digest>(db: &InnerDatabase<D>) -> ChainTip {
+    db.headers
+        .iter()
+        .max_by_key(|(height, _)| *height)
+        .map(|(height, header)| (*height, header.hash()))
+        .unwrap_or_else(|| (0, Vec::new()))
+}
+
+fn push_mmr_leaf<D: Digest>(
+    db: &mut InnerDatabase<D>,
+    tree: MmrTree,
+    leaf: &HashOutput,
+) -> Result<usize, ChainStorageError>
+{
+    #[cfg(test)]
+    {
+        if db.failing_mmr.as_ref() == Some(&tree) {
+            let err =
+                tari_mmr::error::MerkleMountainRangeError::BackendError(format!("Injected {} MMR push failure", tree));
+            return Err(err.into());
         }
     }
+    let index = match tree {
+        MmrTree::Utxo => db.utxo_mmr.push(leaf)?,
+        MmrTree::Kernel => db.kernel_mmr.push(leaf)?,
+        MmrTree::RangeProof => db.range_proof_mmr.push(leaf)?,
+    };
+    Ok(index)
+}
+
+/// The value of a database entry before it was changed by a write operation. `None` means that the entry did not exist.
+enum UndoEntry {
+    Metadata(u32, Option<MetadataValue>),
+    Header(u64, Option<BlockHeader>),
+    BlockHash(HashOutput, Option<u64>),
+    Utxo(HashOutput, Option<MerkleNode<TransactionOutput>>),
+    Stxo(HashOutput, Option<MerkleNode<TransactionOutput>>),
+    Kernel(HashOutput, Option<TransactionKernel>),
+    Orphan(HashOutput, Option<Block>),
+}
+
+/// Records what is needed to undo the operations of a `DbTransaction` that have been applied. For every operation,
+/// the previous values of the entries it may change are recorded before it is applied. MMRs cannot be changed entry
+/// by entry, so each MMR is copied the first time an operation touches it.
+struct WriteRollback<D>
+where D: Digest
+{
+    undo_log: Vec<UndoEntry>,
+    utxo_mmr: Option<MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>>,
+    kernel_mmr: Option<MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>>,
+    range_proof_mmr: Option<MerkleChangeTracker<D, Vec<MmrHash>, Vec<MerkleCheckPoint>>>,
+}
+
+impl<D> Default for WriteRollback<D>
+where D: Digest
+{
+    fn default() -> Self {
+        Self {
+            undo_log: Vec::new(),
+            utxo_mmr: None,
+            kernel_mmr: None,
+            range_proof_mmr: None,
+        }
+    }
+}
+
+impl<D> WriteRollback<D>
+where D: Digest
+{
+    /// Record the current state of everything that `op` may change
+    fn record(&mut self, db: &InnerDatabase<D>, op: &WriteOperation) {
+        match op {
+            WriteOperation::Insert(insert) => match insert {
+                DbKeyValuePair::Metadata(k, _) => {
+                    let key = k.clone() as u32;
+                    self.undo_log
+                        .push(UndoEntry::Metadata(key, db.metadata.get(&key).cloned()));
+                },
+                DbKeyValuePair::BlockHeader(k, v) => {
+                    self.record_header(db, *k);
+                    self.record_block_hash(db, v.hash());
+                },
+                DbKeyValuePair::UnspentOutput(k, _, update_mmr) => {
+                    self.record_utxo(db, k);
+                    if *update_mmr {
+                        self.record_mmr(db, MmrTree::Utxo);
+                        self.record_mmr(db, MmrTree::RangeProof);
+                    }
+                },
+                DbKeyValuePair::TransactionKernel(k, _, update_mmr) => {
+                    self.record_kernel(db, k);
+                    if *update_mmr {
+                        self.record_mmr(db, MmrTree::Kernel);
+                    }
+                },
+                DbKeyValuePair::OrphanBlock(k, _) => self.record_orphan(db, k),
+            },
+            WriteOperation::Delete(delete) => match delete {
+                DbKey::Metadata(_) => {},
+                DbKey::BlockHeader(k) => {
+                    self.record_header(db, *k);
+                    if let Some(header) = db.headers.get(k) {
+                        self.record_block_hash(db, header.hash());
+                    }
+                },
+                DbKey::BlockHash(hash) => {
+                    self.record_block_hash(db, hash.clone());
+                    if let Some(height) = db.block_hashes.get(hash) {
+                        self.record_header(db, *height);
+                    }
+                },
+                DbKey::UnspentOutput(k) => self.record_utxo(db, k),
+                DbKey::SpentOutput(k) => self.record_stxo(db, k),
+                DbKey::TransactionKernel(k) => self.record_kernel(db, k),
+                DbKey::OrphanBlock(k) => self.record_orphan(db, k),
+            },
+            WriteOperation::Spend(key) => {
+                if let DbKey::UnspentOutput(hash) = key {
+                    self.record_utxo(db, hash);
+                    self.record_stxo(db, hash);
+                    self.record_mmr(db, MmrTree::Utxo);
+                }
+            },
+            WriteOperation::UnSpend(key) => {
+                if let DbKey::SpentOutput(hash) = key {
+                    self.record_utxo(db, hash);
+                    self.record_stxo(db, hash);
+                }
+            },
+            WriteOperation::CreateMmrCheckpoint(tree) |
+            WriteOperation::ResetMmr(tree) |
+            WriteOperation::RewindMmr(tree, _) => self.record_mmr(db, tree.clone()),
+        }
+    }
+
+    /// Undo the recorded changes, in the reverse order in which they were made
+    fn restore(self, db: &mut InnerDatabase<D>) {
+        for entry in self.undo_log.into_iter().rev() {
+            match entry {
+                UndoEntry::Metadata(k, v) => restore_entry(&mut db.metadata, k, v),
+                UndoEntry::Header(k, v) => restore_entry(&mut db.headers, k, v),
+                UndoEntry::BlockHash(k, v) => restore_entry(&mut db.block_hashes, k, v),
+                UndoEntry::Utxo(k, v) => restore_entry(&mut db.utxos, k, v),
+                UndoEntry::Stxo(k, v) => restore_entry(&mut db.stxos, k, v),
+                UndoEntry::Kernel(k, v) => restore_entry(&mut db.kernels, k, v),
+                UndoEntry::Orphan(k, v) => restore_entry(&mut db.orphans, k, v),
+            }
+        }
+        if let Some(mmr) = self.utxo_mmr {
+            db.utxo_mmr = mmr;
+        }
+        if let Some(mmr) = self.kernel_mmr {
+            db.kernel_mmr = mmr;
+        }
+        if let Some(mmr) = self.range_proof_mmr {
+            db.range_proof_mmr = mmr;
+        }
+    }
+
+    fn record_header(&mut self, db: &InnerDatabase<D>, height: u64) {
+        self.undo_log
+            .push(UndoEntry::Header(height, db.headers.get(&height).cloned()));
+    }
+
+    fn record_block_hash(&mut self, db: &InnerDatabase<D>, hash: HashOutput) {
+        let height = db.block_hashes.get(&hash).cloned();
+        self.undo_log.push(UndoEntry::BlockHash(hash, height));
+    }
+
+    fn record_utxo(&mut self, db: &InnerDatabase<D>, hash: &HashOutput) {
+        self.undo_log
+            .push(UndoEntry::Utxo(hash.clone(), db.utxos.get(hash).cloned()));
+    }
+
+    fn record_stxo(&mut self, db: &InnerDatabase<D>, hash: &HashOutput) {
+        self.undo_log
+            .push(UndoEntry::Stxo(hash.clone(), db.stxos.get(hash).cloned()));
+    }
+
+    fn record_kernel(&mut self, db: &InnerDatabase<D>, hash: &HashOutput) {
+        self.undo_log
+            .push(UndoEntry::Kernel(hash.clone(), db.kernels.get(hash).cloned()));
+    }
+
+    fn record_orphan(&mut self, db: &InnerDatabase<D>, hash: &HashOutput) {
+        self.undo_log
+            .push(UndoEntry::Orphan(hash.clone(), db.orphans.get(hash).cloned()));
+    }
+
+    fn record_mmr(&mut self, db: &InnerDatabase<D>, tree: MmrTree) {
+        match tree {
+            MmrTree::Utxo => self.utxo_mmr.get_or_insert_with(|| db.utxo_mmr.clone()),
+            MmrTree::Kernel => self.kernel_mmr.get_or_insert_with(|| db.kernel_mmr.clone()),
+            MmrTree::RangeProof => self.range_proof_mmr.get_or_insert_with(|| db.range_proof_mmr.clone()),
+        };
+    }
+}
+
+/// Set the entry for `key` back to `value`, removing it if `value` is `None`
+fn restore_entry<K, V>(map: &mut HashMap<K, V>, key: K, value: Option<V>)
+where K: std::hash::Hash + Eq {
+    match value {
+        Some(value) => {
+            map.insert(key, value);
+        },
+        None => {
+            map.remove(&key);
+        },
+    }
+}
+
+fn apply_write_operation<D: Digest>(
+    db: &mut RwLockWriteGuard<InnerDatabase<D>>,
+    op: WriteOperation,
+) -> Result<(), ChainStorageError>
+{
+    match op {
+        WriteOperation::Insert(insert) => match insert {
+            DbKeyValuePair::Metadata(k, v) => {
+                let key = k.clone() as u32;
+                if db.metadata.contains_key(&key) {
+                    return Err(ChainStorageError::KeyExists {
+                        key: DbKey::Metadata(k).to_string(),
+                    });
+                }
+                db.metadata.insert(key, v);
+            },
+            DbKeyValuePair::BlockHeader(k, v) => {
+                if db.headers.contains_key(&k) {
+                    return Err(ChainStorageError::KeyExists {
+                        key: DbKey::BlockHeader(k).to_string(),
+                    });
+                }
+                db.block_hashes.insert(v.hash(), k);
+                db.headers.insert(k, *v);
+            },
+            DbKeyValuePair::UnspentOutput(k, v, update_mmr) => {
+                if db.utxos.contains_key(&k) {
+                    return Err(ChainStorageError::KeyExists {
+                        key: DbKey::UnspentOutput(k).to_string(),
+                    });
+                }
+                let proof_hash = db.leaf_transform.apply(&v.proof().hash());
+                if update_mmr {
+                    // If the range proof push fails after the UTXO push succeeded, the MMRs are out of sync until the
+                    // failed transaction is rolled back by `write`
+                    let utxo_leaf = db.leaf_transform.apply(&k);
+                    push_mmr_leaf(db, MmrTree::Utxo, &utxo_leaf)?;
+                    push_mmr_leaf(db, MmrTree::RangeProof, &proof_hash)?;
+                }
+                // A UTXO without a range proof leaf would be missing from the UTXO set while its hash is in the UTXO
+                // MMR, so the transaction is rejected (and rolled back by `write`) instead
+                match db.range_proof_mmr.find_leaf_index(&proof_hash)? {
+                    Some(index) => {
+                        let v = MerkleNode { index, value: *v };
+                        db.utxos.insert(k, v);
+                    },
+                    None => {
+                        return Err(ChainStorageError::UnexpectedResult(format!(
+                            "The range proof of {} was not found in the range proof MMR",
+                            DbKey::UnspentOutput(k)
+                        )));
+                    },
+                }
+            },
+            DbKeyValuePair::TransactionKernel(k, v, update_mmr) => {
+                if db.kernels.contains_key(&k) {
+                    return Err(ChainStorageError::KeyExists {
+                        key: DbKey::TransactionKernel(k).to_string(),
+                    });
+                }
+                if update_mmr {
+                    let kernel_leaf = db.leaf_transform.apply(&k);
+                    push_mmr_leaf(db, MmrTree::Kernel, &kernel_leaf)?;
+                }
+                db.kernels.insert(k, *v);
+            },
+            DbKeyValuePair::OrphanBlock(k, v) => {
+                // Orphans are content-addressed, so inserting an orphan that is already stored is a no-op
+                db.orphans.entry(k).or_insert(*v);
+            },
+        },
+        WriteOperation::Delete(delete) => match delete {
+            DbKey::Metadata(_) => {}, // no-op
+            DbKey::BlockHeader(k) => {
+                db.headers.remove(&k).and_then(|v| db.block_hashes.remove(&v.hash()));
+            },
+            DbKey::BlockHash(hash) => {
+                db.block_hashes.remove(&hash).and_then(|i| db.headers.remove(&i));
+            },
+            DbKey::UnspentOutput(k) => {
+                db.utxos.remove(&k);
+            },
+            DbKey::SpentOutput(k) => {
+                db.stxos.remove(&k);
+            },
+            DbKey::TransactionKernel(k) => {
+                db.kernels.remove(&k);
+            },
+            DbKey::OrphanBlock(k) => {
+                db.orphans.remove(&k);
+            },
+        },
+        WriteOperation::Spend(key) => match key {
+            DbKey::UnspentOutput(hash) => {
+                let moved = spend_utxo(&mut db, hash);
+                if !moved {
+                    return Err(ChainStorageError::UnspendableInput);
+                }
+            },
+            _ => return Err(ChainStorageError::InvalidOperation("Only UTXOs can be spent".into())),
+        },
+        WriteOperation::UnSpend(key) => match key {
+            DbKey::SpentOutput(hash) => {
+                let moved = unspend_stxo(&mut db, hash);
+                if !moved {
+                    return Err(ChainStorageError::UnspendError);
+                }
+            },
+            _ => return Err(ChainStorageError::InvalidOperation("Only STXOs can be unspent".into())),
+        },
+        WriteOperation::CreateMmrCheckpoint(tree) => match tree {
+            MmrTree::Kernel => db
+                .kernel_mmr
+                .commit()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::Utxo => db
+                .utxo_mmr
+                .commit()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::RangeProof => db
+                .range_proof_mmr
+                .commit()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+        },
+        WriteOperation::ResetMmr(tree) => match tree {
+            MmrTree::Kernel => db
+                .kernel_mmr
+                .reset()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::Utxo => db
+                .utxo_mmr
+                .reset()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::RangeProof => db
+                .range_proof_mmr
+                .reset()
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+        },
+        WriteOperation::RewindMmr(_, 0) => {
+            return Err(ChainStorageError::InvalidOperation(
+                "An MMR cannot be rewound by zero checkpoints, use ResetMmr instead".into(),
+            ));
+        },
+        WriteOperation::RewindMmr(tree, steps_back) => match tree {
+            MmrTree::Kernel => db
+                .kernel_mmr
+                .rewind(steps_back)
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::Utxo => db
+                .utxo_mmr
+                .rewind(steps_back)
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+            MmrTree::RangeProof => db
+                .range_proof_mmr
+                .rewind(steps_back)
+                .map_err(|e| ChainStorageError::AccessError(e.to_string()))?,
+        },
+    }
+    Ok(())
 }
 
 // This is a private helper function. When it is called, we are guaranteed to have a write lock on self.db
@@ -541,10 +1531,38 @@ fn unspend_stxo<D: Digest>(db: &mut RwLockWriteGuard<InnerDatabase<D>>, hash: Ha
 
 #[cfg(test)]
 mod test {
-    use crate::chain_storage::{BlockchainBackend, MemoryDatabase, MmrTree};
+    use super::TipWatch;
+    use crate::{
+        blocks::{BlockBuilder, BlockHeader},
+        chain_storage::{
+            BlockchainBackend,
+            ChainStorageError,
+            DbKey,
+            DbTransaction,
+            DbValue,
+            MemoryDatabase,
+            MemoryDatabaseConfig,
+            MetadataKey,
+            MetadataValue,
+            MmrTree,
+            WriteStats,
+        },
+    };
     use croaring::Bitmap;
+    use futures::executor::block_on;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
     use tari_mmr::{MerkleChangeTrackerConfig, MutableMmr, MutableMmrLeafNodes};
-    use tari_transactions::{tari_amount::uT, tx, types::HashDigest};
+    use tari_storage::IterationResult;
+    use tari_transactions::{
+        helpers::create_utxo,
+        tari_amount::{uT, MicroTari},
+        tx,
+        types::{CryptoFactories, HashDigest, HashOutput},
+    };
     use tari_utilities::Hashable;
 
     /// Test the ability to assign a given state to the database MMR
@@ -576,4 +1594,634 @@ mod test {
         db.assign_mmr(MmrTree::Kernel, state).unwrap();
         assert_eq!(db.fetch_mmr_root(MmrTree::Kernel).unwrap(), root);
     }
+
+    #[test]
+    fn with_configs() {
+        let kernel_config = MerkleChangeTrackerConfig {
+            min_history_len: 1,
+            max_history_len: 2,
+        };
+        let utxo_config = MerkleChangeTrackerConfig {
+            min_history_len: 40,
+            max_history_len: 50,
+        };
+        let db = MemoryDatabase::<HashDigest>::with_configs(utxo_config, kernel_config, utxo_config);
+        let factories = CryptoFactories::default();
+        for i in 0..10 {
+            let (utxo, _) = create_utxo(MicroTari(10_000 + i), &factories);
+            let (tx, _, _) = tx!(100_000 * uT, fee: 100 * uT);
+            let mut txn = DbTransaction::new();
+            txn.insert_utxo(utxo, true);
+            txn.insert_kernel(tx.body.kernels()[0].clone(), true);
+            txn.commit_block();
+            db.write(txn).unwrap();
+        }
+
+        let inner = db.db_access().unwrap();
+        assert!(inner.kernel_mmr.checkpoint_count().unwrap() <= 2);
+        assert_eq!(inner.utxo_mmr.checkpoint_count().unwrap(), 10);
+        assert_eq!(inner.range_proof_mmr.checkpoint_count().unwrap(), 10);
+    }
+
+    #[test]
+    fn new_with_metadata() {
+        let mut metadata = HashMap::new();
+        metadata.insert(MetadataKey::ChainHeight, MetadataValue::ChainHeight(Some(42)));
+        let mct = MerkleChangeTrackerConfig {
+            min_history_len: 2,
+            max_history_len: 3,
+        };
+        let db = MemoryDatabase::<HashDigest>::new_with_metadata(mct, metadata);
+        match db.fetch(&DbKey::Metadata(MetadataKey::ChainHeight)).unwrap() {
+            Some(DbValue::Metadata(MetadataValue::ChainHeight(Some(height)))) => assert_eq!(height, 42),
+            value => panic!("Unexpected chain height metadata: {:?}", value),
+        }
+        assert!(db.fetch(&DbKey::Metadata(MetadataKey::BestBlock)).unwrap().is_none());
+    }
+
+    fn create_db_with_spent_utxo() -> MemoryDatabase<HashDigest> {
+        let factories = CryptoFactories::default();
+        let db = MemoryDatabase::<HashDigest>::default();
+        let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+        let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+        let hash1 = utxo1.hash();
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo1, true);
+        txn.insert_utxo(utxo2, true);
+        db.write(txn).unwrap();
+        let mut txn = DbTransaction::new();
+        txn.spend_utxo(hash1);
+        db.write(txn).unwrap();
+        db
+    }
+
+    #[test]
+    fn fast_sync_replace() {
+        let factories = CryptoFactories::default();
+        let db = create_db_with_spent_utxo();
+        let old_utxo_hashes = db.db_access().unwrap().utxos.keys().cloned().collect::<Vec<_>>();
+
+        let outputs = (0..4)
+            .map(|i| create_utxo(MicroTari(10_000 + i), &factories).0)
+            .collect::<Vec<_>>();
+        let hashes = outputs.iter().map(|output| output.hash()).collect::<Vec<_>>();
+        let mut deleted = Bitmap::create();
+        deleted.add(1);
+        let state = MutableMmrLeafNodes::new(hashes.clone(), deleted);
+        let mut mmr = MutableMmr::<HashDigest, _>::new(Vec::new());
+        mmr.assign(state.clone()).unwrap();
+
+        // The outputs must match the leaf nodes
+        assert!(db
+            .fast_sync_replace(MmrTree::Utxo, state.clone(), outputs[..3].to_vec())
+            .is_err());
+        assert!(db
+            .fast_sync_replace(MmrTree::Kernel, state.clone(), outputs.clone())
+            .is_err());
+        // The range proofs of the outputs have not been synced yet
+        assert!(db
+            .fast_sync_replace(MmrTree::Utxo, state.clone(), outputs.clone())
+            .is_err());
+        assert!(db.contains(&DbKey::UnspentOutput(old_utxo_hashes[0].clone())).unwrap());
+
+        // The range proofs are synced in the reverse order, so that their leaf indices differ from the UTXO leaf
+        // indices
+        let proof_hashes = outputs.iter().rev().map(|output| output.proof().hash()).collect();
+        db.fast_sync_replace(
+            MmrTree::RangeProof,
+            MutableMmrLeafNodes::new(proof_hashes, Bitmap::create()),
+            Vec::new(),
+        )
+        .unwrap();
+        db.fast_sync_replace(MmrTree::Utxo, state, outputs).unwrap();
+        assert_eq!(
+            db.fetch_mmr_root(MmrTree::Utxo).unwrap(),
+            mmr.get_merkle_root().unwrap()
+        );
+        assert!(!db.contains(&DbKey::UnspentOutput(old_utxo_hashes[0].clone())).unwrap());
+        assert!(db.contains(&DbKey::UnspentOutput(hashes[0].clone())).unwrap());
+        assert!(!db.contains(&DbKey::UnspentOutput(hashes[1].clone())).unwrap());
+        assert!(db.contains(&DbKey::SpentOutput(hashes[1].clone())).unwrap());
+        assert_eq!(db.db_access().unwrap().utxos.len(), 3);
+        assert_eq!(db.db_access().unwrap().stxos.len(), 1);
+        assert_eq!(db.db_access().unwrap().utxos[&hashes[0]].index, 3);
+        assert_eq!(db.db_access().unwrap().stxos[&hashes[1]].index, 2);
+        assert!(db.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn approx_memory_bytes() {
+        let factories = CryptoFactories::default();
+        let db = MemoryDatabase::<HashDigest>::default();
+        let empty_size = db.approx_memory_bytes().unwrap();
+
+        let (utxo, _) = create_utxo(MicroTari(10_000), &factories);
+        let (tx, _, _) = tx!(100_000 * uT, fee: 100 * uT);
+        let mut txn = DbTransaction::new();
+        txn.insert_header(BlockHeader::new(0));
+        txn.insert_utxo(utxo, true);
+        txn.insert_kernel(tx.body.kernels()[0].clone(), true);
+        txn.commit_block();
+        db.write(txn).unwrap();
+        let block_size = db.approx_memory_bytes().unwrap();
+        assert!(block_size > empty_size);
+
+        let orphan = BlockBuilder::new().with_header(BlockHeader::new(0)).build();
+        let mut txn = DbTransaction::new();
+        txn.insert_orphan(orphan);
+        db.write(txn).unwrap();
+        assert!(db.approx_memory_bytes().unwrap() > block_size);
+    }
+
+    #[test]
+    fn deep_clone() {
+        let factories = CryptoFactories::default();
+        let db = MemoryDatabase::<HashDigest>::default();
+        let (utxo, _) = create_utxo(MicroTari(10_000), &factories);
+        let hash = utxo.hash();
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo, true);
+        db.write(txn).unwrap();
+        let utxo_root = db.fetch_mmr_root(MmrTree::Utxo).unwrap();
+
+        let fork = db.deep_clone().unwrap();
+        let mut txn = DbTransaction::new();
+        txn.spend_utxo(hash.clone());
+        fork.write(txn).unwrap();
+
+        assert_eq!(fork.contains(&DbKey::UnspentOutput(hash.clone())), Ok(false));
+        assert_eq!(fork.contains(&DbKey::SpentOutput(hash.clone())), Ok(true));
+        assert_eq!(db.contains(&DbKey::UnspentOutput(hash.clone())), Ok(true));
+        assert_eq!(db.contains(&DbKey::SpentOutput(hash)), Ok(false));
+        assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+        assert_ne!(fork.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+        assert_eq!(fork.write_stats().transactions, 1);
+    }
+
+    #[test]
+    fn failed_write_is_rolled_back() {
+        let factories = CryptoFactories::default();
+        let db = create_db_with_spent_utxo();
+        let (utxo1, _) = create_utxo(MicroTari(20_000), &factories);
+        let (utxo2, _) = create_utxo(MicroTari(25_000), &factories);
+        let hash1 = utxo1.hash();
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo2.clone(), true);
+        db.write(txn).unwrap();
+        let utxo_root = db.fetch_mmr_root(MmrTree::Utxo).unwrap();
+        let rp_root = db.fetch_mmr_root(MmrTree::RangeProof).unwrap();
+        let stats = db.write_stats();
+
+        // The second operation is a duplicate insert, so the first must be rolled back
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo1, true);
+        txn.insert_utxo(utxo2, true);
+        match db.write(txn) {
+            Err(ChainStorageError::OperationFailed { index, .. }) => assert_eq!(index, 1),
+            result => panic!("Unexpected write result: {:?}", result),
+        }
+        assert_eq!(db.contains(&DbKey::UnspentOutput(hash1)), Ok(false));
+        assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+        assert_eq!(db.fetch_mmr_root(MmrTree::RangeProof).unwrap(), rp_root);
+        assert!(db.assert_utxo_consistency().is_ok());
+        assert_eq!(db.write_stats().operations, stats.operations);
+    }
+
+    #[test]
+    fn failed_write_restores_changed_entries() {
+        let db = create_db_with_spent_utxo();
+        let utxo_hash = db.db_access().unwrap().utxos.keys().next().unwrap().clone();
+        let utxo_root = db.fetch_mmr_root(MmrTree::Utxo).unwrap();
+        let header0 = BlockHeader::new(0);
+        let mut txn = DbTransaction::new();
+        txn.insert_header(header0.clone());
+        db.write(txn).unwrap();
+
+        // Each operation changes entries of a different kind before the duplicate header insert fails
+        let mut header1 = BlockHeader::new(0);
+        header1.height = 1;
+        let mut txn = DbTransaction::new();
+        txn.delete(DbKey::BlockHash(header0.hash()));
+        txn.insert_header(header1.clone());
+        txn.spend_utxo(utxo_hash.clone());
+        txn.insert_header(header1.clone());
+        match db.write(txn) {
+            Err(ChainStorageError::OperationFailed { index, .. }) => assert_eq!(index, 3),
+            result => panic!("Unexpected write result: {:?}", result),
+        }
+
+        assert_eq!(db.contains(&DbKey::BlockHeader(0)), Ok(true));
+        assert_eq!(db.contains(&DbKey::BlockHash(header0.hash())), Ok(true));
+        assert_eq!(db.contains(&DbKey::BlockHeader(1)), Ok(false));
+        assert_eq!(db.contains(&DbKey::BlockHash(header1.hash())), Ok(false));
+        assert_eq!(db.contains(&DbKey::UnspentOutput(utxo_hash.clone())), Ok(true));
+        assert_eq!(db.contains(&DbKey::SpentOutput(utxo_hash)), Ok(false));
+        assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+        assert!(db.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn utxo_insert_without_range_proof_leaf() {
+        let factories = CryptoFactories::default();
+        let db = create_db_with_spent_utxo();
+        let (utxo, _) = create_utxo(MicroTari(20_000), &factories);
+        let hash = utxo.hash();
+        let utxo_leaf_count = db.db.read().unwrap().utxo_mmr.get_leaf_count();
+        let rp_leaf_count = db.db.read().unwrap().range_proof_mmr.get_leaf_count();
+
+        // The MMRs are not updated, so the range proof of the UTXO is not in the range proof MMR
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo, false);
+        match db.write(txn) {
+            Err(ChainStorageError::OperationFailed { index, source }) => {
+                assert_eq!(index, 0);
+                match *source {
+                    ChainStorageError::UnexpectedResult(_) => {},
+                    err => panic!("Unexpected error: {:?}", err),
+                }
+            },
+            result => panic!("Unexpected write result: {:?}", result),
+        }
+
+        assert_eq!(db.db.read().unwrap().utxo_mmr.get_leaf_count(), utxo_leaf_count);
+        assert_eq!(db.db.read().unwrap().range_proof_mmr.get_leaf_count(), rp_leaf_count);
+        assert_eq!(db.contains(&DbKey::UnspentOutput(hash)), Ok(false));
+        assert!(db.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn range_proof_mmr_push_failure() {
+        let factories = CryptoFactories::default();
+        let db = create_db_with_spent_utxo();
+        let (utxo, _) = create_utxo(MicroTari(20_000), &factories);
+        let hash = utxo.hash();
+        let utxo_root = db.fetch_mmr_root(MmrTree::Utxo).unwrap();
+        let utxo_leaf_count = db.db.read().unwrap().utxo_mmr.get_leaf_count();
+        let rp_root = db.fetch_mmr_root(MmrTree::RangeProof).unwrap();
+
+        db.db.write().unwrap().failing_mmr = Some(MmrTree::RangeProof);
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo, true);
+        match db.write(txn) {
+            Err(ChainStorageError::OperationFailed { index, source }) => {
+                assert_eq!(index, 0);
+                match *source {
+                    ChainStorageError::MerkleMountainRangeError(_) => {},
+                    err => panic!("Unexpected error: {:?}", err),
+                }
+            },
+            result => panic!("Unexpected write result: {:?}", result),
+        }
+        db.db.write().unwrap().failing_mmr = None;
+
+        assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+        assert_eq!(db.db.read().unwrap().utxo_mmr.get_leaf_count(), utxo_leaf_count);
+        assert_eq!(db.fetch_mmr_root(MmrTree::RangeProof).unwrap(), rp_root);
+        assert_eq!(db.contains(&DbKey::UnspentOutput(hash)), Ok(false));
+        assert!(db.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let factories = CryptoFactories::default();
+        let mct = MerkleChangeTrackerConfig {
+            min_history_len: 2,
+            max_history_len: 3,
+        };
+        let db = MemoryDatabase::<HashDigest>::new(mct);
+        let mut header = BlockHeader::new(0);
+        let mut spendable = Vec::new();
+        for i in 0..5 {
+            let (utxo, _) = create_utxo(MicroTari(10_000 + i), &factories);
+            let (tx, _, _) = tx!(100_000 * uT, fee: 100 * uT);
+            let mut txn = DbTransaction::new();
+            if i > 0 {
+                header = BlockHeader::from_previous(&header);
+            }
+            txn.insert_header(header.clone());
+            spendable.push(utxo.hash());
+            txn.insert_utxo(utxo, true);
+            txn.insert_kernel(tx.body.kernels()[0].clone(), true);
+            if i % 2 == 1 {
+                txn.spend_utxo(spendable.remove(0));
+            }
+            txn.commit_block();
+            db.write(txn).unwrap();
+        }
+
+        let snapshot = db.export_snapshot().unwrap();
+        let snapshot = bincode::deserialize(&bincode::serialize(&snapshot).unwrap()).unwrap();
+        let imported = MemoryDatabase::<HashDigest>::import_snapshot(mct, snapshot).unwrap();
+
+        for tree in &[MmrTree::Utxo, MmrTree::Kernel, MmrTree::RangeProof] {
+            assert_eq!(
+                imported.fetch_mmr_root(tree.clone()).unwrap(),
+                db.fetch_mmr_root(tree.clone()).unwrap()
+            );
+        }
+        assert_eq!(imported.db_access().unwrap().headers.len(), 5);
+        assert_eq!(imported.fetch_last_header().unwrap().unwrap().hash(), header.hash());
+        assert!(imported.contains(&DbKey::BlockHash(header.hash())).unwrap());
+        assert_eq!(*imported.tip_watch().get_ref(), (4, header.hash()));
+        assert!(imported.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn tip_watch() {
+        let db = MemoryDatabase::<HashDigest>::default();
+        let mut tip_watch = db.tip_watch();
+        assert_eq!(*tip_watch.get_ref(), (0, Vec::new()));
+
+        let header0 = BlockHeader::new(0);
+        let header1 = BlockHeader::from_previous(&header0);
+        let mut txn = DbTransaction::new();
+        txn.insert_header(header0.clone());
+        txn.insert_header(header1.clone());
+        db.write(txn).unwrap();
+        let expected = (1, header1.hash());
+        let tip = block_on(async {
+            loop {
+                let tip = tip_watch.recv().await.unwrap();
+                if tip == expected {
+                    break tip;
+                }
+            }
+        });
+        assert_eq!(tip, expected);
+
+        let mut txn = DbTransaction::new();
+        txn.delete(DbKey::BlockHeader(1));
+        db.write(txn).unwrap();
+        let tip = block_on(tip_watch.recv()).unwrap();
+        assert_eq!(tip, (0, header0.hash()));
+    }
+
+    #[test]
+    fn time_since_tip_update() {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock_now = now.clone();
+        let db = MemoryDatabase::<HashDigest> {
+            tip_watch: Arc::new(TipWatch::with_clock(Arc::new(move || *clock_now.lock().unwrap()))),
+            ..Default::default()
+        };
+        assert_eq!(db.time_since_tip_update(), None);
+
+        let mut txn = DbTransaction::new();
+        txn.insert_header(BlockHeader::new(0));
+        db.write(txn).unwrap();
+        assert_eq!(db.time_since_tip_update(), Some(Duration::from_secs(0)));
+
+        *now.lock().unwrap() += Duration::from_secs(90);
+        assert_eq!(db.time_since_tip_update(), Some(Duration::from_secs(90)));
+        // Writes that don't move the tip leave the timestamp alone
+        let (utxo, _) = create_utxo(MicroTari(10_000), &CryptoFactories::default());
+        let mut txn = DbTransaction::new();
+        txn.insert_utxo(utxo, true);
+        db.write(txn).unwrap();
+        assert_eq!(db.time_since_tip_update(), Some(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn tip_watch_debug_does_not_block() {
+        let tip_watch = TipWatch::default();
+        let _guard = tip_watch.updated_at.lock().unwrap();
+        assert!(format!("{:?}", tip_watch).contains("<locked>"));
+    }
+
+    #[test]
+    fn with_genesis() {
+        let factories = CryptoFactories::default();
+        let mct = MerkleChangeTrackerConfig {
+            min_history_len: 2,
+            max_history_len: 3,
+        };
+        let (utxo, _) = create_utxo(MicroTari(10_000), &factories);
+        let (tx, _, _) = tx!(100_000 * uT, fee: 100 * uT);
+        let kernel = tx.body.kernels()[0].clone();
+        // Calculate the genesis MMR roots using an empty database
+        let empty_db = MemoryDatabase::<HashDigest>::new(mct);
+        let mut header = BlockHeader::new(0);
+        header.output_mr = empty_db
+            .calculate_mmr_root(MmrTree::Utxo, vec![utxo.hash()], vec![])
+            .unwrap();
+        header.range_proof_mr = empty_db
+            .calculate_mmr_root(MmrTree::RangeProof, vec![utxo.proof().hash()], vec![])
+            .unwrap();
+        header.kernel_mr = empty_db
+            .calculate_mmr_root(MmrTree::Kernel, vec![kernel.hash()], vec![])
+            .unwrap();
+        let genesis = BlockBuilder::new()
+            .with_header(header.clone())
+            .with_coinbase_utxo(utxo.clone(), kernel)
+            .build();
+        let genesis_hash = genesis.hash();
+
+        let db = MemoryDatabase::<HashDigest>::with_genesis(mct, genesis).unwrap();
+        match db.fetch(&DbKey::Metadata(MetadataKey::ChainHeight)).unwrap() {
+            Some(DbValue::Metadata(MetadataValue::ChainHeight(Some(height)))) => assert_eq!(height, 0),
+            value => panic!("Unexpected chain height metadata: {:?}", value),
+        }
+        match db.fetch(&DbKey::Metadata(MetadataKey::BestBlock)).unwrap() {
+            Some(DbValue::Metadata(MetadataValue::BestBlock(Some(hash)))) => assert_eq!(hash, genesis_hash),
+            value => panic!("Unexpected best block metadata: {:?}", value),
+        }
+        assert_eq!(db.fetch_last_header().unwrap().unwrap().hash(), header.hash());
+        assert!(db.contains(&DbKey::UnspentOutput(utxo.hash())).unwrap());
+        assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), header.output_mr);
+        assert_eq!(db.fetch_mmr_root(MmrTree::RangeProof).unwrap(), header.range_proof_mr);
+        assert_eq!(db.fetch_mmr_root(MmrTree::Kernel).unwrap(), header.kernel_mr);
+        assert_eq!(
+            db.fetch_mmr_checkpoint(MmrTree::Utxo, 0).unwrap().nodes_added().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn for_each_header_desc() {
+        let db = MemoryDatabase::<HashDigest>::default();
+        let mut headers = vec![BlockHeader::new(0)];
+        for _ in 1..10 {
+            let header = BlockHeader::from_previous(headers.last().unwrap());
+            headers.push(header);
+        }
+        let mut txn = DbTransaction::new();
+        headers.iter().for_each(|header| txn.insert_header(header.clone()));
+        db.write(txn).unwrap();
+
+        let mut visited = Vec::new();
+        db.for_each_header_desc(|header| {
+            visited.push(header);
+            if visited.len() == 3 {
+                IterationResult::Break
+            } else {
+                IterationResult::Continue
+            }
+        })
+        .unwrap();
+        assert_eq!(visited, vec![
+            headers[9].clone(),
+            headers[8].clone(),
+            headers[7].clone()
+        ]);
+
+        let mut heights = Vec::new();
+        db.for_each_header_desc(|header| {
+            heights.push(header.height);
+            IterationResult::Continue
+        })
+        .unwrap();
+        assert_eq!(heights, (0..10).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fetch_connectable_orphans() {
+        let db = MemoryDatabase::<HashDigest>::default();
+        let header0 = BlockHeader::new(0);
+        let header1 = BlockHeader::from_previous(&header0);
+        let mut txn = DbTransaction::new();
+        txn.insert_header(header0.clone());
+        db.write(txn).unwrap();
+
+        // The parent of this orphan is stored on the main chain
+        let mut sibling_header = BlockHeader::from_previous(&header0);
+        sibling_header.nonce = 42;
+        let sibling = BlockBuilder::new().with_header(sibling_header).build();
+        // The parent of this orphan is the block at height 1, which has not been added yet
+        let child = BlockBuilder::new()
+            .with_header(BlockHeader::from_previous(&header1))
+            .build();
+        // The parent of this orphan is unknown
+        let mut unknown_parent = BlockHeader::new(0);
+        unknown_parent.nonce = 7;
+        let unconnectable = BlockBuilder::new()
+            .with_header(BlockHeader::from_previous(&unknown_parent))
+            .build();
+        let mut txn = DbTransaction::new();
+        txn.insert_orphan(sibling.clone());
+        txn.insert_orphan(child.clone());
+        txn.insert_orphan(unconnectable);
+        db.write(txn).unwrap();
+
+        assert_eq!(db.fetch_connectable_orphans(&header0.hash()).unwrap(), vec![
+            sibling.clone()
+        ]);
+        // The orphan becomes connectable once its parent is the tip
+        assert_eq!(db.fetch_connectable_orphans(&header1.hash()).unwrap(), vec![
+            sibling.clone(),
+            child.clone()
+        ]);
+        let mut txn = DbTransaction::new();
+        txn.insert_header(header1.clone());
+        db.write(txn).unwrap();
+        assert_eq!(db.fetch_connectable_orphans(&header1.hash()).unwrap(), vec![
+            sibling, child
+        ]);
+    }
+
+    #[test]
+    fn rebuild_block_hash_index() {
+        let db = MemoryDatabase::<HashDigest>::default();
+        let mut txn = DbTransaction::new();
+        let header1 = BlockHeader::new(0);
+        let header2 = BlockHeader::from_previous(&header1);
+        let hash1 = header1.hash();
+        let hash2 = header2.hash();
+        txn.insert_header(header1);
+        txn.insert_header(header2);
+        db.write(txn).unwrap();
+        assert!(db.contains(&DbKey::BlockHash(hash2.clone())).unwrap());
+        // Corrupt the index
+        {
+            let mut inner = db.db.write().unwrap();
+            inner.block_hashes.clear();
+            inner.block_hashes.insert(vec![1, 2, 3], 0);
+        }
+        assert!(db.fetch(&DbKey::BlockHash(hash1.clone())).unwrap().is_none());
+
+        assert_eq!(db.rebuild_block_hash_index().unwrap(), 2);
+        assert!(db.fetch(&DbKey::BlockHash(vec![1, 2, 3])).unwrap().is_none());
+        for (hash, height) in &[(hash1, 0), (hash2, 1)] {
+            match db.fetch(&DbKey::BlockHash(hash.clone())).unwrap() {
+                Some(DbValue::BlockHash(header)) => assert_eq!(header.height, *height),
+                value => panic!("Unexpected block hash value: {:?}", value),
+            }
+        }
+    }
+
+    #[test]
+    fn leaf_transform() {
+        let factories = CryptoFactories::default();
+        let mct = MerkleChangeTrackerConfig {
+            min_history_len: 2,
+            max_history_len: 3,
+        };
+        let plain_db = MemoryDatabase::<HashDigest>::new(mct);
+        let mut config = MemoryDatabaseConfig::new(mct);
+        config.leaf_transform = Some(Arc::new(|hash: &HashOutput| {
+            let mut leaf = vec![0x01];
+            leaf.extend_from_slice(hash);
+            leaf
+        }));
+        let transformed_db = MemoryDatabase::<HashDigest>::new_with_config(config);
+
+        let (utxo, _) = create_utxo(MicroTari(10_000), &factories);
+        let hash = utxo.hash();
+        let expected_root = transformed_db
+            .calculate_mmr_root(MmrTree::Utxo, vec![hash.clone()], vec![])
+            .unwrap();
+        for db in &[&plain_db, &transformed_db] {
+            let mut txn = DbTransaction::new();
+            txn.insert_utxo(utxo.clone(), true);
+            db.write(txn).unwrap();
+        }
+
+        assert_eq!(transformed_db.fetch_mmr_root(MmrTree::Utxo).unwrap(), expected_root);
+        assert_ne!(
+            transformed_db.fetch_mmr_root(MmrTree::Utxo).unwrap(),
+            plain_db.fetch_mmr_root(MmrTree::Utxo).unwrap()
+        );
+        // The UTXO is still indexed against the transformed range proof leaf
+        assert_eq!(transformed_db.fetch_utxo_with_position(&hash).unwrap(), Some((utxo, 0)));
+    }
+
+    #[test]
+    fn write_stats() {
+        let db = MemoryDatabase::<HashDigest>::default();
+        assert_eq!(db.write_stats(), WriteStats::default());
+        let db = create_db_with_spent_utxo();
+        assert_eq!(db.write_stats(), WriteStats {
+            transactions: 2,
+            operations: 3
+        });
+        // Failed operations are not counted
+        let mut txn = DbTransaction::new();
+        txn.spend_utxo(vec![1, 2, 3]);
+        assert!(db.write(txn).is_err());
+        let stats = db.clone().write_stats();
+        assert_eq!(stats.transactions, 3);
+        assert_eq!(stats.operations, 3);
+    }
+
+    #[test]
+    fn utxo_consistency() {
+        let db = create_db_with_spent_utxo();
+        assert!(db.assert_utxo_consistency().is_ok());
+    }
+
+    #[test]
+    fn utxo_consistency_desynced() {
+        let db = create_db_with_spent_utxo();
+        db.db.write().unwrap().utxos.clear();
+        match db.assert_utxo_consistency() {
+            Err(ChainStorageError::CorruptedDatabase(_)) => {},
+            result => panic!("Unexpected consistency result: {:?}", result),
+        }
+
+        let db = create_db_with_spent_utxo();
+        db.db.write().unwrap().stxos.clear();
+        match db.assert_utxo_consistency() {
+            Err(ChainStorageError::CorruptedDatabase(_)) => {},
+            result => panic!("Unexpected consistency result: {:?}", result),
+        }
+    }
 }
@@ -38,8 +38,26 @@ mod metadata;
 pub mod async_db;
 
 // Public API exports
-pub use blockchain_database::{BlockAddResult, BlockchainBackend, BlockchainDatabase, MutableMmrState, Validators};
-pub use db_transaction::{DbKey, DbKeyValuePair, DbTransaction, DbValue, MetadataKey, MetadataValue, MmrTree};
+pub use blockchain_database::{
+    deserialize_mmr_proof,
+    BlockAddResult,
+    BlockchainBackend,
+    BlockchainDatabase,
+    ChainCounts,
+    MutableMmrState,
+    Validators,
+    MMR_PROOF_SERIALIZATION_VERSION,
+};
+pub use db_transaction::{
+    DbKey,
+    DbKeyValuePair,
+    DbTransaction,
+    DbTransactionBuilder,
+    DbValue,
+    MetadataKey,
+    MetadataValue,
+    MmrTree,
+};
 pub use error::ChainStorageError;
 pub use historical_block::HistoricalBlock;
 pub use lmdb_db::{
@@ -59,5 +77,5 @@ pub use lmdb_db::{
     LMDB_DB_UTXO_MMR_BASE_BACKEND,
     LMDB_DB_UTXO_MMR_CP_BACKEND,
 };
-pub use memory_db::MemoryDatabase;
+pub use memory_db::{DbSnapshot, LeafTransform, MemoryDatabase, MemoryDatabaseConfig, WriteStats};
 pub use metadata::ChainMetadata;
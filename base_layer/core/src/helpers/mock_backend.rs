@@ -23,10 +23,22 @@
 
 use crate::{
     blocks::{Block, BlockHeader},
-    chain_storage::{BlockchainBackend, ChainStorageError, DbKey, DbTransaction, DbValue, MmrTree, MutableMmrState},
+    chain_storage::{
+        BlockchainBackend,
+        ChainCounts,
+        ChainStorageError,
+        DbKey,
+        DbTransaction,
+        DbValue,
+        MmrTree,
+        MutableMmrState,
+    },
 };
 use tari_mmr::{Hash, MerkleCheckPoint, MerkleProof, MutableMmrLeafNodes};
-use tari_transactions::types::HashOutput;
+use tari_transactions::{
+    transaction::{TransactionKernel, TransactionOutput},
+    types::HashOutput,
+};
 
 // This is a test backend. This is used so that the ConsensusManager can be called without actually having a backend.
 // Calling this backend will result in a panic.
@@ -45,6 +57,10 @@ impl BlockchainBackend for MockBackend {
         unimplemented!()
     }
 
+    fn contains_all(&self, _keys: &[DbKey]) -> Result<Vec<bool>, ChainStorageError> {
+        unimplemented!()
+    }
+
     fn fetch_mmr_root(&self, _tree: MmrTree) -> Result<HashOutput, ChainStorageError> {
         unimplemented!()
     }
@@ -75,10 +91,28 @@ impl BlockchainBackend for MockBackend {
         unimplemented!()
     }
 
+    fn fetch_block_kernel_proofs(
+        &self,
+        _height: u64,
+    ) -> Result<Vec<(TransactionKernel, MerkleProof)>, ChainStorageError>
+    {
+        unimplemented!()
+    }
+
     fn fetch_mmr_node(&self, _tree: MmrTree, _pos: u32) -> Result<(Hash, bool), ChainStorageError> {
         unimplemented!()
     }
 
+    fn fetch_mmr_nodes(
+        &self,
+        _tree: MmrTree,
+        _start_pos: u32,
+        _count: u32,
+    ) -> Result<Vec<(Hash, bool)>, ChainStorageError>
+    {
+        unimplemented!()
+    }
+
     fn fetch_mmr_base_leaf_nodes(
         &self,
         _tree: MmrTree,
@@ -93,6 +127,14 @@ impl BlockchainBackend for MockBackend {
         unimplemented!()
     }
 
+    fn pending_pruned_checkpoints(&self, _tree: MmrTree) -> Result<usize, ChainStorageError> {
+        unimplemented!()
+    }
+
+    fn fetch_deleted_leaf_indices(&self, _tree: MmrTree) -> Result<Vec<u32>, ChainStorageError> {
+        unimplemented!()
+    }
+
     fn assign_mmr(&self, _tree: MmrTree, _base_state: MutableMmrLeafNodes) -> Result<(), ChainStorageError> {
         unimplemented!()
     }
@@ -105,7 +147,48 @@ impl BlockchainBackend for MockBackend {
         unimplemented!()
     }
 
+    fn for_each_utxo<F>(&self, _f: F) -> Result<(), ChainStorageError>
+    where
+        Self: Sized,
+        F: FnMut(Result<(HashOutput, TransactionOutput), ChainStorageError>),
+    {
+        unimplemented!()
+    }
+
+    fn fetch_counts(&self) -> Result<ChainCounts, ChainStorageError> {
+        unimplemented!()
+    }
+
+    fn fetch_utxos_paged(
+        &self,
+        _offset: usize,
+        _limit: usize,
+    ) -> Result<Vec<(HashOutput, TransactionOutput)>, ChainStorageError>
+    {
+        unimplemented!()
+    }
+
+    fn prune_orphans_below(&self, _height: u64) -> Result<usize, ChainStorageError> {
+        unimplemented!()
+    }
+
     fn fetch_last_header(&self) -> Result<Option<BlockHeader>, ChainStorageError> {
         unimplemented!()
     }
+
+    fn fetch_last_n_headers(&self, _n: usize) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        unimplemented!()
+    }
+
+    fn fetch_headers(&self, _start: u64, _end_inclusive: u64) -> Result<Vec<BlockHeader>, ChainStorageError> {
+        unimplemented!()
+    }
+
+    fn fetch_utxo_with_position(
+        &self,
+        _hash: &HashOutput,
+    ) -> Result<Option<(TransactionOutput, usize)>, ChainStorageError>
+    {
+        unimplemented!()
+    }
 }
@@ -20,20 +20,25 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::collections::HashMap;
 use tari_core::{
     blocks::BlockHeader,
     chain_storage::{
         create_lmdb_database,
         BlockchainBackend,
+        ChainCounts,
+        ChainStorageError,
         DbKey,
         DbKeyValuePair,
         DbTransaction,
+        DbTransactionBuilder,
         DbValue,
         MemoryDatabase,
         MetadataKey,
         MetadataValue,
         MmrTree,
     },
+    consensus::emission::EmissionSchedule,
     helpers::create_orphan_block,
 };
 use tari_mmr::{Hash, MerkleChangeTrackerConfig, MutableMmr};
@@ -130,6 +135,49 @@ fn lmdb_insert_contains_delete_and_fetch_utxo() {
     insert_contains_delete_and_fetch_utxo(db);
 }
 
+fn insert_utxo_without_range_proof_leaf<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let hash2 = utxo2.hash();
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1, true);
+    assert!(db.write(txn).is_ok());
+    let utxo_root = db.fetch_mmr_root(MmrTree::Utxo).unwrap();
+    let rp_root = db.fetch_mmr_root(MmrTree::RangeProof).unwrap();
+
+    // The MMRs are not updated, so the range proof of the UTXO is not in the range proof MMR
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo2, false);
+    let err = match db.write(txn) {
+        Err(ChainStorageError::OperationFailed { source, .. }) => *source,
+        result => result.unwrap_err(),
+    };
+    match err {
+        ChainStorageError::UnexpectedResult(_) => {},
+        err => panic!("Unexpected error: {:?}", err),
+    }
+    assert_eq!(db.contains(&DbKey::UnspentOutput(hash2)), Ok(false));
+    assert_eq!(db.fetch_mmr_root(MmrTree::Utxo).unwrap(), utxo_root);
+    assert_eq!(db.fetch_mmr_root(MmrTree::RangeProof).unwrap(), rp_root);
+}
+
+#[test]
+fn memory_insert_utxo_without_range_proof_leaf() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    insert_utxo_without_range_proof_leaf(db);
+}
+
+#[test]
+fn lmdb_insert_utxo_without_range_proof_leaf() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    insert_utxo_without_range_proof_leaf(db);
+}
+
 fn insert_contains_delete_and_fetch_kernel<T: BlockchainBackend>(db: T) {
     let kernel = create_test_kernel(5.into(), 0);
     let hash = kernel.hash();
@@ -169,6 +217,50 @@ fn lmdb_insert_contains_delete_and_fetch_kernel() {
     insert_contains_delete_and_fetch_kernel(db);
 }
 
+fn fetch_counts<T: BlockchainBackend>(db: T) {
+    assert_eq!(db.fetch_counts(), Ok(ChainCounts::default()));
+
+    let factories = CryptoFactories::default();
+    let header1 = BlockHeader::new(0);
+    let header2 = BlockHeader::from_previous(&header1);
+    let txs = vec![(tx!(1000.into(), fee: 20.into(), inputs: 2, outputs: 1)).0];
+    let mut txn = DbTransaction::new();
+    txn.insert_header(header1);
+    txn.insert_header(header2);
+    for i in 1..=3 {
+        txn.insert_utxo(create_utxo(MicroTari(i * 5_000), &factories).0, true);
+    }
+    txn.insert_orphan(create_orphan_block(10, txs));
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(
+        db.fetch_counts(),
+        Ok(ChainCounts {
+            headers: 2,
+            utxos: 3,
+            stxos: 0,
+            kernels: 0,
+            orphans: 1,
+        })
+    );
+}
+
+#[test]
+fn memory_fetch_counts() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_counts(db);
+}
+
+#[test]
+fn lmdb_fetch_counts() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_counts(db);
+}
+
 fn insert_contains_delete_and_fetch_orphan<T: BlockchainBackend>(db: T) {
     let txs = vec![
         (tx!(1000.into(), fee: 20.into(), inputs: 2, outputs: 1)).0,
@@ -211,6 +303,121 @@ fn lmdb_insert_contains_delete_and_fetch_orphan() {
     insert_contains_delete_and_fetch_orphan(db);
 }
 
+fn insert_duplicate_orphan<T: BlockchainBackend>(db: T) {
+    let txs = vec![(tx!(1000.into(), fee: 20.into(), inputs: 2, outputs: 1)).0];
+    let orphan = create_orphan_block(10, txs);
+    let hash = orphan.hash();
+
+    let mut txn = DbTransaction::new();
+    txn.insert_orphan(orphan.clone());
+    assert!(db.write(txn).is_ok());
+    // Re-inserting the same orphan is a no-op
+    let mut txn = DbTransaction::new();
+    txn.insert_orphan(orphan.clone());
+    assert!(db.write(txn).is_ok());
+
+    match db.fetch(&DbKey::OrphanBlock(hash)).unwrap() {
+        Some(DbValue::OrphanBlock(retrieved_orphan)) => assert_eq!(*retrieved_orphan, orphan),
+        _ => panic!("Orphan block not found"),
+    }
+    let mut orphan_count = 0;
+    db.for_each_orphan(|_| orphan_count += 1).unwrap();
+    assert_eq!(orphan_count, 1);
+}
+
+#[test]
+fn memory_insert_duplicate_orphan() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    insert_duplicate_orphan(db);
+}
+
+#[test]
+fn lmdb_insert_duplicate_orphan() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    insert_duplicate_orphan(db);
+}
+
+fn contains_all_utxos<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let (utxo3, _) = create_utxo(MicroTari(20_000), &factories);
+    let keys = vec![
+        DbKey::UnspentOutput(utxo1.hash()),
+        DbKey::UnspentOutput(utxo2.hash()),
+        DbKey::UnspentOutput(utxo3.hash()),
+        DbKey::UnspentOutput(utxo1.hash()),
+    ];
+    assert_eq!(db.contains_all(&keys), Ok(vec![false, false, false, false]));
+
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1, true);
+    txn.insert_utxo(utxo3, true);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.contains_all(&keys), Ok(vec![true, false, true, true]));
+    assert_eq!(db.contains_all(&[]), Ok(Vec::new()));
+}
+
+#[test]
+fn memory_contains_all_utxos() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    contains_all_utxos(db);
+}
+
+#[test]
+fn lmdb_contains_all_utxos() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    contains_all_utxos(db);
+}
+
+fn fetch_utxos_paged<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let utxos = (1..=6)
+        .map(|i| create_utxo(MicroTari(i * 1_000), &factories).0)
+        .collect::<Vec<_>>();
+    assert_eq!(db.fetch_utxos_paged(0, 2), Ok(Vec::new()));
+
+    let mut txn = DbTransaction::new();
+    utxos.iter().for_each(|utxo| txn.insert_utxo(utxo.clone(), true));
+    assert!(db.write(txn).is_ok());
+
+    let mut fetched = Vec::new();
+    for page in 0..3 {
+        let utxo_page = db.fetch_utxos_paged(page * 2, 2).unwrap();
+        assert_eq!(utxo_page.len(), 2);
+        fetched.extend(utxo_page);
+    }
+    let expected = utxos.iter().map(|utxo| (utxo.hash(), utxo.clone())).collect::<Vec<_>>();
+    assert_eq!(fetched, expected);
+    assert_eq!(db.fetch_utxos_paged(5, 2), Ok(vec![expected[5].clone()]));
+    assert_eq!(db.fetch_utxos_paged(6, 2), Ok(Vec::new()));
+    assert_eq!(db.fetch_utxos_paged(100, 2), Ok(Vec::new()));
+}
+
+#[test]
+fn memory_fetch_utxos_paged() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_utxos_paged(db);
+}
+
+#[test]
+fn lmdb_fetch_utxos_paged() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_utxos_paged(db);
+}
+
 fn spend_utxo_and_unspend_stxo<T: BlockchainBackend>(db: T) {
     let factories = CryptoFactories::default();
     let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
@@ -687,6 +894,106 @@ fn lmdb_commit_block_and_create_fetch_checkpoint_and_rewind_mmr() {
     commit_block_and_create_fetch_checkpoint_and_rewind_mmr(db);
 }
 
+fn reset_and_rewind_mmr<T: BlockchainBackend>(db: T) {
+    let kernel1 = create_test_kernel(100.into(), 0);
+    let kernel2 = create_test_kernel(200.into(), 0);
+    let kernel3 = create_test_kernel(300.into(), 0);
+
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel1, true);
+    txn.commit_block();
+    assert!(db.write(txn).is_ok());
+    let root1 = db.fetch_mmr_only_root(MmrTree::Kernel).unwrap();
+
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel2, true);
+    txn.commit_block();
+    assert!(db.write(txn).is_ok());
+    let root2 = db.fetch_mmr_only_root(MmrTree::Kernel).unwrap();
+    assert_ne!(root1, root2);
+
+    // Uncommitted changes are discarded by a reset
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel3, true);
+    assert!(db.write(txn).is_ok());
+    assert_ne!(db.fetch_mmr_only_root(MmrTree::Kernel).unwrap(), root2);
+    let mut txn = DbTransaction::new();
+    txn.reset_mmr(MmrTree::Kernel);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.fetch_mmr_only_root(MmrTree::Kernel).unwrap(), root2);
+
+    // Rewinding by zero checkpoints is rejected
+    let mut txn = DbTransaction::new();
+    txn.rewind_kernel_mmr(0);
+    assert!(db.write(txn).is_err());
+    assert_eq!(db.fetch_mmr_only_root(MmrTree::Kernel).unwrap(), root2);
+
+    let mut txn = DbTransaction::new();
+    txn.rewind_kernel_mmr(1);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.fetch_mmr_only_root(MmrTree::Kernel).unwrap(), root1);
+}
+
+#[test]
+fn memory_reset_and_rewind_mmr() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    reset_and_rewind_mmr(db);
+}
+
+#[test]
+fn lmdb_reset_and_rewind_mmr() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    reset_and_rewind_mmr(db);
+}
+
+fn pending_pruned_checkpoints<T: BlockchainBackend>(db: T) {
+    // Both backends are configured with min_history_len = 2 and max_history_len = 3. Every checkpoint adds a single
+    // kernel, so the number of leaf nodes in the base MMR equals the number of checkpoints merged into it.
+    let commit_kernel = |fee: u64| {
+        let mut txn = DbTransaction::new();
+        txn.insert_kernel(create_test_kernel(fee.into(), 0), true);
+        txn.commit_block();
+        assert!(db.write(txn).is_ok());
+    };
+
+    for fee in 1..=3 {
+        assert_eq!(db.pending_pruned_checkpoints(MmrTree::Kernel), Ok(0));
+        commit_kernel(fee * 100);
+    }
+    assert_eq!(db.fetch_mmr_base_leaf_node_count(MmrTree::Kernel), Ok(0));
+
+    // The next checkpoint exceeds max_history_len, so checkpoints are merged into the base MMR
+    let predicted = db.pending_pruned_checkpoints(MmrTree::Kernel).unwrap();
+    assert_eq!(predicted, 2);
+    commit_kernel(400);
+    assert_eq!(db.fetch_mmr_base_leaf_node_count(MmrTree::Kernel), Ok(predicted));
+    assert_eq!(db.pending_pruned_checkpoints(MmrTree::Kernel), Ok(0));
+}
+
+#[test]
+fn memory_pending_pruned_checkpoints() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 2,
+        max_history_len: 3,
+    };
+    let db = MemoryDatabase::<HashDigest>::new(mct_config);
+    pending_pruned_checkpoints(db);
+}
+
+#[test]
+fn lmdb_pending_pruned_checkpoints() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 2,
+        max_history_len: 3,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    pending_pruned_checkpoints(db);
+}
+
 // TODO: Test Needed: fetch_mmr_node
 
 fn for_each_orphan<T: BlockchainBackend>(db: T) {
@@ -740,6 +1047,46 @@ fn lmdb_for_each_orphan() {
     for_each_orphan(db);
 }
 
+fn for_each_utxo<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let (utxo3, _) = create_utxo(MicroTari(20_000), &factories);
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1.clone(), true);
+    txn.insert_utxo(utxo2.clone(), true);
+    txn.insert_utxo(utxo3.clone(), true);
+    assert!(db.write(txn).is_ok());
+
+    let mut utxos = HashMap::new();
+    assert!(db
+        .for_each_utxo(|pair| {
+            let (hash, utxo) = pair.unwrap();
+            utxos.insert(hash, utxo);
+        })
+        .is_ok());
+    assert_eq!(utxos.len(), 3);
+    assert_eq!(utxos.get(&utxo1.hash()), Some(&utxo1));
+    assert_eq!(utxos.get(&utxo2.hash()), Some(&utxo2));
+    assert_eq!(utxos.get(&utxo3.hash()), Some(&utxo3));
+}
+
+#[test]
+fn memory_for_each_utxo() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    for_each_utxo(db);
+}
+
+#[test]
+fn lmdb_for_each_utxo() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    for_each_utxo(db);
+}
+
 #[test]
 fn lmdb_backend_restore() {
     let factories = CryptoFactories::default();
@@ -1176,3 +1523,628 @@ fn lmdb_fetch_last_header() {
     let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
     fetch_last_header(db);
 }
+
+#[test]
+fn memory_write_reports_failed_operation_index() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    let mut header0 = BlockHeader::new(0);
+    header0.height = 0;
+    let mut header1 = BlockHeader::new(0);
+    header1.height = 1;
+
+    let mut txn = DbTransaction::new();
+    txn.insert_header(header0.clone());
+    txn.insert_header(header1);
+    txn.insert_header(header0);
+    match db.write(txn) {
+        Err(ChainStorageError::OperationFailed { index, source }) => {
+            assert_eq!(index, 2);
+            assert_eq!(*source, ChainStorageError::KeyExists {
+                key: DbKey::BlockHeader(0).to_string()
+            });
+        },
+        result => panic!("Unexpected write result: {:?}", result),
+    }
+}
+
+#[test]
+fn memory_duplicate_kernel_insert() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    let kernel = create_test_kernel(5.into(), 0);
+    let hash = kernel.hash();
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel.clone(), true);
+    assert!(db.write(txn).is_ok());
+
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel, true);
+    match db.write(txn) {
+        Err(ChainStorageError::OperationFailed { index, source }) => {
+            assert_eq!(index, 0);
+            match *source {
+                ChainStorageError::KeyExists { key } => {
+                    assert!(!key.is_empty());
+                    assert_eq!(key, DbKey::TransactionKernel(hash).to_string());
+                },
+                err => panic!("Unexpected error: {:?}", err),
+            }
+        },
+        result => panic!("Unexpected write result: {:?}", result),
+    }
+}
+
+#[test]
+fn memory_write_transaction_from_builder() {
+    let factories = CryptoFactories::default();
+    let db = MemoryDatabase::<HashDigest>::default();
+    let header = BlockHeader::new(0);
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let hash1 = utxo1.hash();
+    let hash2 = utxo2.hash();
+
+    let txn = DbTransactionBuilder::new()
+        .insert_header(header.clone())
+        .insert_utxo(utxo1, true)
+        .insert_utxo(utxo2, true)
+        .create_checkpoint(MmrTree::Utxo)
+        .spend_utxo(hash1.clone())
+        .build();
+    assert_eq!(txn.operations.len(), 5);
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(db.fetch_last_header(), Ok(Some(header)));
+    assert_eq!(db.contains(&DbKey::UnspentOutput(hash1.clone())), Ok(false));
+    assert_eq!(db.contains(&DbKey::SpentOutput(hash1)), Ok(true));
+    assert_eq!(db.contains(&DbKey::UnspentOutput(hash2)), Ok(true));
+}
+
+fn header_connects_to_tip<T: BlockchainBackend>(db: T) {
+    let header0 = BlockHeader::new(0);
+    let header1 = BlockHeader::from_previous(&header0);
+    assert_eq!(db.header_connects_to_tip(&header1), Ok(false));
+
+    let mut txn = DbTransaction::new();
+    txn.insert_header(header0.clone());
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.header_connects_to_tip(&header1), Ok(true));
+
+    let mut orphan_header = BlockHeader::from_previous(&header0);
+    orphan_header.prev_hash = vec![1u8; 32];
+    assert_eq!(db.header_connects_to_tip(&orphan_header), Ok(false));
+
+    let mut txn = DbTransaction::new();
+    txn.insert_header(header1.clone());
+    assert!(db.write(txn).is_ok());
+    // header1 no longer builds on the tip, since it is the tip
+    assert_eq!(db.header_connects_to_tip(&header1), Ok(false));
+    assert_eq!(
+        db.header_connects_to_tip(&BlockHeader::from_previous(&header1)),
+        Ok(true)
+    );
+}
+
+#[test]
+fn memory_header_connects_to_tip() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    header_connects_to_tip(db);
+}
+
+#[test]
+fn lmdb_header_connects_to_tip() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    header_connects_to_tip(db);
+}
+
+fn fetch_spent_outputs<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let (utxo3, _) = create_utxo(MicroTari(20_000), &factories);
+    let hash1 = utxo1.hash();
+    let hash2 = utxo2.hash();
+    let hash3 = utxo3.hash();
+
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1.clone(), true);
+    txn.insert_utxo(utxo2, true);
+    txn.insert_utxo(utxo3.clone(), true);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(
+        db.fetch_spent_outputs(&[hash1.clone(), hash3.clone()]),
+        Ok(vec![None, None])
+    );
+
+    let mut txn = DbTransaction::new();
+    txn.spend_utxo(hash1.clone());
+    txn.spend_utxo(hash3.clone());
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(
+        db.fetch_spent_outputs(&[hash3.clone(), hash2, hash1.clone()]),
+        Ok(vec![Some(utxo3), None, Some(utxo1)])
+    );
+    assert_eq!(db.fetch_spent_outputs(&[]), Ok(vec![]));
+}
+
+#[test]
+fn memory_fetch_spent_outputs() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_spent_outputs(db);
+}
+
+#[test]
+fn lmdb_fetch_spent_outputs() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_spent_outputs(db);
+}
+
+fn fetch_utxo_with_position<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let hash1 = utxo1.hash();
+    let hash2 = utxo2.hash();
+    let mut range_proof_mmr = MutableMmr::<HashDigest, _>::new(Vec::new());
+    range_proof_mmr.push(&utxo1.proof().hash()).unwrap();
+    range_proof_mmr.push(&utxo2.proof().hash()).unwrap();
+    let index1 = range_proof_mmr.find_leaf_index(&utxo1.proof().hash()).unwrap().unwrap();
+    let index2 = range_proof_mmr.find_leaf_index(&utxo2.proof().hash()).unwrap().unwrap();
+
+    assert_eq!(db.fetch_utxo_with_position(&hash1), Ok(None));
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1.clone(), true);
+    txn.insert_utxo(utxo2.clone(), true);
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(db.fetch_utxo_with_position(&hash1), Ok(Some((utxo1, index1))));
+    assert_eq!(db.fetch_utxo_with_position(&hash2), Ok(Some((utxo2, index2))));
+
+    let mut txn = DbTransaction::new();
+    txn.spend_utxo(hash1.clone());
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.fetch_utxo_with_position(&hash1), Ok(None));
+}
+
+#[test]
+fn memory_fetch_utxo_with_position() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_utxo_with_position(db);
+}
+
+#[test]
+fn lmdb_fetch_utxo_with_position() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_utxo_with_position(db);
+}
+
+fn fetch_deleted_leaf_indices<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let (utxo3, _) = create_utxo(MicroTari(20_000), &factories);
+    let hash1 = utxo1.hash();
+    let hash3 = utxo3.hash();
+
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1, true);
+    txn.insert_utxo(utxo2, true);
+    txn.insert_utxo(utxo3, true);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.fetch_deleted_leaf_indices(MmrTree::Utxo), Ok(vec![]));
+
+    let mut txn = DbTransaction::new();
+    txn.spend_utxo(hash3);
+    txn.spend_utxo(hash1);
+    assert!(db.write(txn).is_ok());
+    assert_eq!(db.fetch_deleted_leaf_indices(MmrTree::Utxo), Ok(vec![0, 2]));
+    assert_eq!(db.fetch_deleted_leaf_indices(MmrTree::Kernel), Ok(vec![]));
+    assert_eq!(db.fetch_deleted_leaf_indices(MmrTree::RangeProof), Ok(vec![]));
+}
+
+#[test]
+fn memory_fetch_deleted_leaf_indices() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_deleted_leaf_indices(db);
+}
+
+#[test]
+fn lmdb_fetch_deleted_leaf_indices() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_deleted_leaf_indices(db);
+}
+
+fn calculate_mmr_root_with_missing_deletion<T: BlockchainBackend>(db: T) {
+    let factories = CryptoFactories::default();
+    let (utxo1, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(15_000), &factories);
+    let utxo_hash1 = utxo1.hash();
+    let utxo_hash2 = utxo2.hash();
+
+    // Deleting from an empty UTXO set
+    assert_eq!(
+        db.calculate_mmr_root(MmrTree::Utxo, vec![], vec![utxo_hash1.clone()]),
+        Err(ChainStorageError::ValueNotFound(DbKey::UnspentOutput(
+            utxo_hash1.clone()
+        )))
+    );
+
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1, true);
+    assert!(db.write(txn).is_ok());
+    assert!(db
+        .calculate_mmr_root(MmrTree::Utxo, vec![], vec![utxo_hash1.clone()])
+        .is_ok());
+    assert_eq!(
+        db.calculate_mmr_root(MmrTree::Utxo, vec![], vec![utxo_hash1, utxo_hash2.clone()]),
+        Err(ChainStorageError::ValueNotFound(DbKey::UnspentOutput(utxo_hash2)))
+    );
+}
+
+#[test]
+fn memory_calculate_mmr_root_with_missing_deletion() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    calculate_mmr_root_with_missing_deletion(db);
+}
+
+#[test]
+fn lmdb_calculate_mmr_root_with_missing_deletion() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    calculate_mmr_root_with_missing_deletion(db);
+}
+
+fn build_block_locator<T: BlockchainBackend>(db: T) {
+    assert_eq!(db.build_block_locator(), Ok(Vec::new()));
+
+    let mut hashes = Vec::new();
+    let mut txn = DbTransaction::new();
+    for height in 0..20 {
+        let mut header = BlockHeader::new(0);
+        header.height = height;
+        header.nonce = height;
+        hashes.push(header.hash());
+        txn.insert_header(header);
+    }
+    assert!(db.write(txn).is_ok());
+
+    let expected = [19, 18, 17, 15, 11, 3, 0]
+        .iter()
+        .map(|height| hashes[*height].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(db.build_block_locator(), Ok(expected));
+}
+
+#[test]
+fn memory_build_block_locator() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    build_block_locator(db);
+}
+
+#[test]
+fn lmdb_build_block_locator() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    build_block_locator(db);
+}
+
+fn compute_reorg_depth<T: BlockchainBackend>(db: T) {
+    let mut headers = vec![BlockHeader::new(0)];
+    for _ in 1..10 {
+        let header = BlockHeader::from_previous(headers.last().unwrap());
+        headers.push(header);
+    }
+    let mut txn = DbTransaction::new();
+    headers.iter().for_each(|header| txn.insert_header(header.clone()));
+    assert!(db.write(txn).is_ok());
+
+    // A competing chain that forks 3 blocks back from the tip at height 9
+    let mut competing_headers = vec![headers[5].clone(), headers[6].clone()];
+    for _ in 0..4 {
+        let mut header = BlockHeader::from_previous(competing_headers.last().unwrap());
+        header.nonce = 1;
+        competing_headers.push(header);
+    }
+    assert_eq!(db.compute_reorg_depth(&competing_headers), Ok(3));
+    assert_eq!(db.compute_reorg_depth(&competing_headers[2..]), Ok(3));
+
+    // A competing chain that extends the tip directly
+    let extension = vec![BlockHeader::from_previous(&headers[9])];
+    assert_eq!(db.compute_reorg_depth(&extension), Ok(0));
+    assert_eq!(db.compute_reorg_depth(&headers[3..]), Ok(0));
+
+    // A competing chain that does not connect to the current chain
+    let mut disconnected = BlockHeader::new(0);
+    disconnected.prev_hash = vec![1; 32];
+    assert!(db.compute_reorg_depth(&[disconnected]).is_err());
+}
+
+fn fetch_headers<T: BlockchainBackend>(db: T) {
+    let mut headers = vec![BlockHeader::new(0)];
+    for _ in 1..10 {
+        let header = BlockHeader::from_previous(headers.last().unwrap());
+        headers.push(header);
+    }
+    let mut txn = DbTransaction::new();
+    headers.iter().for_each(|header| txn.insert_header(header.clone()));
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(db.fetch_headers(3, 7), Ok(headers[3..=7].to_vec()));
+    assert_eq!(db.fetch_headers(9, 9), Ok(vec![headers[9].clone()]));
+    assert_eq!(
+        db.fetch_headers(8, 11),
+        Err(ChainStorageError::ValueNotFound(DbKey::BlockHeader(10)))
+    );
+    assert!(db.fetch_headers(7, 3).is_err());
+}
+
+#[test]
+fn memory_fetch_headers() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_headers(db);
+}
+
+#[test]
+fn lmdb_fetch_headers() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_headers(db);
+}
+
+fn fetch_last_n_headers<T: BlockchainBackend>(db: T) {
+    assert_eq!(db.fetch_last_n_headers(3), Ok(Vec::new()));
+    let mut headers = vec![BlockHeader::new(0)];
+    for _ in 1..5 {
+        let header = BlockHeader::from_previous(headers.last().unwrap());
+        headers.push(header);
+    }
+    let mut txn = DbTransaction::new();
+    headers.iter().for_each(|header| txn.insert_header(header.clone()));
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(
+        db.fetch_last_n_headers(3),
+        Ok(vec![headers[4].clone(), headers[3].clone(), headers[2].clone()])
+    );
+    let mut all_headers = headers.clone();
+    all_headers.reverse();
+    assert_eq!(db.fetch_last_n_headers(10), Ok(all_headers));
+    assert_eq!(db.fetch_last_n_headers(0), Ok(Vec::new()));
+}
+
+#[test]
+fn memory_fetch_last_n_headers() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_last_n_headers(db);
+}
+
+#[test]
+fn lmdb_fetch_last_n_headers() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_last_n_headers(db);
+}
+
+fn horizon_block_height_without_headers<T: BlockchainBackend>(db: T) {
+    for i in 0..3 {
+        let txn = DbTransactionBuilder::new()
+            .insert_kernel(create_test_kernel(100.into(), i), true)
+            .create_checkpoint(MmrTree::Kernel)
+            .build();
+        assert!(db.write(txn).is_ok());
+    }
+    assert_eq!(db.fetch_horizon_block_height(), Ok(0));
+}
+
+#[test]
+fn memory_horizon_block_height_without_headers() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    horizon_block_height_without_headers(db);
+}
+
+#[test]
+fn lmdb_horizon_block_height_without_headers() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    horizon_block_height_without_headers(db);
+}
+
+fn calculate_emission_at_tip<T: BlockchainBackend>(db: T) {
+    let schedule = EmissionSchedule::new(MicroTari(1_000), 0.5, MicroTari(10));
+    assert_eq!(db.calculate_emission_at_tip(&schedule), Ok(MicroTari(0)));
+
+    let mut headers = vec![BlockHeader::new(0)];
+    for _ in 1..4 {
+        let header = BlockHeader::from_previous(headers.last().unwrap());
+        headers.push(header);
+    }
+    let mut txn = DbTransaction::new();
+    headers.into_iter().for_each(|header| txn.insert_header(header));
+    assert!(db.write(txn).is_ok());
+
+    // Block rewards of 1010, 510, 260 and 135
+    assert_eq!(db.calculate_emission_at_tip(&schedule), Ok(MicroTari(1_915)));
+}
+
+#[test]
+fn memory_calculate_emission_at_tip() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    calculate_emission_at_tip(db);
+}
+
+#[test]
+fn lmdb_calculate_emission_at_tip() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    calculate_emission_at_tip(db);
+}
+
+#[test]
+fn memory_compute_reorg_depth() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    compute_reorg_depth(db);
+}
+
+#[test]
+fn lmdb_compute_reorg_depth() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    compute_reorg_depth(db);
+}
+
+fn prune_orphans_below<T: BlockchainBackend>(db: T) {
+    let orphans = [1, 5, 10]
+        .iter()
+        .map(|height| create_orphan_block(*height, vec![]))
+        .collect::<Vec<_>>();
+    let mut txn = DbTransaction::new();
+    for orphan in orphans.iter() {
+        txn.insert_orphan(orphan.clone());
+    }
+    assert!(db.write(txn).is_ok());
+
+    assert_eq!(db.prune_orphans_below(6).unwrap(), 2);
+    assert_eq!(db.contains(&DbKey::OrphanBlock(orphans[0].hash())), Ok(false));
+    assert_eq!(db.contains(&DbKey::OrphanBlock(orphans[1].hash())), Ok(false));
+    assert_eq!(db.contains(&DbKey::OrphanBlock(orphans[2].hash())), Ok(true));
+    assert_eq!(db.prune_orphans_below(6).unwrap(), 0);
+}
+
+#[test]
+fn memory_prune_orphans_below() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    prune_orphans_below(db);
+}
+
+#[test]
+fn lmdb_prune_orphans_below() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    prune_orphans_below(db);
+}
+
+fn fetch_block_kernel_proofs<T: BlockchainBackend>(db: T) {
+    let kernels = (0..3).map(|i| create_test_kernel(100.into(), i)).collect::<Vec<_>>();
+
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernels[0].clone(), true);
+    txn.insert_kernel(kernels[1].clone(), true);
+    txn.commit_block();
+    assert!(db.write(txn).is_ok());
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernels[2].clone(), true);
+    txn.commit_block();
+    assert!(db.write(txn).is_ok());
+
+    let root = db.fetch_mmr_only_root(MmrTree::Kernel).unwrap();
+    let proofs = db.fetch_block_kernel_proofs(0).unwrap();
+    assert_eq!(proofs.len(), 2);
+    for (i, (kernel, proof)) in proofs.iter().enumerate() {
+        assert_eq!(*kernel, kernels[i]);
+        assert!(proof.verify_leaf::<HashDigest>(&root, &kernel.hash(), i).is_ok());
+    }
+    let proofs = db.fetch_block_kernel_proofs(1).unwrap();
+    assert_eq!(proofs.len(), 1);
+    assert_eq!(proofs[0].0, kernels[2]);
+    assert!(proofs[0]
+        .1
+        .verify_leaf::<HashDigest>(&root, &kernels[2].hash(), 2)
+        .is_ok());
+    assert!(db.fetch_block_kernel_proofs(2).is_err());
+}
+
+#[test]
+fn memory_fetch_block_kernel_proofs() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_block_kernel_proofs(db);
+}
+
+#[test]
+fn lmdb_fetch_block_kernel_proofs() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_block_kernel_proofs(db);
+}
+
+fn fetch_mmr_nodes<T: BlockchainBackend>(db: T) {
+    let kernels = (0..5).map(|i| create_test_kernel(100.into(), i)).collect::<Vec<_>>();
+    let hashes = kernels.iter().map(|kernel| kernel.hash()).collect::<Vec<_>>();
+
+    let mut txn = DbTransaction::new();
+    for kernel in kernels {
+        txn.insert_kernel(kernel, true);
+    }
+    txn.commit_block();
+    assert!(db.write(txn).is_ok());
+
+    let nodes = db.fetch_mmr_nodes(MmrTree::Kernel, 0, 5).unwrap();
+    assert_eq!(nodes.len(), 5);
+    for (i, (hash, deleted)) in nodes.iter().enumerate() {
+        assert_eq!(*hash, hashes[i]);
+        assert!(!deleted);
+        assert_eq!(
+            db.fetch_mmr_node(MmrTree::Kernel, i as u32).unwrap(),
+            (hash.clone(), *deleted)
+        );
+    }
+    assert!(db.fetch_mmr_nodes(MmrTree::Kernel, 3, 5).is_err());
+}
+
+#[test]
+fn memory_fetch_mmr_nodes() {
+    let db = MemoryDatabase::<HashDigest>::default();
+    fetch_mmr_nodes(db);
+}
+
+#[test]
+fn lmdb_fetch_mmr_nodes() {
+    let mct_config = MerkleChangeTrackerConfig {
+        min_history_len: 10,
+        max_history_len: 20,
+    };
+    let db = create_lmdb_database(&create_temporary_data_path(), mct_config).unwrap();
+    fetch_mmr_nodes(db);
+}
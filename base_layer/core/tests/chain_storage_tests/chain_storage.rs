@@ -29,6 +29,7 @@ use std::thread;
 use tari_core::{
     blocks::{Block, BlockHeader},
     chain_storage::{
+        deserialize_mmr_proof,
         BlockAddResult,
         BlockchainDatabase,
         ChainStorageError,
@@ -37,6 +38,7 @@ use tari_core::{
         MemoryDatabase,
         MmrTree,
         Validators,
+        MMR_PROOF_SERIALIZATION_VERSION,
     },
     helpers::{create_mem_db, create_orphan_block},
     validation::mocks::MockValidator,
@@ -335,6 +337,55 @@ fn kernel_mmr_proof() {
     assert!(proof3.verify_leaf::<HashDigest>(&root, &kernel3.hash(), 2).is_ok());
 }
 
+#[test]
+fn verify_mmr_proof() {
+    let store = create_mem_db();
+
+    let kernel1 = create_test_kernel(100.into(), 0);
+    let kernel2 = create_test_kernel(200.into(), 1);
+    let kernel3 = create_test_kernel(300.into(), 2);
+    let mut txn = DbTransaction::new();
+    txn.insert_kernel(kernel1.clone(), true);
+    txn.insert_kernel(kernel2.clone(), true);
+    txn.insert_kernel(kernel3.clone(), true);
+    assert!(store.commit(txn).is_ok());
+
+    assert_eq!(store.verify_mmr_proof(MmrTree::Kernel, 0, &kernel1.hash()), Ok(true));
+    assert_eq!(store.verify_mmr_proof(MmrTree::Kernel, 2, &kernel3.hash()), Ok(true));
+    assert_eq!(store.verify_mmr_proof(MmrTree::Kernel, 1, &kernel3.hash()), Ok(false));
+    assert_eq!(store.verify_mmr_proof(MmrTree::Kernel, 1, &[0u8; 32]), Ok(false));
+    assert!(store.verify_mmr_proof(MmrTree::Kernel, 5, &kernel1.hash()).is_err());
+}
+
+#[test]
+fn mmr_proof_bytes() {
+    let store = create_mem_db();
+    let factories = CryptoFactories::default();
+
+    let (utxo1, _) = create_utxo(MicroTari(5_000), &factories);
+    let (utxo2, _) = create_utxo(MicroTari(10_000), &factories);
+    let (utxo3, _) = create_utxo(MicroTari(15_000), &factories);
+    let mut txn = DbTransaction::new();
+    txn.insert_utxo(utxo1.clone(), true);
+    txn.insert_utxo(utxo2.clone(), true);
+    txn.insert_utxo(utxo3.clone(), true);
+    assert!(store.commit(txn).is_ok());
+
+    let root = store.fetch_mmr_only_root(MmrTree::Utxo).unwrap();
+    let proof = store.fetch_mmr_proof(MmrTree::Utxo, 1).unwrap();
+    let bytes = store.fetch_mmr_proof_bytes(MmrTree::Utxo, 1).unwrap();
+    assert_eq!(bytes[0], MMR_PROOF_SERIALIZATION_VERSION);
+    let decoded = deserialize_mmr_proof(&bytes).unwrap();
+    assert_eq!(decoded, proof);
+    assert!(decoded.verify_leaf::<HashDigest>(&root, &utxo2.hash(), 1).is_ok());
+
+    let mut bad_version = bytes.clone();
+    bad_version[0] = MMR_PROOF_SERIALIZATION_VERSION + 1;
+    assert!(deserialize_mmr_proof(&bad_version).is_err());
+    assert!(deserialize_mmr_proof(&[]).is_err());
+    assert!(deserialize_mmr_proof(&bytes[..bytes.len() - 1]).is_err());
+}
+
 #[test]
 fn store_and_retrieve_block() {
     // Create new database
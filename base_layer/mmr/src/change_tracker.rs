@@ -140,6 +140,16 @@ where
             .map_err(|e| MerkleMountainRangeError::BackendError(e.to_string()))
     }
 
+    /// Returns the number of checkpoints that will be merged into the base MMR (and dropped from the checkpoint
+    /// history) when the next checkpoint is committed
+    pub fn pending_pruned_checkpoints(&self) -> Result<usize, MerkleMountainRangeError> {
+        if self.checkpoint_count()? + 1 > self.config.max_history_len {
+            Ok(self.hist_commit_count)
+        } else {
+            Ok(0)
+        }
+    }
+
     /// Push the given hash into the MMR and update the current change-set
     pub fn push(&mut self, hash: &Hash) -> Result<usize, MerkleMountainRangeError> {
         let result = self.mmr.push(hash)?;
@@ -318,6 +328,25 @@ where
     }
 }
 
+impl<D, BaseBackend, CpBackend> Clone for MerkleChangeTracker<D, BaseBackend, CpBackend>
+where
+    D: Digest,
+    BaseBackend: ArrayLike<Value = Hash> + Clone,
+    CpBackend: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            mmr: self.mmr.clone(),
+            checkpoints: self.checkpoints.clone(),
+            current_additions: self.current_additions.clone(),
+            current_deletions: self.current_deletions.clone(),
+            config: self.config,
+            hist_commit_count: self.hist_commit_count,
+        }
+    }
+}
+
 impl<D, BaseBackend, DiffBackend> Deref for MerkleChangeTracker<D, BaseBackend, DiffBackend>
 where
     D: Digest,
@@ -243,6 +243,17 @@ where
     }
 }
 
+impl<D, B> Clone for MerkleMountainRange<D, B>
+where B: ArrayLike + Clone
+{
+    fn clone(&self) -> Self {
+        Self {
+            hashes: self.hashes.clone(),
+            _hasher: PhantomData,
+        }
+    }
+}
+
 impl<D, B, B2> PartialEq<MerkleMountainRange<D, B2>> for MerkleMountainRange<D, B>
 where
     D: Digest,
@@ -112,6 +112,11 @@ where
         self.size as usize
     }
 
+    /// Returns the bitmap of leaf indices that have been marked for deletion
+    pub fn deleted(&self) -> &Bitmap {
+        &self.deleted
+    }
+
     /// Returns a merkle(ish) root for this merkle set.
     ///
     /// The root is calculated by concatenating the MMR merkle root with the compressed serialisation of the bitmap
@@ -248,6 +253,20 @@ where
     }
 }
 
+impl<D, B> Clone for MutableMmr<D, B>
+where
+    D: Digest,
+    B: ArrayLike<Value = Hash> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            mmr: self.mmr.clone(),
+            deleted: self.deleted.clone(),
+            size: self.size,
+        }
+    }
+}
+
 impl<D, B, B2> PartialEq<MutableMmr<D, B2>> for MutableMmr<D, B>
 where
     D: Digest,
@@ -71,6 +71,7 @@ fn delete() {
     assert!(mmr.delete_and_compress(0, false));
     assert!(mmr.delete_and_compress(2, false));
     assert!(mmr.delete_and_compress(4, true));
+    assert_eq!(mmr.deleted().to_vec(), vec![0, 2, 4]);
     let root = mmr.get_merkle_root().unwrap();
     assert_eq!(
         &root.to_hex(),
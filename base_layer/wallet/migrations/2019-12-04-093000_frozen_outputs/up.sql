@@ -0,0 +1 @@
+ALTER TABLE outputs ADD COLUMN frozen INTEGER NOT NULL DEFAULT 0;
\ No newline at end of file
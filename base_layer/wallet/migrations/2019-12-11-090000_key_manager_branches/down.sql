@@ -0,0 +1 @@
+DROP TABLE IF EXISTS key_manager_branches;
\ No newline at end of file
@@ -0,0 +1,4 @@
+CREATE TABLE key_manager_branches (
+    branch TEXT PRIMARY KEY NOT NULL,
+    key_index INTEGER NOT NULL
+);
\ No newline at end of file
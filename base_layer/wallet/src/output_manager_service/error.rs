@@ -25,7 +25,7 @@ use derive_error::Error;
 use diesel::result::Error as DieselError;
 use tari_key_manager::{key_manager::KeyManagerError, mnemonic::MnemonicError};
 use tari_service_framework::reply_channel::TransportChannelError;
-use tari_transactions::transaction_protocol::TransactionProtocolError;
+use tari_transactions::{tari_amount::MicroTari, transaction_protocol::TransactionProtocolError};
 use tari_utilities::ByteArrayError;
 use time::OutOfRangeError;
 
@@ -42,8 +42,13 @@ pub enum OutputManagerError {
     KeyManagerError(KeyManagerError),
     /// Not all the transaction inputs and outputs are present to be confirmed
     IncompleteTransaction,
-    /// Not enough funds to fulfil transaction
-    NotEnoughFunds,
+    /// Not enough funds to fulfil transaction. `required` is the amount plus the fee of a transaction spending every
+    /// available output and `available` is the total value of those outputs.
+    #[error(non_std, no_from)]
+    InsufficientFunds {
+        required: MicroTari,
+        available: MicroTari,
+    },
     /// Output already exists
     DuplicateOutput,
     /// Error sending a message to the public API
@@ -74,8 +79,22 @@ pub enum OutputManagerStorageError {
     ConversionError,
     /// Output has already been spent
     OutputAlreadySpent,
+    /// Output is already encumbered by a pending transaction
+    OutputAlreadyEncumbered,
     /// Key Manager not initialized
     KeyManagerNotInitialized,
+    /// The key manager index cannot be decremented below zero
+    KeyIndexUnderflow,
+    /// Outputs can only be imported into an empty store
+    StoreNotEmpty,
+    /// The operation cannot be buffered and is not allowed while a batch of writes is in progress
+    BatchInProgress,
+    /// The available outputs cannot be split as requested
+    #[error(msg_embedded, non_std, no_from)]
+    InvalidSplit(String),
+    /// The stored outputs do not add up to a consistent balance
+    #[error(msg_embedded, non_std, no_from)]
+    InconsistentBalance(String),
     OutOfRangeError(OutOfRangeError),
     R2d2Error,
     DieselError(DieselError),
@@ -39,6 +39,7 @@ use tower::Service;
 #[derive(Debug)]
 pub enum OutputManagerRequest {
     GetBalance,
+    GetConfirmedBalance,
     AddOutput(UnblindedOutput),
     GetRecipientKey((u64, MicroTari)),
     GetCoinbaseKey((u64, MicroTari, u64)),
@@ -51,6 +52,7 @@ pub enum OutputManagerRequest {
     GetSpentOutputs,
     GetUnspentOutputs,
     GetSeedWords,
+    SetChainHeight(u64),
 }
 
 /// API Reply enum
@@ -67,6 +69,7 @@ pub enum OutputManagerResponse {
     SpentOutputs(Vec<UnblindedOutput>),
     UnspentOutputs(Vec<UnblindedOutput>),
     SeedWords(Vec<String>),
+    ChainHeightSet,
 }
 
 #[derive(Clone)]
@@ -93,6 +96,14 @@ impl OutputManagerHandle {
         }
     }
 
+    /// Get the balance without the unconfirmed pending incoming funds, i.e. `pending_incoming_balance` is always zero
+    pub async fn get_confirmed_balance(&mut self) -> Result<Balance, OutputManagerError> {
+        match self.handle.call(OutputManagerRequest::GetConfirmedBalance).await?? {
+            OutputManagerResponse::Balance(b) => Ok(b),
+            _ => Err(OutputManagerError::UnexpectedApiResponse),
+        }
+    }
+
     pub async fn get_recipient_spending_key(
         &mut self,
         tx_id: u64,
@@ -126,6 +137,14 @@ impl OutputManagerHandle {
         }
     }
 
+    /// Set the current height of the base chain, which determines which outputs have matured and can be spent
+    pub async fn set_chain_height(&mut self, height: u64) -> Result<(), OutputManagerError> {
+        match self.handle.call(OutputManagerRequest::SetChainHeight(height)).await?? {
+            OutputManagerResponse::ChainHeightSet => Ok(()),
+            _ => Err(OutputManagerError::UnexpectedApiResponse),
+        }
+    }
+
     pub async fn prepare_transaction_to_send(
         &mut self,
         amount: MicroTari,
@@ -57,6 +57,7 @@ where T: OutputManagerBackend
 {
     key_manager: Mutex<KeyManager<PrivateKey, KeyDigest>>,
     db: OutputManagerDatabase<T>,
+    chain_height: u64,
     request_stream:
         Option<reply_channel::Receiver<OutputManagerRequest, Result<OutputManagerResponse, OutputManagerError>>>,
     factories: CryptoFactories,
@@ -96,6 +97,7 @@ where T: OutputManagerBackend
                 key_manager_state.primary_key_index,
             )),
             db,
+            chain_height: 0,
             request_stream: Some(request_stream),
             factories,
         })
@@ -141,6 +143,9 @@ where T: OutputManagerBackend
         match request {
             OutputManagerRequest::AddOutput(uo) => self.add_output(uo).map(|_| OutputManagerResponse::OutputAdded),
             OutputManagerRequest::GetBalance => self.get_balance().map(|a| OutputManagerResponse::Balance(a)),
+            OutputManagerRequest::GetConfirmedBalance => {
+                self.get_confirmed_balance().map(|a| OutputManagerResponse::Balance(a))
+            },
             OutputManagerRequest::GetRecipientKey((tx_id, amount)) => self
                 .get_recipient_spending_key(tx_id, amount)
                 .map(|k| OutputManagerResponse::RecipientKeyGenerated(k)),
@@ -172,9 +177,19 @@ where T: OutputManagerBackend
             OutputManagerRequest::GetCoinbaseKey((tx_id, amount, maturity_height)) => self
                 .get_coinbase_spending_key(tx_id, amount, maturity_height)
                 .map(|k| OutputManagerResponse::RecipientKeyGenerated(k)),
+            OutputManagerRequest::SetChainHeight(height) => {
+                self.set_chain_height(height);
+                Ok(OutputManagerResponse::ChainHeightSet)
+            },
         }
     }
 
+    /// Set the current height of the base chain. Outputs that have not matured at this height are not selected to be
+    /// spent. Until the height is set, only outputs without a maturity height can be spent.
+    pub fn set_chain_height(&mut self, height: u64) {
+        self.chain_height = height;
+    }
+
     /// Add an unblinded output to the unspent outputs list
     pub fn add_output(&mut self, output: UnblindedOutput) -> Result<(), OutputManagerError> {
         Ok(self.db.add_unspent_output(output)?)
@@ -184,6 +199,11 @@ where T: OutputManagerBackend
         Ok(self.db.get_balance()?)
     }
 
+    /// Get the balance excluding funds that are pending incoming and have not yet been confirmed
+    pub fn get_confirmed_balance(&self) -> Result<Balance, OutputManagerError> {
+        Ok(self.db.get_confirmed_balance()?)
+    }
+
     /// Request a spending key to be used to accept a transaction from a sender.
     pub fn get_recipient_spending_key(
         &mut self,
@@ -264,7 +284,7 @@ where T: OutputManagerBackend
     ) -> Result<SenderTransactionProtocol, OutputManagerError>
     {
         let mut rng = TransactionRng::new().unwrap();
-        let outputs = self.select_outputs(amount, fee_per_gram, UTXOSelectionStrategy::Smallest)?;
+        let outputs = self.select_outputs(amount, fee_per_gram, self.chain_height, UTXOSelectionStrategy::Smallest)?;
         let total = outputs.iter().fold(MicroTari::from(0), |acc, x| acc + x.value);
 
         let offset = PrivateKey::random(&mut rng);
@@ -369,11 +389,15 @@ where T: OutputManagerBackend
     }
 
     /// Select which outputs to use to send a transaction of the specified amount. Use the specified selection strategy
-    /// to choose the outputs
+    /// to choose the outputs. Only outputs that have matured at `current_height` are selected. Every selected input
+    /// adds to the transaction fee, so outputs are added until their sum covers the amount plus the fee of a
+    /// transaction with the selected inputs (with or without a change output). Returns `InsufficientFunds` if the
+    /// spendable outputs cannot cover the amount and the resulting fee.
     fn select_outputs(
         &mut self,
         amount: MicroTari,
         fee_per_gram: MicroTari,
+        current_height: u64,
         strategy: UTXOSelectionStrategy,
     ) -> Result<Vec<UnblindedOutput>, OutputManagerError>
     {
@@ -382,7 +406,7 @@ where T: OutputManagerBackend
         let mut fee_without_change = MicroTari::from(0);
         let mut fee_with_change = MicroTari::from(0);
 
-        let uo = self.db.fetch_sorted_unspent_outputs()?;
+        let uo = self.db.fetch_spendable_outputs(current_height)?;
 
         match strategy {
             UTXOSelectionStrategy::Smallest => {
@@ -401,7 +425,17 @@ where T: OutputManagerBackend
         }
 
         if (total != amount + fee_without_change) && (total < amount + fee_with_change) {
-            return Err(OutputManagerError::NotEnoughFunds);
+            // If the outputs cover more than the transaction without change needs, the excess has to go to a change
+            // output, so the change output's fee is required too
+            let fee = if total > amount + fee_without_change {
+                fee_with_change
+            } else {
+                Fee::calculate(fee_per_gram, outputs.len(), 1)
+            };
+            return Err(OutputManagerError::InsufficientFunds {
+                required: amount + fee,
+                available: total,
+            });
         }
 
         Ok(outputs)
@@ -447,4 +481,9 @@ pub struct Balance {
     pub pending_incoming_balance: MicroTari,
     /// The current balance of funds encumbered in pending outbound transactions that have not been confirmed
     pub pending_outgoing_balance: MicroTari,
+    /// The current balance of unspent outputs that have been frozen and are excluded from the available balance
+    pub frozen_balance: MicroTari,
+    /// The current balance of unspent outputs that have not yet reached their maturity height and are excluded from
+    /// the available balance. This is only reported by balances calculated at a given chain height.
+    pub time_locked_balance: MicroTari,
 }
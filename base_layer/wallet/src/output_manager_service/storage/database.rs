@@ -21,17 +21,20 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::output_manager_service::{error::OutputManagerStorageError, service::Balance, TxId};
-use chrono::{NaiveDateTime, Utc};
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
 use log::*;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::{Display, Error, Formatter},
     time::Duration,
 };
+use tari_crypto::commitment::HomomorphicCommitmentFactory;
 use tari_transactions::{
+    fee::{Fee, WEIGHT_PER_INPUT},
     tari_amount::MicroTari,
     transaction::{OutputFeatures, UnblindedOutput},
-    types::{BlindingFactor, PrivateKey},
+    types::{BlindingFactor, Commitment, CommitmentFactory, PrivateKey},
 };
 
 const LOG_TARGET: &'static str = "wallet::output_manager_service::database";
@@ -45,10 +48,17 @@ pub trait OutputManagerBackend: Send + Sync {
     fn fetch(&self, key: &DbKey) -> Result<Option<DbValue>, OutputManagerStorageError>;
     /// Modify the state the of the backend with a write operation
     fn write(&mut self, op: WriteOperation) -> Result<Option<DbValue>, OutputManagerStorageError>;
+    /// Modify the state of the backend with a sequence of write operations, applied in order. The operations must be
+    /// applied atomically: if any of them fails, none of them may be applied.
+    fn write_many(&mut self, ops: Vec<WriteOperation>) -> Result<(), OutputManagerStorageError>;
     /// This method is called when a pending transaction is to be confirmed. It must move the `outputs_to_be_spent` and
     /// `outputs_to_be_received` from a `PendingTransactionOutputs` record into the `unspent_outputs` and
     /// `spent_outputs` collections.
     fn confirm_transaction(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError>;
+    /// This method is called when only the received outputs of a pending transaction are confirmed. It moves the
+    /// `outputs_to_be_received` from a `PendingTransactionOutputs` record into the `unspent_outputs` collection and
+    /// leaves the `outputs_to_be_spent` encumbered in the record.
+    fn confirm_received_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError>;
     /// This method encumbers the specified outputs into a `PendingTransactionOutputs` record. This reserves these
     /// outputs until the transaction is confirmed or cancelled
     fn encumber_outputs(
@@ -63,13 +73,32 @@ pub trait OutputManagerBackend: Send + Sync {
     /// This method must run through all the `PendingTransactionOutputs` and test if any have existed for longer that
     /// the specified duration. If they have they should be cancelled.
     fn timeout_pending_transactions(&mut self, period: Duration) -> Result<(), OutputManagerStorageError>;
+    /// This method sets the frozen flag of the unspent output with the given spending key. Frozen outputs are
+    /// excluded from the `UnspentOutputs` collection so they cannot be selected to be spent, and are instead returned
+    /// by the `FrozenOutputs` key.
+    fn set_output_frozen(
+        &mut self,
+        spending_key: &BlindingFactor,
+        frozen: bool,
+    ) -> Result<(), OutputManagerStorageError>;
+    /// This method moves the unspent output with the given spending key into the invalidated outputs, e.g. when it
+    /// turns out to be a double spend or was removed by a reorg. Invalidated outputs are excluded from the
+    /// `UnspentOutputs` collection so they are never selected to be spent, but the record is kept and returned by the
+    /// `InvalidatedOutputs` key.
+    fn invalidate_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError>;
     /// This method will increment the currently stored key index for the key manager config. Increment this after eac
     /// key is generated
     fn increment_key_index(&mut self) -> Result<(), OutputManagerStorageError>;
+    /// This method will increment the key index of the named key manager branch. A branch that has not been used
+    /// before starts at an index of zero. The key manager state must be initialized.
+    fn increment_key_index_for_branch(&mut self, branch: &str) -> Result<(), OutputManagerStorageError>;
+    /// This method will decrement the currently stored key index for the key manager config, so that the key generated
+    /// last is reused. A `KeyIndexUnderflow` error is returned if the index is zero.
+    fn decrement_key_index(&mut self) -> Result<(), OutputManagerStorageError>;
 }
 
 /// Holds the outputs that have been selected for a given pending transaction waiting for confirmation
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PendingTransactionOutputs {
     pub tx_id: u64,
     pub outputs_to_be_spent: Vec<UnblindedOutput>,
@@ -77,8 +106,32 @@ pub struct PendingTransactionOutputs {
     pub timestamp: NaiveDateTime,
 }
 
+/// A plan for splitting unspent outputs into a number of roughly equal outputs. The output amounts and fee add up to
+/// the total value of the inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitPlan {
+    pub inputs: Vec<UnblindedOutput>,
+    pub output_amounts: Vec<MicroTari>,
+    pub fee: MicroTari,
+}
+
+/// A self-contained copy of the outputs and key manager state of an Output Manager database, used to migrate a
+/// wallet. It is created by `export_outputs` and restored into an empty database by `import_outputs`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputBackup {
+    pub unspent_outputs: Vec<UnblindedOutput>,
+    pub spent_outputs: Vec<UnblindedOutput>,
+    pub frozen_outputs: Vec<UnblindedOutput>,
+    pub invalidated_outputs: Vec<UnblindedOutput>,
+    pub pending_transactions: Vec<PendingTransactionOutputs>,
+    pub key_manager_state: Option<KeyManagerState>,
+    /// The key indices of the key manager branches other than the default branch, e.g. the change branch
+    #[serde(default)]
+    pub branch_key_indices: HashMap<String, usize>,
+}
+
 /// Holds the state of the KeyManager being used by the Output Manager Service
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct KeyManagerState {
     pub master_seed: PrivateKey,
     pub branch_seed: String,
@@ -92,8 +145,12 @@ pub enum DbKey {
     PendingTransactionOutputs(TxId),
     UnspentOutputs,
     SpentOutputs,
+    FrozenOutputs,
+    InvalidatedOutputs,
     AllPendingTransactionOutputs,
     KeyManagerState,
+    BranchKeyIndex(String),
+    BranchKeyIndices,
 }
 
 #[derive(Debug)]
@@ -103,19 +160,28 @@ pub enum DbValue {
     PendingTransactionOutputs(Box<PendingTransactionOutputs>),
     UnspentOutputs(Vec<UnblindedOutput>),
     SpentOutputs(Vec<UnblindedOutput>),
+    FrozenOutputs(Vec<UnblindedOutput>),
+    InvalidatedOutputs(Vec<UnblindedOutput>),
     AllPendingTransactionOutputs(HashMap<TxId, PendingTransactionOutputs>),
     KeyManagerState(KeyManagerState),
+    BranchKeyIndex(usize),
+    BranchKeyIndices(HashMap<String, usize>),
 }
 
 pub enum DbKeyValuePair {
     SpentOutput(BlindingFactor, Box<UnblindedOutput>),
     UnspentOutput(BlindingFactor, Box<UnblindedOutput>),
+    FrozenOutput(BlindingFactor, Box<UnblindedOutput>),
+    InvalidatedOutput(BlindingFactor, Box<UnblindedOutput>),
     PendingTransactionOutputs(TxId, Box<PendingTransactionOutputs>),
     KeyManagerState(KeyManagerState),
+    BranchKeyIndex(String, usize),
 }
 
 pub enum WriteOperation {
     Insert(DbKeyValuePair),
+    /// Insert all of the values in a single operation. None of the values are inserted if any of them cannot be.
+    InsertBatch(Vec<DbKeyValuePair>),
     Remove(DbKey),
 }
 
@@ -138,13 +204,82 @@ pub struct OutputManagerDatabase<T>
 where T: OutputManagerBackend
 {
     db: T,
+    batch: Option<Vec<WriteOperation>>,
 }
 
 impl<T> OutputManagerDatabase<T>
 where T: OutputManagerBackend
 {
     pub fn new(db: T) -> Self {
-        Self { db }
+        Self { db, batch: None }
+    }
+
+    /// Starts buffering write operations instead of writing them to the backend one at a time. The buffered operations
+    /// are flushed to the backend with a single, atomic `write_many` call by `commit_batch`. Buffered operations are
+    /// not visible to reads until the batch is committed. Operations that cannot be expressed as write operations (e.g.
+    /// confirming, cancelling and timing out pending transactions, freezing outputs and updating key indices) would be
+    /// applied out of order, so they return a `BatchInProgress` error until the batch is committed. Calling this
+    /// while a batch is in progress has no effect.
+    pub fn begin_batch(&mut self) {
+        if self.batch.is_none() {
+            self.batch = Some(Vec::new());
+        }
+    }
+
+    /// Returns true if write operations are currently being buffered
+    pub fn is_batching(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// Flushes all buffered write operations to the backend and stops buffering. This does nothing if no batch is in
+    /// progress.
+    pub fn commit_batch(&mut self) -> Result<(), OutputManagerStorageError> {
+        match self.batch.take() {
+            Some(ops) if !ops.is_empty() => self.db.write_many(ops),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a `BatchInProgress` error if write operations are being buffered. This guards the operations that are
+    /// applied to the backend directly, which would otherwise overtake the buffered writes.
+    fn ensure_not_batching(&self) -> Result<(), OutputManagerStorageError> {
+        if self.is_batching() {
+            return Err(OutputManagerStorageError::BatchInProgress);
+        }
+        Ok(())
+    }
+
+    /// Returns the unspent outputs as they will be once the buffered write operations are committed, sorted by value.
+    /// This is the same as `fetch_sorted_unspent_outputs` if no batch is in progress.
+    fn fetch_staged_unspent_outputs(&self) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError> {
+        let mut unspent_outputs = self.fetch_sorted_unspent_outputs()?;
+        for op in self.batch.iter().flatten() {
+            match op {
+                WriteOperation::Insert(DbKeyValuePair::UnspentOutput(_, o)) => unspent_outputs.push((**o).clone()),
+                WriteOperation::InsertBatch(kvps) => {
+                    for kvp in kvps {
+                        if let DbKeyValuePair::UnspentOutput(_, o) = kvp {
+                            unspent_outputs.push((**o).clone());
+                        }
+                    }
+                },
+                WriteOperation::Remove(DbKey::UnspentOutput(k)) => unspent_outputs.retain(|o| &o.spending_key != k),
+                _ => {},
+            }
+        }
+        unspent_outputs.sort();
+        Ok(unspent_outputs)
+    }
+
+    /// Buffers the write operation if a batch is in progress, otherwise writes it to the backend
+    fn write(&mut self, op: WriteOperation) -> Result<(), OutputManagerStorageError> {
+        match self.batch.as_mut() {
+            Some(batch) => batch.push(op),
+            None => {
+                self.db.write(op)?;
+            },
+        }
+        Ok(())
     }
 
     pub fn get_key_manager_state(&self) -> Result<Option<KeyManagerState>, OutputManagerStorageError> {
@@ -157,19 +292,68 @@ where T: OutputManagerBackend
     }
 
     pub fn set_key_manager_state(&mut self, state: KeyManagerState) -> Result<(), OutputManagerStorageError> {
-        self.db
-            .write(WriteOperation::Insert(DbKeyValuePair::KeyManagerState(state)))?;
+        self.write(WriteOperation::Insert(DbKeyValuePair::KeyManagerState(state)))?;
 
         Ok(())
     }
 
     pub fn increment_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
         self.db.increment_key_index()?;
         Ok(())
     }
 
+    /// Increments the key index of the named key manager branch, e.g. a branch used for change outputs. The branch of
+    /// the key manager state (its `branch_seed`) is the default branch, whose index is the `primary_key_index`
+    /// updated by `increment_key_index`; all other branches are indexed independently.
+    pub fn increment_key_index_for_branch(&mut self, branch: &str) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        let state = self
+            .get_key_manager_state()?
+            .ok_or(OutputManagerStorageError::KeyManagerNotInitialized)?;
+        if state.branch_seed == branch {
+            return self.db.increment_key_index();
+        }
+        self.db.increment_key_index_for_branch(branch)
+    }
+
+    /// Returns the key index of the named key manager branch. A branch that has not been used has an index of zero.
+    /// The index of the default branch (the `branch_seed` of the key manager state) is its `primary_key_index`.
+    pub fn get_key_index_for_branch(&self, branch: &str) -> Result<usize, OutputManagerStorageError> {
+        let state = self
+            .get_key_manager_state()?
+            .ok_or(OutputManagerStorageError::KeyManagerNotInitialized)?;
+        if state.branch_seed == branch {
+            return Ok(state.primary_key_index);
+        }
+        let key = DbKey::BranchKeyIndex(branch.to_string());
+        match self.db.fetch(&key) {
+            Ok(None) => Ok(0),
+            Ok(Some(DbValue::BranchKeyIndex(index))) => Ok(index),
+            Ok(Some(other)) => unexpected_result(key, other),
+            Err(e) => log_error(key, e),
+        }
+    }
+
+    /// Returns the key indices of all the key manager branches that have been used, other than the default branch
+    pub fn fetch_branch_key_indices(&self) -> Result<HashMap<String, usize>, OutputManagerStorageError> {
+        match self.db.fetch(&DbKey::BranchKeyIndices) {
+            Ok(None) => Ok(HashMap::new()),
+            Ok(Some(DbValue::BranchKeyIndices(indices))) => Ok(indices),
+            Ok(Some(other)) => unexpected_result(DbKey::BranchKeyIndices, other),
+            Err(e) => log_error(DbKey::BranchKeyIndices, e),
+        }
+    }
+
+    /// Rewinds the key manager index by one to reclaim a key that was generated but not used, e.g. by a cancelled
+    /// transaction
+    pub fn decrement_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        self.db.decrement_key_index()
+    }
+
     pub fn add_unspent_output(&mut self, output: UnblindedOutput) -> Result<(), OutputManagerStorageError> {
-        self.db.write(WriteOperation::Insert(DbKeyValuePair::UnspentOutput(
+        self.write(WriteOperation::Insert(DbKeyValuePair::UnspentOutput(
             output.spending_key.clone(),
             Box::new(output),
         )))?;
@@ -177,6 +361,55 @@ where T: OutputManagerBackend
         Ok(())
     }
 
+    /// Adds all of the outputs to the unspent outputs in a single write operation, e.g. when restoring a wallet. A
+    /// `DuplicateOutput` error is returned and none of the outputs are added if two outputs share a spending key or
+    /// any of them is already stored.
+    pub fn add_unspent_outputs(&mut self, outputs: Vec<UnblindedOutput>) -> Result<(), OutputManagerStorageError> {
+        for (i, output) in outputs.iter().enumerate() {
+            if outputs[..i].iter().any(|o| o.spending_key == output.spending_key) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+        }
+        self.write(WriteOperation::InsertBatch(
+            outputs
+                .into_iter()
+                .map(|o| DbKeyValuePair::UnspentOutput(o.spending_key.clone(), Box::new(o)))
+                .collect(),
+        ))
+    }
+
+    /// Freezes the unspent output with the given spending key. A frozen output is not selected to be spent, but its
+    /// value is still reported in the `frozen_balance`.
+    pub fn freeze_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        self.db.set_output_frozen(spending_key, true)
+    }
+
+    /// Unfreezes the frozen output with the given spending key so that it can be selected to be spent again
+    pub fn unfreeze_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        self.db.set_output_frozen(spending_key, false)
+    }
+
+    /// Invalidates the unspent output with the given spending key, e.g. because it was double spent or reorged out.
+    /// The output is kept as a historical record but is excluded from the balance and never selected to be spent.
+    pub fn invalidate_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        self.db.invalidate_output(spending_key)
+    }
+
+    /// Compute a conservative balance that ignores funds that are due to be received but have not yet been confirmed.
+    /// The `pending_incoming_balance` of the returned balance is always zero. Outputs encumbered by pending outbound
+    /// transactions are already excluded from the `available_balance`, so the available balance is the amount that can
+    /// be relied upon and `pending_outgoing_balance` is still reported.
+    pub fn get_confirmed_balance(&self) -> Result<Balance, OutputManagerStorageError> {
+        let balance = self.get_balance()?;
+        Ok(Balance {
+            pending_incoming_balance: MicroTari::from(0),
+            ..balance
+        })
+    }
+
     pub fn get_balance(&self) -> Result<Balance, OutputManagerStorageError> {
         let pending_txs =
             self.db
@@ -195,6 +428,10 @@ where T: OutputManagerBackend
         if let DbValue::UnspentOutputs(uo) = unspent_outputs {
             if let DbValue::AllPendingTransactionOutputs(pto) = pending_txs {
                 let available_balance = uo.iter().fold(MicroTari::from(0), |acc, x| acc + x.value);
+                let frozen_balance = self
+                    .fetch_frozen_outputs()?
+                    .iter()
+                    .fold(MicroTari::from(0), |acc, x| acc + x.value);
                 let mut pending_incoming = MicroTari::from(0);
                 let mut pending_outgoing = MicroTari::from(0);
 
@@ -213,6 +450,8 @@ where T: OutputManagerBackend
                     available_balance,
                     pending_incoming_balance: pending_incoming,
                     pending_outgoing_balance: pending_outgoing,
+                    frozen_balance,
+                    time_locked_balance: MicroTari::from(0),
                 });
             }
         }
@@ -222,16 +461,61 @@ where T: OutputManagerBackend
         ))
     }
 
+    /// Compute the balance at the given chain height. Unspent outputs that have not yet matured, i.e. with a
+    /// `features.maturity` greater than `current_height`, are excluded from the `available_balance` and reported in the
+    /// `time_locked_balance` instead.
+    pub fn get_balance_at_height(&self, current_height: u64) -> Result<Balance, OutputManagerStorageError> {
+        let balance = self.get_balance()?;
+        let time_locked_balance = self
+            .fetch_sorted_unspent_outputs()?
+            .iter()
+            .filter(|o| o.features.maturity > current_height)
+            .fold(MicroTari::from(0), |acc, x| acc + x.value);
+        Ok(Balance {
+            available_balance: balance.available_balance - time_locked_balance,
+            time_locked_balance,
+            ..balance
+        })
+    }
+
+    /// Checks that the balance reported by `get_balance` is consistent and returns it. Outputs encumbered by pending
+    /// transactions are removed from the available balance, so the pending outgoing balance can only exceed the
+    /// funds held by the wallet if an encumbered output is counted more than once, i.e. it is also unspent or frozen,
+    /// or it is encumbered by more than one pending transaction. An `InconsistentBalance` error is returned if that
+    /// is the case.
+    pub fn validate_balance(&self) -> Result<Balance, OutputManagerStorageError> {
+        let mut held_outputs = self.fetch_sorted_unspent_outputs()?;
+        held_outputs.extend(self.fetch_frozen_outputs()?);
+        let mut encumbered_keys = Vec::new();
+        for (tx_id, p) in self.fetch_all_pending_transaction_outputs()? {
+            for o in p.outputs_to_be_spent {
+                if held_outputs.iter().any(|h| h.spending_key == o.spending_key) {
+                    return Err(OutputManagerStorageError::InconsistentBalance(format!(
+                        "An output of {} encumbered by pending transaction {} is also counted as unspent",
+                        o.value, tx_id
+                    )));
+                }
+                if encumbered_keys.contains(&o.spending_key) {
+                    return Err(OutputManagerStorageError::InconsistentBalance(format!(
+                        "An output of {} encumbered by pending transaction {} is encumbered more than once",
+                        o.value, tx_id
+                    )));
+                }
+                encumbered_keys.push(o.spending_key);
+            }
+        }
+        self.get_balance()
+    }
+
     pub fn add_pending_transaction_outputs(
         &mut self,
         pending_transaction_outputs: PendingTransactionOutputs,
     ) -> Result<(), OutputManagerStorageError>
     {
-        self.db
-            .write(WriteOperation::Insert(DbKeyValuePair::PendingTransactionOutputs(
-                pending_transaction_outputs.tx_id.clone(),
-                Box::new(pending_transaction_outputs),
-            )))?;
+        self.write(WriteOperation::Insert(DbKeyValuePair::PendingTransactionOutputs(
+            pending_transaction_outputs.tx_id.clone(),
+            Box::new(pending_transaction_outputs),
+        )))?;
 
         Ok(())
     }
@@ -244,13 +528,36 @@ where T: OutputManagerBackend
         fetch!(self, tx_id, PendingTransactionOutputs)
     }
 
+    /// Returns the total value of the outputs encumbered by the pending transaction with the given `tx_id`, i.e. the
+    /// amount that is made available again if the transaction is cancelled.
+    pub fn encumbered_amount(&self, tx_id: TxId) -> Result<MicroTari, OutputManagerStorageError> {
+        let key = DbKey::PendingTransactionOutputs(tx_id);
+        match self.db.fetch(&key)? {
+            Some(DbValue::PendingTransactionOutputs(pending_tx)) => Ok(pending_tx
+                .outputs_to_be_spent
+                .iter()
+                .fold(MicroTari::from(0), |acc, x| acc + x.value)),
+            Some(other) => unexpected_result(key, other),
+            None => Err(OutputManagerStorageError::PendingTransactionNotFound),
+        }
+    }
+
     /// This method is called when a pending transaction is confirmed. It moves the `outputs_to_be_spent` and
     /// `outputs_to_be_received` from a `PendingTransactionOutputs` record into the `unspent_outputs` and
     /// `spent_outputs` collections.
     pub fn confirm_pending_transaction_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
         self.db.confirm_transaction(tx_id)
     }
 
+    /// This method is called when the outputs received by a pending transaction are confirmed while its spend is not,
+    /// e.g. after a reorg. The `outputs_to_be_received` are moved into the `unspent_outputs` collection and the
+    /// `outputs_to_be_spent` remain encumbered by the pending transaction.
+    pub fn confirm_received_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
+        self.db.confirm_received_outputs(tx_id)
+    }
+
     /// This method accepts and stores a pending inbound transaction and creates the `output_to_be_received` from the
     /// amount and provided spending key.
     pub fn accept_incoming_pending_transaction(
@@ -261,20 +568,19 @@ where T: OutputManagerBackend
         output_features: OutputFeatures,
     ) -> Result<(), OutputManagerStorageError>
     {
-        self.db
-            .write(WriteOperation::Insert(DbKeyValuePair::PendingTransactionOutputs(
-                tx_id.clone(),
-                Box::new(PendingTransactionOutputs {
-                    tx_id: tx_id.clone(),
-                    outputs_to_be_spent: Vec::new(),
-                    outputs_to_be_received: vec![UnblindedOutput {
-                        value: amount.clone(),
-                        spending_key: spending_key.clone(),
-                        features: output_features,
-                    }],
-                    timestamp: Utc::now().naive_utc(),
-                }),
-            )))?;
+        self.write(WriteOperation::Insert(DbKeyValuePair::PendingTransactionOutputs(
+            tx_id.clone(),
+            Box::new(PendingTransactionOutputs {
+                tx_id: tx_id.clone(),
+                outputs_to_be_spent: Vec::new(),
+                outputs_to_be_received: vec![UnblindedOutput {
+                    value: amount.clone(),
+                    spending_key: spending_key.clone(),
+                    features: output_features,
+                }],
+                timestamp: Utc::now().naive_utc(),
+            }),
+        )))?;
         Ok(())
     }
 
@@ -287,18 +593,77 @@ where T: OutputManagerBackend
         change_output: Option<UnblindedOutput>,
     ) -> Result<(), OutputManagerStorageError>
     {
-        self.db.encumber_outputs(tx_id, outputs_to_send, change_output)
+        if !self.is_batching() {
+            return self.db.encumber_outputs(tx_id, outputs_to_send, change_output);
+        }
+
+        // Within a batch, the outputs are encumbered by removing them from the unspent outputs and inserting them into
+        // a pending transaction record. The outputs must still be unspent once the writes staged so far are applied.
+        let unspent_outputs = self.fetch_staged_unspent_outputs()?;
+        if !outputs_to_send
+            .iter()
+            .all(|o| unspent_outputs.iter().any(|uo| uo.spending_key == o.spending_key))
+        {
+            return Err(OutputManagerStorageError::ValuesNotFound);
+        }
+        for o in outputs_to_send {
+            self.write(WriteOperation::Remove(DbKey::UnspentOutput(o.spending_key.clone())))?;
+        }
+        self.write(WriteOperation::Insert(DbKeyValuePair::PendingTransactionOutputs(
+            tx_id,
+            Box::new(PendingTransactionOutputs {
+                tx_id,
+                outputs_to_be_spent: outputs_to_send.clone(),
+                outputs_to_be_received: change_output.into_iter().collect(),
+                timestamp: Utc::now().naive_utc(),
+            }),
+        )))
+    }
+
+    /// Encumbers exactly the unspent outputs with the provided spending keys against a pending transaction. No outputs
+    /// are encumbered if any of the outputs is already encumbered or cannot be found.
+    pub fn encumber_specific_outputs(
+        &mut self,
+        tx_id: TxId,
+        spending_keys: &[BlindingFactor],
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        let pending_txs = self.fetch_all_pending_transaction_outputs()?;
+        let is_encumbered = |key: &BlindingFactor| {
+            pending_txs
+                .values()
+                .any(|p| p.outputs_to_be_spent.iter().any(|o| &o.spending_key == key))
+        };
+        if spending_keys.iter().any(is_encumbered) {
+            return Err(OutputManagerStorageError::OutputAlreadyEncumbered);
+        }
+
+        let unspent_outputs = self.fetch_sorted_unspent_outputs()?;
+        let outputs_to_send = spending_keys
+            .iter()
+            .map(|key| {
+                unspent_outputs
+                    .iter()
+                    .find(|o| &o.spending_key == key)
+                    .cloned()
+                    .ok_or(OutputManagerStorageError::ValuesNotFound)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.encumber_outputs(tx_id, &outputs_to_send, None)
     }
 
     /// When a pending transaction is cancelled the encumbered outputs are moved back to the `unspent_outputs`
     /// collection.
     pub fn cancel_pending_transaction_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
         self.db.cancel_pending_transaction(tx_id)
     }
 
     /// This method is check all pending transactions to see if any are older that the provided duration. If they are
     /// they will be cancelled.
     pub fn timeout_pending_transaction_outputs(&mut self, period: Duration) -> Result<(), OutputManagerStorageError> {
+        self.ensure_not_batching()?;
         self.db.timeout_pending_transactions(period)
     }
 
@@ -317,6 +682,159 @@ where T: OutputManagerBackend
         Ok(uo)
     }
 
+    /// Returns the unspent outputs that can be spent at the given chain height, sorted by value. Outputs with a
+    /// `features.maturity` greater than `current_height` are time locked and excluded.
+    pub fn fetch_spendable_outputs(
+        &self,
+        current_height: u64,
+    ) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError>
+    {
+        Ok(self
+            .fetch_sorted_unspent_outputs()?
+            .into_iter()
+            .filter(|o| o.features.maturity <= current_height)
+            .collect())
+    }
+
+    /// Returns the unspent outputs with a value strictly greater than `dust_threshold`, sorted by value. Coin selection
+    /// can use this to avoid bloating transactions with outputs of negligible value.
+    pub fn fetch_spendable_outputs_above(
+        &self,
+        dust_threshold: MicroTari,
+    ) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError>
+    {
+        Ok(self
+            .fetch_sorted_unspent_outputs()?
+            .into_iter()
+            .filter(|o| o.value > dust_threshold)
+            .collect())
+    }
+
+    /// Returns the unspent outputs with a value in the inclusive range `[min, max]`, sorted by value
+    pub fn fetch_unspent_outputs_in_range(
+        &self,
+        min: MicroTari,
+        max: MicroTari,
+    ) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError>
+    {
+        Ok(self
+            .fetch_sorted_unspent_outputs()?
+            .into_iter()
+            .filter(|o| o.value >= min && o.value <= max)
+            .collect())
+    }
+
+    /// Returns the spendable output with the largest value at the given chain height and fee per gram, or None if
+    /// there are no spendable outputs. Frozen, encumbered, immature and dust outputs are not spendable, as for
+    /// `max_sendable`.
+    pub fn largest_spendable_output(
+        &self,
+        current_height: u64,
+        fee_per_gram: MicroTari,
+    ) -> Result<Option<UnblindedOutput>, OutputManagerStorageError>
+    {
+        Ok(self
+            .fetch_spendable_outputs_without_dust(current_height, fee_per_gram)?
+            .pop())
+    }
+
+    /// Counts the unspent outputs by value. Each bucket boundary is the inclusive lower bound of a bucket, which
+    /// extends up to the next boundary. The buckets are returned as `(lower bound, output count)` pairs sorted by
+    /// lower bound, with duplicate boundaries removed. Outputs with a value below the smallest boundary are not
+    /// counted.
+    pub fn utxo_value_histogram(
+        &self,
+        bucket_boundaries: &[MicroTari],
+    ) -> Result<Vec<(MicroTari, usize)>, OutputManagerStorageError>
+    {
+        let mut histogram = bucket_boundaries.iter().map(|b| (*b, 0)).collect::<Vec<_>>();
+        histogram.sort();
+        histogram.dedup();
+        for output in self.fetch_sorted_unspent_outputs()? {
+            if let Some(bucket) = histogram.iter_mut().rev().find(|(b, _)| *b <= output.value) {
+                bucket.1 += 1;
+            }
+        }
+        Ok(histogram)
+    }
+
+    /// Returns the unspent outputs that are worth spending at the given chain height and fee per gram, sorted by value.
+    /// Frozen, encumbered and immature outputs are not spendable, and neither are dust outputs, whose value does not
+    /// cover the fee for including them as an input.
+    fn fetch_spendable_outputs_without_dust(
+        &self,
+        current_height: u64,
+        fee_per_gram: MicroTari,
+    ) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError>
+    {
+        let input_fee = u64::from(fee_per_gram) * WEIGHT_PER_INPUT;
+        Ok(self
+            .fetch_spendable_outputs(current_height)?
+            .into_iter()
+            .filter(|o| u64::from(o.value) > input_fee)
+            .collect())
+    }
+
+    /// Computes the largest amount that can be sent at the given chain height in a single transaction that consumes
+    /// every spendable output and has one output, after reserving the fee for that transaction at the given fee per
+    /// gram. Frozen, encumbered and immature outputs are not spendable, and neither are dust outputs, whose value does
+    /// not cover the fee for including them as an input. Zero is returned if the spendable outputs do not cover the
+    /// fee.
+    pub fn max_sendable(
+        &self,
+        fee_per_gram: MicroTari,
+        current_height: u64,
+    ) -> Result<MicroTari, OutputManagerStorageError>
+    {
+        let spendable = self.fetch_spendable_outputs_without_dust(current_height, fee_per_gram)?;
+        if spendable.is_empty() {
+            return Ok(MicroTari::from(0));
+        }
+        let total = spendable.iter().fold(MicroTari::from(0), |acc, o| acc + o.value);
+        let fee = Fee::calculate(fee_per_gram, spendable.len(), 1);
+        Ok(total.checked_sub(fee).unwrap_or_else(|| MicroTari::from(0)))
+    }
+
+    /// Plans how to split the current unspent outputs into `target_count` roughly equal outputs at the given fee per
+    /// gram. All unspent outputs are consumed and the value remaining after the fee is shared between the outputs,
+    /// with any remainder going to the first outputs. The database is not modified.
+    pub fn plan_split(
+        &self,
+        target_count: usize,
+        fee_per_gram: MicroTari,
+    ) -> Result<SplitPlan, OutputManagerStorageError>
+    {
+        if target_count == 0 {
+            return Err(OutputManagerStorageError::InvalidSplit(
+                "At least one output is required".to_string(),
+            ));
+        }
+        let inputs = self.fetch_sorted_unspent_outputs()?;
+        let total = inputs.iter().fold(0u64, |acc, o| acc + u64::from(o.value));
+        let fee = Fee::calculate(fee_per_gram, inputs.len(), target_count);
+        let remaining = total.saturating_sub(u64::from(fee));
+        if inputs.is_empty() || remaining < target_count as u64 {
+            return Err(OutputManagerStorageError::InvalidSplit(format!(
+                "Unspent outputs totalling {} cannot be split into {} outputs with a fee of {}",
+                MicroTari::from(total),
+                target_count,
+                fee
+            )));
+        }
+
+        let amount = remaining / target_count as u64;
+        let remainder = (remaining % target_count as u64) as usize;
+        let output_amounts = (0..target_count)
+            .map(|i| MicroTari::from(if i < remainder { amount + 1 } else { amount }))
+            .collect();
+
+        Ok(SplitPlan {
+            inputs,
+            output_amounts,
+            fee,
+        })
+    }
+
     pub fn fetch_spent_outputs(&self) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError> {
         let uo = match self.db.fetch(&DbKey::SpentOutputs) {
             Ok(None) => log_error(
@@ -330,6 +848,30 @@ where T: OutputManagerBackend
         Ok(uo)
     }
 
+    pub fn fetch_frozen_outputs(&self) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError> {
+        match self.db.fetch(&DbKey::FrozenOutputs) {
+            Ok(None) => log_error(
+                DbKey::FrozenOutputs,
+                OutputManagerStorageError::UnexpectedResult("Could not retrieve frozen outputs".to_string()),
+            ),
+            Ok(Some(DbValue::FrozenOutputs(fo))) => Ok(fo),
+            Ok(Some(other)) => unexpected_result(DbKey::FrozenOutputs, other),
+            Err(e) => log_error(DbKey::FrozenOutputs, e),
+        }
+    }
+
+    pub fn fetch_invalidated_outputs(&self) -> Result<Vec<UnblindedOutput>, OutputManagerStorageError> {
+        match self.db.fetch(&DbKey::InvalidatedOutputs) {
+            Ok(None) => log_error(
+                DbKey::InvalidatedOutputs,
+                OutputManagerStorageError::UnexpectedResult("Could not retrieve invalidated outputs".to_string()),
+            ),
+            Ok(Some(DbValue::InvalidatedOutputs(io))) => Ok(io),
+            Ok(Some(other)) => unexpected_result(DbKey::InvalidatedOutputs, other),
+            Err(e) => log_error(DbKey::InvalidatedOutputs, e),
+        }
+    }
+
     pub fn fetch_all_pending_transaction_outputs(
         &self,
     ) -> Result<HashMap<u64, PendingTransactionOutputs>, OutputManagerStorageError> {
@@ -346,6 +888,172 @@ where T: OutputManagerBackend
         }?;
         Ok(uo)
     }
+
+    /// Exports all of the outputs and the key manager state to an [OutputBackup] that can be restored with
+    /// `import_outputs`. The pending transactions are sorted by `tx_id`.
+    pub fn export_outputs(&self) -> Result<OutputBackup, OutputManagerStorageError> {
+        let mut pending_transactions = self
+            .fetch_all_pending_transaction_outputs()?
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect::<Vec<_>>();
+        pending_transactions.sort_by_key(|p| p.tx_id);
+        Ok(OutputBackup {
+            unspent_outputs: self.fetch_sorted_unspent_outputs()?,
+            spent_outputs: self.fetch_spent_outputs()?,
+            frozen_outputs: self.fetch_frozen_outputs()?,
+            invalidated_outputs: self.fetch_invalidated_outputs()?,
+            pending_transactions,
+            key_manager_state: self.get_key_manager_state()?,
+            branch_key_indices: self.fetch_branch_key_indices()?,
+        })
+    }
+
+    /// Writes all of the outputs and the key manager state of an [OutputBackup] to this database in a single write
+    /// operation, so nothing is imported if any part of the backup cannot be. A backup can only be imported into an
+    /// empty database; a `StoreNotEmpty` error is returned if any outputs, pending transactions or key manager state
+    /// are already stored.
+    pub fn import_outputs(&mut self, backup: OutputBackup) -> Result<(), OutputManagerStorageError> {
+        if !self.fetch_all_spending_keys()?.is_empty() ||
+            !self.fetch_all_pending_transaction_outputs()?.is_empty() ||
+            self.get_key_manager_state()?.is_some()
+        {
+            return Err(OutputManagerStorageError::StoreNotEmpty);
+        }
+
+        let mut kvps = Vec::new();
+        if let Some(state) = backup.key_manager_state {
+            kvps.push(DbKeyValuePair::KeyManagerState(state));
+        }
+        kvps.extend(
+            backup
+                .branch_key_indices
+                .into_iter()
+                .map(|(branch, index)| DbKeyValuePair::BranchKeyIndex(branch, index)),
+        );
+        kvps.extend(
+            backup
+                .unspent_outputs
+                .into_iter()
+                .map(|o| DbKeyValuePair::UnspentOutput(o.spending_key.clone(), Box::new(o))),
+        );
+        kvps.extend(
+            backup
+                .spent_outputs
+                .into_iter()
+                .map(|o| DbKeyValuePair::SpentOutput(o.spending_key.clone(), Box::new(o))),
+        );
+        kvps.extend(
+            backup
+                .frozen_outputs
+                .into_iter()
+                .map(|o| DbKeyValuePair::FrozenOutput(o.spending_key.clone(), Box::new(o))),
+        );
+        kvps.extend(
+            backup
+                .invalidated_outputs
+                .into_iter()
+                .map(|o| DbKeyValuePair::InvalidatedOutput(o.spending_key.clone(), Box::new(o))),
+        );
+        kvps.extend(
+            backup
+                .pending_transactions
+                .into_iter()
+                .map(|p| DbKeyValuePair::PendingTransactionOutputs(p.tx_id, Box::new(p))),
+        );
+        self.write(WriteOperation::InsertBatch(kvps))
+    }
+
+    /// Returns the spending keys of all the outputs known to the wallet, whether they are unspent, spent, frozen,
+    /// invalidated or part of a pending transaction. Each key is only returned once.
+    pub fn fetch_all_spending_keys(&self) -> Result<Vec<BlindingFactor>, OutputManagerStorageError> {
+        let mut outputs = self.fetch_sorted_unspent_outputs()?;
+        outputs.extend(self.fetch_spent_outputs()?);
+        outputs.extend(self.fetch_frozen_outputs()?);
+        outputs.extend(self.fetch_invalidated_outputs()?);
+        for (_, p) in self.fetch_all_pending_transaction_outputs()? {
+            outputs.extend(p.outputs_to_be_spent);
+            outputs.extend(p.outputs_to_be_received);
+        }
+
+        let mut keys = Vec::with_capacity(outputs.len());
+        for o in outputs {
+            if !keys.contains(&o.spending_key) {
+                keys.push(o.spending_key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Returns every commitment that is shared by more than one stored output, whether the outputs are unspent, spent,
+    /// frozen, invalidated or part of a pending transaction. Distinct outputs should never share a commitment, so any
+    /// collision points to a bug or an attack. The commitments are returned in ascending order.
+    pub fn find_commitment_collisions(&self) -> Result<Vec<Commitment>, OutputManagerStorageError> {
+        let mut outputs = self.fetch_sorted_unspent_outputs()?;
+        outputs.extend(self.fetch_spent_outputs()?);
+        outputs.extend(self.fetch_frozen_outputs()?);
+        outputs.extend(self.fetch_invalidated_outputs()?);
+        for (_, p) in self.fetch_all_pending_transaction_outputs()? {
+            outputs.extend(p.outputs_to_be_spent);
+            outputs.extend(p.outputs_to_be_received);
+        }
+
+        let factory = CommitmentFactory::default();
+        let mut counts = HashMap::new();
+        for o in outputs {
+            let commitment = factory.commit(&o.spending_key, &o.value.into());
+            *counts.entry(commitment).or_insert(0usize) += 1;
+        }
+        let mut collisions = counts
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(commitment, _)| commitment)
+            .collect::<Vec<_>>();
+        collisions.sort();
+        Ok(collisions)
+    }
+
+    /// Returns the tx_id of every pending transaction together with how long it has been pending, oldest first.
+    pub fn fetch_pending_transaction_ages(&self) -> Result<Vec<(TxId, Duration)>, OutputManagerStorageError> {
+        self.fetch_pending_transaction_ages_at(Utc::now().naive_utc())
+    }
+
+    /// Returns the tx_id of every pending transaction together with how long it had been pending at the provided
+    /// time, oldest first. A pending transaction with a timestamp later than `now` has an age of zero.
+    pub fn fetch_pending_transaction_ages_at(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<Vec<(TxId, Duration)>, OutputManagerStorageError>
+    {
+        let pending_txs = self.fetch_all_pending_transaction_outputs()?;
+        let mut ages = pending_txs
+            .values()
+            .map(|p| {
+                let age = (now - p.timestamp).to_std().unwrap_or_else(|_| Duration::from_secs(0));
+                (p.tx_id, age)
+            })
+            .collect::<Vec<_>>();
+        ages.sort_by(|(_, a), (_, b)| b.cmp(a));
+        Ok(ages)
+    }
+
+    /// Returns the pending transactions that have been pending for longer than `period`, oldest first. Unlike
+    /// `timeout_pending_transaction_outputs`, the pending transactions are not cancelled.
+    pub fn fetch_pending_transactions_older_than(
+        &self,
+        period: Duration,
+    ) -> Result<Vec<PendingTransactionOutputs>, OutputManagerStorageError>
+    {
+        let cutoff = Utc::now().naive_utc() - ChronoDuration::from_std(period)?;
+        let mut pending_txs = self
+            .fetch_all_pending_transaction_outputs()?
+            .into_iter()
+            .map(|(_, p)| p)
+            .filter(|p| p.timestamp < cutoff)
+            .collect::<Vec<_>>();
+        pending_txs.sort_by_key(|p| p.timestamp);
+        Ok(pending_txs)
+    }
 }
 
 fn unexpected_result<T>(req: DbKey, res: DbValue) -> Result<T, OutputManagerStorageError> {
@@ -364,8 +1072,12 @@ impl Display for DbKey {
             },
             DbKey::UnspentOutputs => f.write_str(&format!("Unspent Outputs Key")),
             DbKey::SpentOutputs => f.write_str(&format!("Spent Outputs Key")),
+            DbKey::FrozenOutputs => f.write_str(&format!("Frozen Outputs Key")),
+            DbKey::InvalidatedOutputs => f.write_str(&format!("Invalidated Outputs Key")),
             DbKey::AllPendingTransactionOutputs => f.write_str(&format!("All Pending Transaction Outputs")),
             DbKey::KeyManagerState => f.write_str(&format!("Key Manager State")),
+            DbKey::BranchKeyIndex(branch) => f.write_str(&format!("Key Index of Branch: {}", branch)),
+            DbKey::BranchKeyIndices => f.write_str("Key Indices of all Branches"),
         }
     }
 }
@@ -378,8 +1090,12 @@ impl Display for DbValue {
             DbValue::PendingTransactionOutputs(_) => f.write_str("Pending Transaction Outputs"),
             DbValue::UnspentOutputs(_) => f.write_str("Unspent Outputs"),
             DbValue::SpentOutputs(_) => f.write_str("Spent Outputs"),
+            DbValue::FrozenOutputs(_) => f.write_str("Frozen Outputs"),
+            DbValue::InvalidatedOutputs(_) => f.write_str("Invalidated Outputs"),
             DbValue::AllPendingTransactionOutputs(_) => f.write_str("All Pending Transaction Outputs"),
             DbValue::KeyManagerState(_) => f.write_str(&format!("Key Manager State")),
+            DbValue::BranchKeyIndex(_) => f.write_str("Branch Key Index"),
+            DbValue::BranchKeyIndices(_) => f.write_str("Branch Key Indices"),
         }
     }
 }
@@ -39,15 +39,19 @@ use std::{
     sync::{Arc, RwLock},
     time::Duration,
 };
-use tari_transactions::transaction::UnblindedOutput;
+use tari_transactions::{transaction::UnblindedOutput, types::BlindingFactor};
 
 /// This structure is an In-Memory database backend that implements the `OutputManagerBackend` trait and provides all
 /// the functionality required by the trait.
+#[derive(Clone)]
 pub struct InnerDatabase {
     unspent_outputs: Vec<UnblindedOutput>,
     spent_outputs: Vec<UnblindedOutput>,
+    frozen_outputs: Vec<UnblindedOutput>,
+    invalidated_outputs: Vec<UnblindedOutput>,
     pending_transactions: HashMap<TxId, PendingTransactionOutputs>,
     key_manager_state: Option<KeyManagerState>,
+    branch_key_indices: HashMap<String, usize>,
 }
 
 impl InnerDatabase {
@@ -55,8 +59,11 @@ impl InnerDatabase {
         Self {
             unspent_outputs: Vec::new(),
             spent_outputs: Vec::new(),
+            frozen_outputs: Vec::new(),
+            invalidated_outputs: Vec::new(),
             pending_transactions: HashMap::new(),
             key_manager_state: None,
+            branch_key_indices: HashMap::new(),
         }
     }
 }
@@ -74,6 +81,101 @@ impl OutputManagerMemoryDatabase {
     }
 }
 
+/// Returns true if an unspent, spent, frozen or invalidated output with the given spending key is stored
+fn contains_output(db: &InnerDatabase, spending_key: &BlindingFactor) -> bool {
+    db.unspent_outputs
+        .iter()
+        .chain(db.spent_outputs.iter())
+        .chain(db.frozen_outputs.iter())
+        .chain(db.invalidated_outputs.iter())
+        .any(|v| &v.spending_key == spending_key)
+}
+
+fn insert(db: &mut InnerDatabase, kvp: DbKeyValuePair) -> Result<(), OutputManagerStorageError> {
+    match kvp {
+        DbKeyValuePair::SpentOutput(k, o) => {
+            if contains_output(db, &k) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            db.spent_outputs.push(*o);
+        },
+        DbKeyValuePair::UnspentOutput(k, o) => {
+            if contains_output(db, &k) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            db.unspent_outputs.push(*o);
+        },
+        DbKeyValuePair::FrozenOutput(k, o) => {
+            if contains_output(db, &k) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            db.frozen_outputs.push(*o);
+        },
+        DbKeyValuePair::InvalidatedOutput(k, o) => {
+            if contains_output(db, &k) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            db.invalidated_outputs.push(*o);
+        },
+        DbKeyValuePair::PendingTransactionOutputs(t, p) => {
+            db.pending_transactions.insert(t, *p);
+        },
+        DbKeyValuePair::KeyManagerState(km) => db.key_manager_state = Some(km),
+        DbKeyValuePair::BranchKeyIndex(branch, index) => {
+            db.branch_key_indices.insert(branch, index);
+        },
+    }
+    Ok(())
+}
+
+/// Applies a single write operation to the database
+fn write(db: &mut InnerDatabase, op: WriteOperation) -> Result<Option<DbValue>, OutputManagerStorageError> {
+    match op {
+        WriteOperation::Insert(kvp) => insert(db, kvp)?,
+        WriteOperation::InsertBatch(kvps) => {
+            let snapshot = db.clone();
+            for kvp in kvps {
+                if let Err(e) = insert(db, kvp) {
+                    *db = snapshot;
+                    return Err(e);
+                }
+            }
+        },
+        WriteOperation::Remove(k) => match k {
+            DbKey::SpentOutput(k) => match db.spent_outputs.iter().position(|v| v.spending_key == k) {
+                None => return Err(OutputManagerStorageError::ValueNotFound(DbKey::SpentOutput(k))),
+                Some(pos) => {
+                    return Ok(Some(DbValue::SpentOutput(Box::new(db.spent_outputs.remove(pos)))));
+                },
+            },
+            DbKey::UnspentOutput(k) => match db.unspent_outputs.iter().position(|v| v.spending_key == k) {
+                None => return Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(k))),
+                Some(pos) => {
+                    return Ok(Some(DbValue::UnspentOutput(Box::new(db.unspent_outputs.remove(pos)))));
+                },
+            },
+            DbKey::PendingTransactionOutputs(tx_id) => {
+                if let Some(p) = db.pending_transactions.remove(&tx_id) {
+                    return Ok(Some(DbValue::PendingTransactionOutputs(Box::new(p))));
+                } else {
+                    return Err(OutputManagerStorageError::ValueNotFound(
+                        DbKey::PendingTransactionOutputs(tx_id),
+                    ));
+                }
+            },
+            DbKey::UnspentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::SpentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::FrozenOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::InvalidatedOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::AllPendingTransactionOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::KeyManagerState => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::BranchKeyIndex(_) => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::BranchKeyIndices => return Err(OutputManagerStorageError::OperationNotSupported),
+        },
+    }
+    Ok(None)
+}
+
 impl OutputManagerBackend for OutputManagerMemoryDatabase {
     fn fetch(&self, key: &DbKey) -> Result<Option<DbValue>, OutputManagerStorageError> {
         let db = acquire_read_lock!(self.db);
@@ -94,6 +196,8 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
                 .map(|v| DbValue::PendingTransactionOutputs(Box::new(v.clone()))),
             DbKey::UnspentOutputs => Some(DbValue::UnspentOutputs(db.unspent_outputs.clone())),
             DbKey::SpentOutputs => Some(DbValue::SpentOutputs(db.spent_outputs.clone())),
+            DbKey::FrozenOutputs => Some(DbValue::FrozenOutputs(db.frozen_outputs.clone())),
+            DbKey::InvalidatedOutputs => Some(DbValue::InvalidatedOutputs(db.invalidated_outputs.clone())),
             DbKey::AllPendingTransactionOutputs => {
                 Some(DbValue::AllPendingTransactionOutputs(db.pending_transactions.clone()))
             },
@@ -101,6 +205,11 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
                 .key_manager_state
                 .as_ref()
                 .map(|km| DbValue::KeyManagerState(km.clone())),
+            DbKey::BranchKeyIndex(branch) => db
+                .branch_key_indices
+                .get(branch)
+                .map(|index| DbValue::BranchKeyIndex(*index)),
+            DbKey::BranchKeyIndices => Some(DbValue::BranchKeyIndices(db.branch_key_indices.clone())),
         };
 
         Ok(result)
@@ -108,58 +217,19 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
 
     fn write(&mut self, op: WriteOperation) -> Result<Option<DbValue>, OutputManagerStorageError> {
         let mut db = acquire_write_lock!(self.db);
-        match op {
-            WriteOperation::Insert(kvp) => match kvp {
-                DbKeyValuePair::SpentOutput(k, o) => {
-                    if db.spent_outputs.iter().any(|v| v.spending_key == k) ||
-                        db.unspent_outputs.iter().any(|v| v.spending_key == k)
-                    {
-                        return Err(OutputManagerStorageError::DuplicateOutput);
-                    }
-                    db.spent_outputs.push(*o);
-                },
-                DbKeyValuePair::UnspentOutput(k, o) => {
-                    if db.unspent_outputs.iter().any(|v| v.spending_key == k) ||
-                        db.spent_outputs.iter().any(|v| v.spending_key == k)
-                    {
-                        return Err(OutputManagerStorageError::DuplicateOutput);
-                    }
-                    db.unspent_outputs.push(*o);
-                },
-                DbKeyValuePair::PendingTransactionOutputs(t, p) => {
-                    db.pending_transactions.insert(t, *p);
-                },
-                DbKeyValuePair::KeyManagerState(km) => db.key_manager_state = Some(km),
-            },
-            WriteOperation::Remove(k) => match k {
-                DbKey::SpentOutput(k) => match db.spent_outputs.iter().position(|v| v.spending_key == k) {
-                    None => return Err(OutputManagerStorageError::ValueNotFound(DbKey::SpentOutput(k))),
-                    Some(pos) => {
-                        return Ok(Some(DbValue::SpentOutput(Box::new(db.spent_outputs.remove(pos)))));
-                    },
-                },
-                DbKey::UnspentOutput(k) => match db.unspent_outputs.iter().position(|v| v.spending_key == k) {
-                    None => return Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(k))),
-                    Some(pos) => {
-                        return Ok(Some(DbValue::UnspentOutput(Box::new(db.unspent_outputs.remove(pos)))));
-                    },
-                },
-                DbKey::PendingTransactionOutputs(tx_id) => {
-                    if let Some(p) = db.pending_transactions.remove(&tx_id) {
-                        return Ok(Some(DbValue::PendingTransactionOutputs(Box::new(p))));
-                    } else {
-                        return Err(OutputManagerStorageError::ValueNotFound(
-                            DbKey::PendingTransactionOutputs(tx_id),
-                        ));
-                    }
-                },
-                DbKey::UnspentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::SpentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::AllPendingTransactionOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::KeyManagerState => return Err(OutputManagerStorageError::OperationNotSupported),
-            },
+        write(&mut db, op)
+    }
+
+    fn write_many(&mut self, ops: Vec<WriteOperation>) -> Result<(), OutputManagerStorageError> {
+        let mut db = acquire_write_lock!(self.db);
+        let snapshot = (*db).clone();
+        for op in ops {
+            if let Err(e) = write(&mut db, op) {
+                *db = snapshot;
+                return Err(e);
+            }
         }
-        Ok(None)
+        Ok(())
     }
 
     fn confirm_transaction(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
@@ -184,6 +254,22 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
         Ok(())
     }
 
+    fn confirm_received_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        let mut db = acquire_write_lock!(self.db);
+        let received = db
+            .pending_transactions
+            .get_mut(&tx_id)
+            .ok_or(OutputManagerStorageError::ValueNotFound(
+                DbKey::PendingTransactionOutputs(tx_id.clone()),
+            ))?
+            .outputs_to_be_received
+            .drain(..)
+            .collect::<Vec<_>>();
+        db.unspent_outputs.extend(received);
+
+        Ok(())
+    }
+
     fn encumber_outputs(
         &mut self,
         tx_id: TxId,
@@ -248,6 +334,44 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
         Ok(())
     }
 
+    fn set_output_frozen(
+        &mut self,
+        spending_key: &BlindingFactor,
+        frozen: bool,
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        let mut guard = acquire_write_lock!(self.db);
+        let db = &mut *guard;
+        let (from, to) = if frozen {
+            (&mut db.unspent_outputs, &mut db.frozen_outputs)
+        } else {
+            (&mut db.frozen_outputs, &mut db.unspent_outputs)
+        };
+        match from.iter().position(|v| &v.spending_key == spending_key) {
+            None => Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(
+                spending_key.clone(),
+            ))),
+            Some(pos) => {
+                to.push(from.remove(pos));
+                Ok(())
+            },
+        }
+    }
+
+    fn invalidate_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        let mut db = acquire_write_lock!(self.db);
+        match db.unspent_outputs.iter().position(|v| &v.spending_key == spending_key) {
+            None => Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(
+                spending_key.clone(),
+            ))),
+            Some(pos) => {
+                let output = db.unspent_outputs.remove(pos);
+                db.invalidated_outputs.push(output);
+                Ok(())
+            },
+        }
+    }
+
     fn increment_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
         let mut db = acquire_write_lock!(self.db);
 
@@ -261,4 +385,30 @@ impl OutputManagerBackend for OutputManagerMemoryDatabase {
 
         Ok(())
     }
+
+    fn increment_key_index_for_branch(&mut self, branch: &str) -> Result<(), OutputManagerStorageError> {
+        let mut db = acquire_write_lock!(self.db);
+
+        if db.key_manager_state.is_none() {
+            return Err(OutputManagerStorageError::KeyManagerNotInitialized);
+        }
+        *db.branch_key_indices.entry(branch.to_string()).or_insert(0) += 1;
+
+        Ok(())
+    }
+
+    fn decrement_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        let mut db = acquire_write_lock!(self.db);
+
+        let state = db
+            .key_manager_state
+            .as_mut()
+            .ok_or(OutputManagerStorageError::KeyManagerNotInitialized)?;
+        state.primary_key_index = state
+            .primary_key_index
+            .checked_sub(1)
+            .ok_or(OutputManagerStorageError::KeyIndexUnderflow)?;
+
+        Ok(())
+    }
 }
@@ -34,7 +34,7 @@ use crate::{
         },
         TxId,
     },
-    schema::{key_manager_states, outputs, pending_transaction_outputs},
+    schema::{key_manager_branches, key_manager_states, outputs, pending_transaction_outputs},
 };
 use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
 use diesel::{
@@ -47,7 +47,7 @@ use std::{collections::HashMap, convert::TryFrom, io, path::Path, time::Duration
 use tari_transactions::{
     tari_amount::MicroTari,
     transaction::{OutputFeatures, OutputFlags, UnblindedOutput},
-    types::PrivateKey,
+    types::{BlindingFactor, PrivateKey},
 };
 use tari_utilities::ByteArray;
 
@@ -141,6 +141,18 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                     .map(|o| UnblindedOutput::try_from(o.clone()))
                     .collect::<Result<Vec<_>, _>>()?,
             )),
+            DbKey::FrozenOutputs => Some(DbValue::FrozenOutputs(
+                OutputSql::index_frozen(&conn)?
+                    .iter()
+                    .map(|o| UnblindedOutput::try_from(o.clone()))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            DbKey::InvalidatedOutputs => Some(DbValue::InvalidatedOutputs(
+                OutputSql::index_invalidated(&conn)?
+                    .iter()
+                    .map(|o| UnblindedOutput::try_from(o.clone()))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
             DbKey::AllPendingTransactionOutputs => {
                 let pending_sql_txs = PendingTransactionOutputSql::index(&conn)?;
                 let mut pending_txs = HashMap::new();
@@ -161,6 +173,22 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                 None => None,
                 Some(km) => Some(DbValue::KeyManagerState(KeyManagerState::try_from(km)?)),
             },
+            DbKey::BranchKeyIndex(branch) => match KeyManagerBranchSql::find(branch, &conn) {
+                Ok(b) => Some(DbValue::BranchKeyIndex(b.key_index as usize)),
+                Err(e) => {
+                    match e {
+                        OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
+                        e => return Err(e),
+                    };
+                    None
+                },
+            },
+            DbKey::BranchKeyIndices => Some(DbValue::BranchKeyIndices(
+                KeyManagerBranchSql::index(&conn)?
+                    .into_iter()
+                    .map(|b| (b.branch, b.key_index as usize))
+                    .collect(),
+            )),
         };
 
         Ok(result)
@@ -173,86 +201,22 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
             .get()
             .map_err(|_| OutputManagerStorageError::R2d2Error)?;
 
-        match op {
-            WriteOperation::Insert(kvp) => match kvp {
-                DbKeyValuePair::SpentOutput(k, o) => {
-                    if let Ok(_) = OutputSql::find(&k.to_vec(), &conn) {
-                        return Err(OutputManagerStorageError::DuplicateOutput);
-                    }
-                    OutputSql::new(*o, true, false, false, None).commit(&conn)?
-                },
-                DbKeyValuePair::UnspentOutput(k, o) => {
-                    if let Ok(_) = OutputSql::find(&k.to_vec(), &conn) {
-                        return Err(OutputManagerStorageError::DuplicateOutput);
-                    }
-                    OutputSql::new(*o, false, false, false, None).commit(&conn)?
-                },
-                DbKeyValuePair::PendingTransactionOutputs(tx_id, p) => {
-                    if let Ok(_) = PendingTransactionOutputSql::find(&tx_id, &conn) {
-                        return Err(OutputManagerStorageError::DuplicateOutput);
-                    }
-                    PendingTransactionOutputSql::new(p.tx_id.clone(), p.timestamp.clone()).commit(&conn)?;
-                    for o in p.outputs_to_be_spent {
-                        OutputSql::new(o.clone(), false, false, true, Some(p.tx_id.clone())).commit(&conn)?;
-                    }
-                    for o in p.outputs_to_be_received {
-                        OutputSql::new(o.clone(), false, true, true, Some(p.tx_id.clone())).commit(&conn)?;
-                    }
-                },
-                DbKeyValuePair::KeyManagerState(km) => KeyManagerStateSql::set_state(km, &conn)?,
-            },
-            WriteOperation::Remove(k) => match k {
-                DbKey::SpentOutput(s) => match OutputSql::find_spent(&s.to_vec(), true, &conn) {
-                    Ok(o) => {
-                        o.clone().delete(&conn)?;
-                        return Ok(Some(DbValue::SpentOutput(Box::new(UnblindedOutput::try_from(o)?))));
-                    },
-                    Err(e) => {
-                        match e {
-                            OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
-                            e => return Err(e),
-                        };
-                    },
-                },
-                DbKey::UnspentOutput(k) => match OutputSql::find_spent(&k.to_vec(), false, &conn) {
-                    Ok(o) => {
-                        o.clone().delete(&conn)?;
-                        return Ok(Some(DbValue::SpentOutput(Box::new(UnblindedOutput::try_from(o)?))));
-                    },
-                    Err(e) => {
-                        match e {
-                            OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
-                            e => return Err(e),
-                        };
-                    },
-                },
-                DbKey::PendingTransactionOutputs(tx_id) => match PendingTransactionOutputSql::find(&tx_id, &conn) {
-                    Ok(p) => {
-                        let outputs = OutputSql::find_by_tx_id_and_encumbered(&(p.tx_id as u64), &conn)?;
-                        p.clone().delete(&conn)?;
-                        return Ok(Some(DbValue::PendingTransactionOutputs(Box::new(
-                            pending_transaction_outputs_from_sql_outputs(
-                                &(p.tx_id.clone() as u64),
-                                &p.timestamp,
-                                outputs,
-                            )?,
-                        ))));
-                    },
-                    Err(e) => {
-                        match e {
-                            OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
-                            e => return Err(e),
-                        };
-                    },
-                },
-                DbKey::UnspentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::SpentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::AllPendingTransactionOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
-                DbKey::KeyManagerState => return Err(OutputManagerStorageError::OperationNotSupported),
-            },
-        }
+        write(op, &conn)
+    }
 
-        Ok(None)
+    fn write_many(&mut self, ops: Vec<WriteOperation>) -> Result<(), OutputManagerStorageError> {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        conn.transaction::<_, OutputManagerStorageError, _>(|| {
+            for op in ops {
+                write(op, &conn)?;
+            }
+            Ok(())
+        })
     }
 
     fn confirm_transaction(&mut self, tx_id: u64) -> Result<(), OutputManagerStorageError> {
@@ -274,6 +238,8 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                                 received: None,
                                 encumbered: Some(false),
                                 tx_id: None,
+                                frozen: None,
+                                invalidated: None,
                             },
                             &conn,
                         )?;
@@ -284,6 +250,8 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                                 received: None,
                                 encumbered: Some(false),
                                 tx_id: None,
+                                frozen: None,
+                                invalidated: None,
                             },
                             &conn,
                         )?;
@@ -303,6 +271,46 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
         Ok(())
     }
 
+    fn confirm_received_outputs(&mut self, tx_id: u64) -> Result<(), OutputManagerStorageError> {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        match PendingTransactionOutputSql::find(&tx_id, &conn) {
+            Ok(_) => {
+                let outputs = OutputSql::find_by_tx_id_and_encumbered(&tx_id, &conn)?;
+
+                for o in outputs.into_iter().filter(|o| o.to_be_received == 1i32) {
+                    o.update(
+                        UpdateOutput {
+                            spent: None,
+                            received: None,
+                            encumbered: Some(false),
+                            tx_id: None,
+                            frozen: None,
+                            invalidated: None,
+                        },
+                        &conn,
+                    )?;
+                }
+            },
+            Err(e) => {
+                match e {
+                    OutputManagerStorageError::DieselError(DieselError::NotFound) => {
+                        return Err(OutputManagerStorageError::ValueNotFound(
+                            DbKey::PendingTransactionOutputs(tx_id.clone()),
+                        ))
+                    },
+                    e => return Err(e),
+                };
+            },
+        }
+
+        Ok(())
+    }
+
     fn encumber_outputs(
         &mut self,
         tx_id: u64,
@@ -322,6 +330,9 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
             if output.spent == 1 {
                 return Err(OutputManagerStorageError::OutputAlreadySpent);
             }
+            if output.frozen == 1 || output.invalidated == 1 {
+                return Err(OutputManagerStorageError::ValuesNotFound);
+            }
             outputs_to_be_spent.push(output);
         }
 
@@ -334,6 +345,8 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                     received: None,
                     encumbered: Some(true),
                     tx_id: Some(tx_id.clone()),
+                    frozen: None,
+                    invalidated: None,
                 },
                 &conn,
             )?;
@@ -367,6 +380,8 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
                                 received: None,
                                 encumbered: Some(false),
                                 tx_id: None,
+                                frozen: None,
+                                invalidated: None,
                             },
                             &conn,
                         )?;
@@ -409,6 +424,75 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
         Ok(())
     }
 
+    fn set_output_frozen(
+        &mut self,
+        spending_key: &BlindingFactor,
+        frozen: bool,
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        let not_found = || OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(spending_key.clone()));
+        let output = match OutputSql::find_spent(&spending_key.to_vec(), false, &conn) {
+            Ok(o) => o,
+            Err(OutputManagerStorageError::DieselError(DieselError::NotFound)) => return Err(not_found()),
+            Err(e) => return Err(e),
+        };
+        // Only unencumbered, valid outputs that are not already in the requested state can be frozen or unfrozen
+        if output.encumbered == 1 || output.invalidated == 1 || output.frozen == frozen as i32 {
+            return Err(not_found());
+        }
+        output.update(
+            UpdateOutput {
+                spent: None,
+                received: None,
+                encumbered: None,
+                tx_id: None,
+                frozen: Some(frozen),
+                invalidated: None,
+            },
+            &conn,
+        )?;
+
+        Ok(())
+    }
+
+    fn invalidate_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        let not_found = || OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(spending_key.clone()));
+        let output = match OutputSql::find_spent(&spending_key.to_vec(), false, &conn) {
+            Ok(o) => o,
+            Err(OutputManagerStorageError::DieselError(DieselError::NotFound)) => return Err(not_found()),
+            Err(e) => return Err(e),
+        };
+        // Only outputs in the unspent pool can be invalidated
+        if output.encumbered == 1 || output.frozen == 1 || output.invalidated == 1 {
+            return Err(not_found());
+        }
+        output.update(
+            UpdateOutput {
+                spent: None,
+                received: None,
+                encumbered: None,
+                tx_id: None,
+                frozen: None,
+                invalidated: Some(true),
+            },
+            &conn,
+        )?;
+
+        Ok(())
+    }
+
     fn increment_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
         let conn = self
             .database_connection_pool
@@ -420,6 +504,155 @@ impl OutputManagerBackend for OutputManagerSqliteDatabase {
 
         Ok(())
     }
+
+    fn increment_key_index_for_branch(&mut self, branch: &str) -> Result<(), OutputManagerStorageError> {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        KeyManagerBranchSql::increment_index(branch, &conn)?;
+
+        Ok(())
+    }
+
+    fn decrement_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        let conn = self
+            .database_connection_pool
+            .clone()
+            .get()
+            .map_err(|_| OutputManagerStorageError::R2d2Error)?;
+
+        KeyManagerStateSql::decrement_index(&conn)?;
+
+        Ok(())
+    }
+}
+
+/// Applies a single write operation to the database
+fn write(
+    op: WriteOperation,
+    conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<Option<DbValue>, OutputManagerStorageError>
+{
+    match op {
+        WriteOperation::Insert(kvp) => insert(kvp, conn)?,
+        WriteOperation::InsertBatch(kvps) => conn.transaction::<_, OutputManagerStorageError, _>(|| {
+            for kvp in kvps {
+                insert(kvp, conn)?;
+            }
+            Ok(())
+        })?,
+        WriteOperation::Remove(k) => match k {
+            DbKey::SpentOutput(s) => match OutputSql::find_spent(&s.to_vec(), true, conn) {
+                Ok(o) => {
+                    o.clone().delete(conn)?;
+                    return Ok(Some(DbValue::SpentOutput(Box::new(UnblindedOutput::try_from(o)?))));
+                },
+                Err(e) => {
+                    match e {
+                        OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
+                        e => return Err(e),
+                    };
+                },
+            },
+            DbKey::UnspentOutput(k) => match OutputSql::find_spent(&k.to_vec(), false, conn) {
+                Ok(o) => {
+                    o.clone().delete(conn)?;
+                    return Ok(Some(DbValue::SpentOutput(Box::new(UnblindedOutput::try_from(o)?))));
+                },
+                Err(e) => {
+                    match e {
+                        OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
+                        e => return Err(e),
+                    };
+                },
+            },
+            DbKey::PendingTransactionOutputs(tx_id) => match PendingTransactionOutputSql::find(&tx_id, conn) {
+                Ok(p) => {
+                    let outputs = OutputSql::find_by_tx_id_and_encumbered(&(p.tx_id as u64), conn)?;
+                    p.clone().delete(conn)?;
+                    return Ok(Some(DbValue::PendingTransactionOutputs(Box::new(
+                        pending_transaction_outputs_from_sql_outputs(&(p.tx_id.clone() as u64), &p.timestamp, outputs)?,
+                    ))));
+                },
+                Err(e) => {
+                    match e {
+                        OutputManagerStorageError::DieselError(DieselError::NotFound) => (),
+                        e => return Err(e),
+                    };
+                },
+            },
+            DbKey::UnspentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::SpentOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::FrozenOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::InvalidatedOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::AllPendingTransactionOutputs => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::KeyManagerState => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::BranchKeyIndex(_) => return Err(OutputManagerStorageError::OperationNotSupported),
+            DbKey::BranchKeyIndices => return Err(OutputManagerStorageError::OperationNotSupported),
+        },
+    }
+
+    Ok(None)
+}
+
+/// Inserts a single key value pair into the database
+fn insert(
+    kvp: DbKeyValuePair,
+    conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+) -> Result<(), OutputManagerStorageError>
+{
+    match kvp {
+        DbKeyValuePair::SpentOutput(k, o) => {
+            if let Ok(_) = OutputSql::find(&k.to_vec(), conn) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            OutputSql::new(*o, true, false, false, None).commit(conn)?
+        },
+        DbKeyValuePair::UnspentOutput(k, o) => {
+            if let Ok(_) = OutputSql::find(&k.to_vec(), conn) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            OutputSql::new(*o, false, false, false, None).commit(conn)?
+        },
+        DbKeyValuePair::FrozenOutput(k, o) => {
+            if let Ok(_) = OutputSql::find(&k.to_vec(), conn) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            let mut output = OutputSql::new(*o, false, false, false, None);
+            output.frozen = 1;
+            output.commit(conn)?
+        },
+        DbKeyValuePair::InvalidatedOutput(k, o) => {
+            if let Ok(_) = OutputSql::find(&k.to_vec(), conn) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            let mut output = OutputSql::new(*o, false, false, false, None);
+            output.invalidated = 1;
+            output.commit(conn)?
+        },
+        DbKeyValuePair::PendingTransactionOutputs(tx_id, p) => {
+            if let Ok(_) = PendingTransactionOutputSql::find(&tx_id, conn) {
+                return Err(OutputManagerStorageError::DuplicateOutput);
+            }
+            PendingTransactionOutputSql::new(p.tx_id.clone(), p.timestamp.clone()).commit(conn)?;
+            for o in p.outputs_to_be_spent {
+                OutputSql::new(o.clone(), false, false, true, Some(p.tx_id.clone())).commit(conn)?;
+            }
+            for o in p.outputs_to_be_received {
+                OutputSql::new(o.clone(), false, true, true, Some(p.tx_id.clone())).commit(conn)?;
+            }
+        },
+        DbKeyValuePair::KeyManagerState(km) => KeyManagerStateSql::set_state(km, conn)?,
+        DbKeyValuePair::BranchKeyIndex(branch, index) => KeyManagerBranchSql {
+            branch,
+            key_index: index as i64,
+        }
+        .set(conn)?,
+    }
+    Ok(())
 }
 
 /// A utility function to construct a PendingTransactionOutputs structure for a TxId, set of Outputs and a Timestamp
@@ -460,6 +693,8 @@ struct OutputSql {
     to_be_received: i32,
     encumbered: i32,
     tx_id: Option<i64>,
+    frozen: i32,
+    invalidated: i32,
 }
 
 impl OutputSql {
@@ -480,6 +715,8 @@ impl OutputSql {
             to_be_received: to_be_received as i32,
             encumbered: encumbered as i32,
             tx_id: tx_id.map(|i| i as i64),
+            frozen: 0,
+            invalidated: 0,
         }
     }
 
@@ -503,7 +740,7 @@ impl OutputSql {
             .load::<OutputSql>(conn)?)
     }
 
-    /// Return all unencumbered outputs with the specified spent status
+    /// Return all unencumbered, unfrozen and valid outputs with the specified spent status
     pub fn index_spent(
         spent: bool,
         conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
@@ -511,10 +748,29 @@ impl OutputSql {
     {
         Ok(outputs::table
             .filter(outputs::encumbered.eq(false as i32))
+            .filter(outputs::frozen.eq(false as i32))
+            .filter(outputs::invalidated.eq(false as i32))
             .filter(outputs::spent.eq(spent as i32))
             .load(conn)?)
     }
 
+    /// Return all unspent outputs that have been frozen
+    pub fn index_frozen(
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<Vec<OutputSql>, OutputManagerStorageError> {
+        Ok(outputs::table
+            .filter(outputs::frozen.eq(true as i32))
+            .filter(outputs::spent.eq(false as i32))
+            .load(conn)?)
+    }
+
+    /// Return all outputs that have been invalidated
+    pub fn index_invalidated(
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<Vec<OutputSql>, OutputManagerStorageError> {
+        Ok(outputs::table.filter(outputs::invalidated.eq(true as i32)).load(conn)?)
+    }
+
     /// Find a particular Output, if it exists
     pub fn find(
         spending_key: &Vec<u8>,
@@ -629,6 +885,8 @@ pub struct UpdateOutput {
     received: Option<bool>,
     encumbered: Option<bool>,
     tx_id: Option<TxId>,
+    frozen: Option<bool>,
+    invalidated: Option<bool>,
 }
 
 #[derive(AsChangeset)]
@@ -638,6 +896,8 @@ pub struct UpdateOutputSql {
     to_be_received: Option<i32>,
     encumbered: Option<i32>,
     tx_id: Option<i64>,
+    frozen: Option<i32>,
+    invalidated: Option<i32>,
 }
 
 #[derive(AsChangeset)]
@@ -656,6 +916,8 @@ impl From<UpdateOutput> for UpdateOutputSql {
             to_be_received: u.received.map(|r| r as i32),
             encumbered: u.encumbered.map(|e| e as i32),
             tx_id: u.tx_id.map(|t| t as i64),
+            frozen: u.frozen.map(|f| f as i32),
+            invalidated: u.invalidated.map(|i| i as i32),
         }
     }
 }
@@ -842,6 +1104,99 @@ impl KeyManagerStateSql {
             Err(_) => return Err(OutputManagerStorageError::KeyManagerNotInitialized),
         })
     }
+
+    pub fn decrement_index(
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<usize, OutputManagerStorageError> {
+        Ok(match KeyManagerStateSql::get_state(conn) {
+            Ok(km) => {
+                if km.primary_key_index <= 0 {
+                    return Err(OutputManagerStorageError::KeyIndexUnderflow);
+                }
+                let current_index = (km.primary_key_index - 1) as usize;
+                let update = KeyManagerStateUpdate {
+                    master_seed: None,
+                    branch_seed: None,
+                    primary_key_index: Some(current_index),
+                };
+                let num_updated = diesel::update(key_manager_states::table.filter(key_manager_states::id.eq(&km.id)))
+                    .set(KeyManagerStateUpdateSql::from(update))
+                    .execute(conn)?;
+                if num_updated == 0 {
+                    return Err(OutputManagerStorageError::UnexpectedResult(
+                        "Database update error".to_string(),
+                    ));
+                }
+                current_index
+            },
+            Err(_) => return Err(OutputManagerStorageError::KeyManagerNotInitialized),
+        })
+    }
+}
+
+/// The key index of a key manager branch other than the default branch of the key manager state
+#[derive(Clone, Debug, Queryable, Insertable)]
+#[table_name = "key_manager_branches"]
+struct KeyManagerBranchSql {
+    branch: String,
+    key_index: i64,
+}
+
+impl KeyManagerBranchSql {
+    pub fn index(
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<Vec<KeyManagerBranchSql>, OutputManagerStorageError> {
+        Ok(key_manager_branches::table.load::<KeyManagerBranchSql>(conn)?)
+    }
+
+    /// Sets the key index of the branch, replacing any index that is already stored for it
+    pub fn set(
+        self,
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        diesel::replace_into(key_manager_branches::table)
+            .values(self)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn find(
+        branch: &str,
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<KeyManagerBranchSql, OutputManagerStorageError>
+    {
+        Ok(key_manager_branches::table
+            .filter(key_manager_branches::branch.eq(branch))
+            .first::<KeyManagerBranchSql>(conn)?)
+    }
+
+    pub fn increment_index(
+        branch: &str,
+        conn: &PooledConnection<ConnectionManager<SqliteConnection>>,
+    ) -> Result<usize, OutputManagerStorageError>
+    {
+        KeyManagerStateSql::get_state(conn)?;
+        match KeyManagerBranchSql::find(branch, conn) {
+            Ok(b) => {
+                let current_index = b.key_index + 1;
+                diesel::update(key_manager_branches::table.filter(key_manager_branches::branch.eq(branch)))
+                    .set(key_manager_branches::key_index.eq(current_index))
+                    .execute(conn)?;
+                Ok(current_index as usize)
+            },
+            Err(OutputManagerStorageError::DieselError(DieselError::NotFound)) => {
+                diesel::insert_into(key_manager_branches::table)
+                    .values(KeyManagerBranchSql {
+                        branch: branch.to_string(),
+                        key_index: 1,
+                    })
+                    .execute(conn)?;
+                Ok(1)
+            },
+            Err(e) => Err(e),
+        }
+    }
 }
 
 struct KeyManagerStateUpdate {
@@ -971,6 +1326,8 @@ mod test {
                     received: None,
                     encumbered: None,
                     tx_id: Some(tx_id),
+                    frozen: None,
+                    invalidated: None,
                 },
                 &conn,
             )
@@ -1000,6 +1357,8 @@ mod test {
                     received: None,
                     encumbered: None,
                     tx_id: Some(44u64),
+                    frozen: None,
+                    invalidated: None,
                 },
                 &conn,
             )
@@ -1013,6 +1372,8 @@ mod test {
                     received: None,
                     encumbered: Some(true),
                     tx_id: Some(44u64),
+                    frozen: None,
+                    invalidated: None,
                 },
                 &conn,
             )
@@ -39,6 +39,13 @@ table! {
     }
 }
 
+table! {
+    key_manager_branches (branch) {
+        branch -> Text,
+        key_index -> BigInt,
+    }
+}
+
 table! {
     key_manager_states (id) {
         id -> Nullable<BigInt>,
@@ -71,6 +78,8 @@ table! {
         to_be_received -> Integer,
         encumbered -> Integer,
         tx_id -> Nullable<BigInt>,
+        frozen -> Integer,
+        invalidated -> Integer,
     }
 }
 
@@ -95,6 +104,7 @@ allow_tables_to_appear_in_same_query!(
     completed_transactions,
     contacts,
     inbound_transactions,
+    key_manager_branches,
     key_manager_states,
     outbound_transactions,
     outputs,
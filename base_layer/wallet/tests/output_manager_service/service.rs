@@ -170,22 +170,24 @@ fn send_not_enough_funds<T: OutputManagerBackend + 'static>(backend: T) {
 
     let (mut oms, _shutdown) = setup_output_manager_service(&runtime, backend);
     let num_outputs = 20;
+    let mut total = MicroTari::from(0);
     for _i in 0..num_outputs {
         let (_ti, uo) = make_input(
             &mut rng.clone(),
             MicroTari::from(100 + rng.next_u64() % 1000),
             &factories.commitment,
         );
+        total += uo.value;
         runtime.block_on(oms.add_output(uo)).unwrap();
     }
 
-    match runtime.block_on(oms.prepare_transaction_to_send(
-        MicroTari::from(num_outputs * 2000),
-        MicroTari::from(20),
-        None,
-        "".to_string(),
-    )) {
-        Err(OutputManagerError::NotEnoughFunds) => assert!(true),
+    let amount = MicroTari::from(num_outputs * 2000);
+    let fee_per_gram = MicroTari::from(20);
+    match runtime.block_on(oms.prepare_transaction_to_send(amount, fee_per_gram, None, "".to_string())) {
+        Err(OutputManagerError::InsufficientFunds { required, available }) => {
+            assert_eq!(available, total);
+            assert_eq!(required, amount + Fee::calculate(fee_per_gram, num_outputs as usize, 1));
+        },
         _ => assert!(false),
     }
 }
@@ -265,6 +267,67 @@ fn send_no_change<T: OutputManagerBackend + 'static>(backend: T) {
     assert_eq!(runtime.block_on(oms.get_unspent_outputs()).unwrap().len(), 0);
 }
 
+fn send_with_immature_output<T: OutputManagerBackend + 'static>(backend: T) {
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let runtime = Runtime::new().unwrap();
+
+    let (mut oms, _shutdown) = setup_output_manager_service(&runtime, backend);
+
+    let fee_per_gram = MicroTari::from(20);
+    let mature_value = MicroTari::from(500);
+    runtime
+        .block_on(oms.add_output(UnblindedOutput::new(mature_value, PrivateKey::random(&mut rng), None)))
+        .unwrap();
+    let immature_key = PrivateKey::random(&mut rng);
+    runtime
+        .block_on(oms.add_output(UnblindedOutput::new(
+            MicroTari::from(5000),
+            immature_key.clone(),
+            Some(OutputFeatures::with_maturity(10)),
+        )))
+        .unwrap();
+
+    // The immature output is not selected before the chain reaches its maturity height
+    let amount = MicroTari::from(1000);
+    runtime.block_on(oms.set_chain_height(9)).unwrap();
+    match runtime.block_on(oms.prepare_transaction_to_send(amount, fee_per_gram, None, "".to_string())) {
+        Err(OutputManagerError::InsufficientFunds { required, available }) => {
+            assert_eq!(available, mature_value);
+            assert_eq!(required, amount + Fee::calculate(fee_per_gram, 1, 1));
+        },
+        _ => assert!(false),
+    }
+
+    runtime.block_on(oms.set_chain_height(10)).unwrap();
+    runtime
+        .block_on(oms.prepare_transaction_to_send(amount, fee_per_gram, None, "".to_string()))
+        .unwrap();
+    let pending_txs = runtime.block_on(oms.get_pending_transactions()).unwrap();
+    assert_eq!(pending_txs.len(), 1);
+    assert!(pending_txs
+        .values()
+        .next()
+        .unwrap()
+        .outputs_to_be_spent
+        .iter()
+        .any(|o| o.spending_key == immature_key));
+}
+
+#[test]
+fn send_with_immature_output_memory_db() {
+    send_with_immature_output(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+fn send_with_immature_output_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let db_tempdir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = db_tempdir.path().to_str().unwrap().to_string();
+    let db_path = format!("{}/{}", db_folder, db_name);
+    send_with_immature_output(OutputManagerSqliteDatabase::new(db_path).unwrap());
+}
+
 #[test]
 fn send_no_change_memory_db() {
     send_no_change(OutputManagerMemoryDatabase::new());
@@ -305,7 +368,10 @@ fn send_not_enough_for_change<T: OutputManagerBackend + 'static>(backend: T) {
         None,
         "".to_string(),
     )) {
-        Err(OutputManagerError::NotEnoughFunds) => assert!(true),
+        Err(OutputManagerError::InsufficientFunds { required, available }) => {
+            assert_eq!(available, MicroTari::from(value1 + value2));
+            assert_eq!(required, MicroTari::from(value1 + value2 + 1));
+        },
         _ => assert!(false),
     }
 }
@@ -324,6 +390,98 @@ fn send_not_enough_for_change_sqlite_db() {
     send_not_enough_for_change(OutputManagerSqliteDatabase::new(db_path).unwrap());
 }
 
+fn send_not_enough_for_change_fee<T: OutputManagerBackend + 'static>(backend: T) {
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let runtime = Runtime::new().unwrap();
+
+    let (mut oms, _shutdown) = setup_output_manager_service(&runtime, backend);
+
+    let fee_per_gram = MicroTari::from(20);
+    let fee_without_change = Fee::calculate(fee_per_gram, 2, 1);
+    let fee_with_change = Fee::calculate(fee_per_gram, 2, 2);
+    let key1 = PrivateKey::random(&mut rng);
+    let value1 = 500;
+    runtime
+        .block_on(oms.add_output(UnblindedOutput::new(MicroTari::from(value1), key1, None)))
+        .unwrap();
+    let key2 = PrivateKey::random(&mut rng);
+    let value2 = 800;
+    runtime
+        .block_on(oms.add_output(UnblindedOutput::new(MicroTari::from(value2), key2, None)))
+        .unwrap();
+
+    // The outputs cover the transaction without change, but not the fee of the change output for the excess of 1
+    let amount = MicroTari::from(value1 + value2 - 1) - fee_without_change;
+    match runtime.block_on(oms.prepare_transaction_to_send(amount, fee_per_gram, None, "".to_string())) {
+        Err(OutputManagerError::InsufficientFunds { required, available }) => {
+            assert_eq!(available, MicroTari::from(value1 + value2));
+            assert_eq!(required, amount + fee_with_change);
+            assert!(required > available);
+        },
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn send_not_enough_for_change_fee_memory_db() {
+    send_not_enough_for_change_fee(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+fn send_not_enough_for_change_fee_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let db_tempdir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = db_tempdir.path().to_str().unwrap().to_string();
+    let db_path = format!("{}/{}", db_folder, db_name);
+    send_not_enough_for_change_fee(OutputManagerSqliteDatabase::new(db_path).unwrap());
+}
+
+fn send_extra_input_to_cover_fee<T: OutputManagerBackend + 'static>(backend: T) {
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let runtime = Runtime::new().unwrap();
+
+    let (mut oms, _shutdown) = setup_output_manager_service(&runtime, backend);
+
+    let fee_per_gram = MicroTari::from(20);
+    let fee_without_change = Fee::calculate(fee_per_gram, 2, 1);
+    let values = vec![500, 800, 1000];
+    for value in values.iter() {
+        runtime
+            .block_on(oms.add_output(UnblindedOutput::new(
+                MicroTari::from(*value),
+                PrivateKey::random(&mut rng),
+                None,
+            )))
+            .unwrap();
+    }
+
+    // The two smallest outputs cover the amount, but not the fee that spending them incurs, so a third input is needed
+    let amount = MicroTari::from(values[0] + values[1] + 1) - fee_without_change;
+    let stp = runtime
+        .block_on(oms.prepare_transaction_to_send(amount, fee_per_gram, None, "".to_string()))
+        .unwrap();
+
+    let sender_tx_id = stp.get_tx_id().unwrap();
+    let pending_txs = runtime.block_on(oms.get_pending_transactions()).unwrap();
+    assert_eq!(pending_txs.get(&sender_tx_id).unwrap().outputs_to_be_spent.len(), 3);
+}
+
+#[test]
+fn send_extra_input_to_cover_fee_memory_db() {
+    send_extra_input_to_cover_fee(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+fn send_extra_input_to_cover_fee_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let db_tempdir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = db_tempdir.path().to_str().unwrap().to_string();
+    let db_path = format!("{}/{}", db_folder, db_name);
+    send_extra_input_to_cover_fee(OutputManagerSqliteDatabase::new(db_path).unwrap());
+}
+
 fn receiving_and_confirmation<T: OutputManagerBackend + 'static>(backend: T) {
     let factories = CryptoFactories::default();
 
@@ -503,6 +661,13 @@ fn test_get_balance<T: OutputManagerBackend + 'static>(backend: T) {
     assert_eq!(output_val, balance.available_balance);
     assert_eq!(recv_value + change_val, balance.pending_incoming_balance);
     assert_eq!(output_val, balance.pending_outgoing_balance);
+
+    let confirmed_balance = runtime.block_on(oms.get_confirmed_balance()).unwrap();
+
+    assert_eq!(output_val, confirmed_balance.available_balance);
+    assert_eq!(MicroTari::from(0), confirmed_balance.pending_incoming_balance);
+    assert_eq!(output_val, confirmed_balance.pending_outgoing_balance);
+    assert_eq!(balance.frozen_balance, confirmed_balance.frozen_balance);
 }
 
 #[test]
@@ -21,22 +21,40 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::support::utils::{make_input, random_string};
-use chrono::{Duration as ChronoDuration, Utc};
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
 use rand::RngCore;
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tari_crypto::keys::SecretKey;
 use tari_transactions::{
+    fee::Fee,
     tari_amount::MicroTari,
-    transaction::OutputFeatures,
-    types::{CryptoFactories, PrivateKey},
+    transaction::{OutputFeatures, UnblindedOutput},
+    types::{BlindingFactor, CryptoFactories, PrivateKey},
 };
 use tari_wallet::output_manager_service::{
+    error::OutputManagerStorageError,
     service::Balance,
     storage::{
-        database::{KeyManagerState, OutputManagerBackend, OutputManagerDatabase, PendingTransactionOutputs},
+        database::{
+            DbKey,
+            DbValue,
+            KeyManagerState,
+            OutputBackup,
+            OutputManagerBackend,
+            OutputManagerDatabase,
+            PendingTransactionOutputs,
+            WriteOperation,
+        },
         memory_db::OutputManagerMemoryDatabase,
         sqlite_db::OutputManagerSqliteDatabase,
     },
+    TxId,
 };
 use tempdir::TempDir;
 
@@ -120,7 +138,9 @@ pub fn test_db_backend<T: OutputManagerBackend>(backend: T) {
     assert_eq!(balance, Balance {
         available_balance,
         pending_incoming_balance,
-        pending_outgoing_balance
+        pending_outgoing_balance,
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
     });
 
     db.confirm_pending_transaction_outputs(pending_txs[0].tx_id).unwrap();
@@ -144,7 +164,9 @@ pub fn test_db_backend<T: OutputManagerBackend>(backend: T) {
     assert_eq!(balance, Balance {
         available_balance,
         pending_incoming_balance,
-        pending_outgoing_balance
+        pending_outgoing_balance,
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
     });
 
     let spent_outputs = db.fetch_spent_outputs().unwrap();
@@ -176,7 +198,9 @@ pub fn test_db_backend<T: OutputManagerBackend>(backend: T) {
     assert_eq!(balance, Balance {
         available_balance,
         pending_incoming_balance,
-        pending_outgoing_balance
+        pending_outgoing_balance,
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
     });
 
     let (_ti, uo_incoming) = make_input(
@@ -198,7 +222,9 @@ pub fn test_db_backend<T: OutputManagerBackend>(backend: T) {
     assert_eq!(balance, Balance {
         available_balance,
         pending_incoming_balance,
-        pending_outgoing_balance
+        pending_outgoing_balance,
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
     });
 
     db.cancel_pending_transaction_outputs(pending_txs[1].tx_id).unwrap();
@@ -223,7 +249,9 @@ pub fn test_db_backend<T: OutputManagerBackend>(backend: T) {
     assert_eq!(balance, Balance {
         available_balance,
         pending_incoming_balance,
-        pending_outgoing_balance
+        pending_outgoing_balance,
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
     });
 
     let remaining_p_tx = db.fetch_all_pending_transaction_outputs().unwrap();
@@ -310,3 +338,1185 @@ pub fn test_key_manager_crud_sqlite_db() {
     let db_folder = temp_dir.path().to_str().unwrap().to_string();
     test_key_manager_crud(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
 }
+
+pub fn test_decrement_key_index<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let mut rng = rand::OsRng::new().unwrap();
+
+    assert!(db.decrement_key_index().is_err());
+
+    db.set_key_manager_state(KeyManagerState {
+        master_seed: PrivateKey::random(&mut rng),
+        branch_seed: "blah".to_string(),
+        primary_key_index: 0,
+    })
+    .unwrap();
+    match db.decrement_key_index() {
+        Err(OutputManagerStorageError::KeyIndexUnderflow) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    db.increment_key_index().unwrap();
+    db.increment_key_index().unwrap();
+    db.decrement_key_index().unwrap();
+
+    let state = db.get_key_manager_state().unwrap().unwrap();
+    assert_eq!(state.primary_key_index, 1);
+}
+
+#[test]
+pub fn test_decrement_key_index_memory_db() {
+    test_decrement_key_index(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_decrement_key_index_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_decrement_key_index(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_key_index_branches<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let mut rng = rand::OsRng::new().unwrap();
+
+    assert!(db.increment_key_index_for_branch("change").is_err());
+
+    db.set_key_manager_state(KeyManagerState {
+        master_seed: PrivateKey::random(&mut rng),
+        branch_seed: "default".to_string(),
+        primary_key_index: 0,
+    })
+    .unwrap();
+
+    db.increment_key_index_for_branch("change").unwrap();
+    db.increment_key_index_for_branch("change").unwrap();
+    db.increment_key_index_for_branch("receive").unwrap();
+    db.increment_key_index().unwrap();
+    db.increment_key_index().unwrap();
+    db.increment_key_index().unwrap();
+
+    assert_eq!(db.get_key_index_for_branch("change").unwrap(), 2);
+    assert_eq!(db.get_key_index_for_branch("receive").unwrap(), 1);
+    assert_eq!(db.get_key_index_for_branch("unused").unwrap(), 0);
+    // The default branch is the one used by the single-branch methods
+    db.increment_key_index_for_branch("default").unwrap();
+    assert_eq!(db.get_key_index_for_branch("default").unwrap(), 4);
+    assert_eq!(db.get_key_manager_state().unwrap().unwrap().primary_key_index, 4);
+}
+
+#[test]
+pub fn test_key_index_branches_memory_db() {
+    test_key_index_branches(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_key_index_branches_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_key_index_branches(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_pending_transactions_older_than<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let now = Utc::now().naive_utc();
+
+    for (tx_id, timestamp) in [(1u64, now), (2, now - ChronoDuration::hours(2))].iter() {
+        db.add_pending_transaction_outputs(PendingTransactionOutputs {
+            tx_id: *tx_id,
+            outputs_to_be_spent: vec![],
+            outputs_to_be_received: vec![],
+            timestamp: *timestamp,
+        })
+        .unwrap();
+    }
+
+    let old = db
+        .fetch_pending_transactions_older_than(Duration::from_secs(60 * 60))
+        .unwrap();
+    assert_eq!(old.len(), 1);
+    assert_eq!(old[0].tx_id, 2);
+    // Nothing is cancelled
+    assert_eq!(db.fetch_all_pending_transaction_outputs().unwrap().len(), 2);
+}
+
+#[test]
+pub fn test_pending_transactions_older_than_memory_db() {
+    test_pending_transactions_older_than(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_pending_transactions_older_than_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_pending_transactions_older_than(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_pending_transaction_ages<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+
+    assert_eq!(db.fetch_pending_transaction_ages_at(now).unwrap(), vec![]);
+
+    let ages = vec![(1u64, 600u64), (2, 3600), (3, 0)];
+    for (tx_id, age) in ages.iter() {
+        db.add_pending_transaction_outputs(PendingTransactionOutputs {
+            tx_id: *tx_id,
+            outputs_to_be_spent: vec![],
+            outputs_to_be_received: vec![],
+            timestamp: now - ChronoDuration::seconds(*age as i64),
+        })
+        .unwrap();
+    }
+
+    assert_eq!(db.fetch_pending_transaction_ages_at(now).unwrap(), vec![
+        (2, Duration::from_secs(3600)),
+        (1, Duration::from_secs(600)),
+        (3, Duration::from_secs(0)),
+    ]);
+
+    // A clock that is behind the record timestamps reports zero ages rather than failing
+    let earlier = now - ChronoDuration::seconds(7200);
+    assert!(db
+        .fetch_pending_transaction_ages_at(earlier)
+        .unwrap()
+        .iter()
+        .all(|(_, age)| *age == Duration::from_secs(0)));
+
+    let current_ages = db.fetch_pending_transaction_ages().unwrap();
+    assert_eq!(current_ages.len(), 3);
+    assert_eq!(current_ages[0].0, 2);
+    assert!(current_ages[0].1 >= Duration::from_secs(3600));
+}
+
+#[test]
+pub fn test_pending_transaction_ages_memory_db() {
+    test_pending_transaction_ages(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_pending_transaction_ages_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_pending_transaction_ages(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_largest_spendable_output<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let fee_per_gram = MicroTari::from(20);
+    assert_eq!(db.largest_spendable_output(10, fee_per_gram).unwrap(), None);
+
+    let mut outputs = Vec::new();
+    for value in [300, 1500, 200, 800].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    // The largest output has not matured at the current height, so it is not spendable
+    let (_ti, mut immature) = make_input(&mut rng.clone(), MicroTari::from(5000), &factories.commitment);
+    immature.features = OutputFeatures::with_maturity(11);
+    db.add_unspent_output(immature.clone()).unwrap();
+
+    let largest = db.largest_spendable_output(10, fee_per_gram).unwrap().unwrap();
+    assert_eq!(largest.value, MicroTari::from(1500));
+    assert_eq!(largest.spending_key, outputs[1].spending_key);
+
+    let largest = db.largest_spendable_output(11, fee_per_gram).unwrap().unwrap();
+    assert_eq!(largest.spending_key, immature.spending_key);
+
+    // At a high enough fee per gram, none of the mature outputs cover the fee of spending them
+    assert_eq!(db.largest_spendable_output(10, MicroTari::from(1500)).unwrap(), None);
+}
+
+#[test]
+pub fn test_largest_spendable_output_memory_db() {
+    test_largest_spendable_output(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_largest_spendable_output_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_largest_spendable_output(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_utxo_value_histogram<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let boundaries = [MicroTari::from(1000), MicroTari::from(100), MicroTari::from(10_000)];
+    assert_eq!(db.utxo_value_histogram(&boundaries).unwrap(), vec![
+        (MicroTari::from(100), 0),
+        (MicroTari::from(1000), 0),
+        (MicroTari::from(10_000), 0),
+    ]);
+
+    for value in [50, 100, 250, 999, 1000, 5000, 10_000, 250_000].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo).unwrap();
+    }
+
+    assert_eq!(db.utxo_value_histogram(&boundaries).unwrap(), vec![
+        (MicroTari::from(100), 3),
+        (MicroTari::from(1000), 2),
+        (MicroTari::from(10_000), 2),
+    ]);
+    assert_eq!(db.utxo_value_histogram(&[MicroTari::from(0)]).unwrap(), vec![(
+        MicroTari::from(0),
+        8
+    )]);
+    assert!(db.utxo_value_histogram(&[]).unwrap().is_empty());
+}
+
+#[test]
+pub fn test_utxo_value_histogram_memory_db() {
+    test_utxo_value_histogram(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_utxo_value_histogram_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_utxo_value_histogram(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_fetch_unspent_outputs_in_range<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [1000, 100, 500].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+
+    let in_range = db
+        .fetch_unspent_outputs_in_range(MicroTari::from(200), MicroTari::from(800))
+        .unwrap();
+    assert_eq!(in_range.len(), 1);
+    assert_eq!(in_range[0].value, MicroTari::from(500));
+    assert_eq!(in_range[0].spending_key, outputs[2].spending_key);
+
+    // The range is inclusive and the outputs are sorted by value
+    let in_range = db
+        .fetch_unspent_outputs_in_range(MicroTari::from(100), MicroTari::from(1000))
+        .unwrap();
+    let values = in_range.iter().map(|o| o.value).collect::<Vec<_>>();
+    assert_eq!(values, vec![
+        MicroTari::from(100),
+        MicroTari::from(500),
+        MicroTari::from(1000)
+    ]);
+    assert!(db
+        .fetch_unspent_outputs_in_range(MicroTari::from(800), MicroTari::from(200))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+pub fn test_fetch_unspent_outputs_in_range_memory_db() {
+    test_fetch_unspent_outputs_in_range(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_fetch_unspent_outputs_in_range_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_fetch_unspent_outputs_in_range(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_balance_at_height<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let (_ti, mature) = make_input(&mut rng.clone(), MicroTari::from(1000), &factories.commitment);
+    db.add_unspent_output(mature.clone()).unwrap();
+    let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(5000), &factories.commitment);
+    let immature = UnblindedOutput::new(uo.value, uo.spending_key, Some(OutputFeatures::with_maturity(50)));
+    db.add_unspent_output(immature).unwrap();
+
+    let balance = db.get_balance_at_height(10).unwrap();
+    assert_eq!(balance.available_balance, MicroTari::from(1000));
+    assert_eq!(balance.time_locked_balance, MicroTari::from(5000));
+    let spendable = db.fetch_spendable_outputs(10).unwrap();
+    assert_eq!(spendable.len(), 1);
+    assert_eq!(spendable[0].spending_key, mature.spending_key);
+
+    let balance = db.get_balance_at_height(50).unwrap();
+    assert_eq!(balance.available_balance, MicroTari::from(6000));
+    assert_eq!(balance.time_locked_balance, MicroTari::from(0));
+    assert_eq!(db.fetch_spendable_outputs(50).unwrap().len(), 2);
+}
+
+#[test]
+pub fn test_balance_at_height_memory_db() {
+    test_balance_at_height(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_balance_at_height_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_balance_at_height(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+pub fn test_max_sendable<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+    let fee_per_gram = MicroTari::from(20);
+
+    assert_eq!(db.max_sendable(fee_per_gram, 10).unwrap(), MicroTari::from(0));
+
+    for value in [1000, 2000, 5000].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo).unwrap();
+    }
+    // Neither frozen nor dust outputs are spendable
+    let (_ti, frozen) = make_input(&mut rng.clone(), MicroTari::from(3000), &factories.commitment);
+    db.add_unspent_output(frozen.clone()).unwrap();
+    db.freeze_output(&frozen.spending_key).unwrap();
+    let (_ti, dust) = make_input(&mut rng.clone(), MicroTari::from(15), &factories.commitment);
+    db.add_unspent_output(dust).unwrap();
+    // Outputs that have not matured at the current height are not spendable either
+    let (_ti, mut immature) = make_input(&mut rng.clone(), MicroTari::from(4000), &factories.commitment);
+    immature.features = OutputFeatures::with_maturity(11);
+    db.add_unspent_output(immature).unwrap();
+
+    let max = db.max_sendable(fee_per_gram, 10).unwrap();
+    assert_eq!(max + Fee::calculate(fee_per_gram, 3, 1), MicroTari::from(8000));
+
+    let max = db.max_sendable(fee_per_gram, 11).unwrap();
+    assert_eq!(max + Fee::calculate(fee_per_gram, 4, 1), MicroTari::from(12000));
+}
+
+#[test]
+pub fn test_max_sendable_memory_db() {
+    test_max_sendable(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_max_sendable_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_max_sendable(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+pub fn test_fetch_spendable_outputs_above<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    for value in [1, 5000, 2].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo).unwrap();
+    }
+
+    let outputs = db.fetch_spendable_outputs_above(MicroTari::from(10)).unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].value, MicroTari::from(5000));
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap().len(), 3);
+}
+
+#[test]
+pub fn test_fetch_spendable_outputs_above_memory_db() {
+    test_fetch_spendable_outputs_above(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_fetch_spendable_outputs_above_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_fetch_spendable_outputs_above(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_confirm_received_outputs<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let (_ti, spent) = make_input(&mut rng.clone(), MicroTari::from(1000), &factories.commitment);
+    db.add_unspent_output(spent.clone()).unwrap();
+    let (_ti, received) = make_input(&mut rng.clone(), MicroTari::from(400), &factories.commitment);
+    db.encumber_outputs(1, &vec![spent.clone()], Some(received.clone()))
+        .unwrap();
+
+    db.confirm_received_outputs(1).unwrap();
+
+    let unspent = db.fetch_sorted_unspent_outputs().unwrap();
+    assert_eq!(unspent.len(), 1);
+    assert_eq!(unspent[0].spending_key, received.spending_key);
+    let pending = db.fetch_pending_transaction_outputs(1).unwrap();
+    assert_eq!(pending.outputs_to_be_spent, vec![spent]);
+    assert!(pending.outputs_to_be_received.is_empty());
+
+    // Confirming the rest of the transaction spends the encumbered output
+    db.confirm_pending_transaction_outputs(1).unwrap();
+    assert_eq!(db.fetch_spent_outputs().unwrap().len(), 1);
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap().len(), 1);
+
+    match db.confirm_received_outputs(2) {
+        Err(OutputManagerStorageError::ValueNotFound(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+pub fn test_confirm_received_outputs_memory_db() {
+    test_confirm_received_outputs(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_confirm_received_outputs_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_confirm_received_outputs(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_add_unspent_outputs<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let outputs = [1000, 2000, 3000]
+        .iter()
+        .map(|v| make_input(&mut rng.clone(), MicroTari::from(*v), &factories.commitment).1)
+        .collect::<Vec<_>>();
+    db.add_unspent_outputs(outputs.clone()).unwrap();
+    assert_eq!(db.get_balance().unwrap().available_balance, MicroTari::from(6000));
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap().len(), 3);
+
+    // Duplicates within the batch or with stored outputs are rejected without writing any of the outputs
+    let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(500), &factories.commitment);
+    match db.add_unspent_outputs(vec![uo.clone(), uo.clone()]) {
+        Err(OutputManagerStorageError::DuplicateOutput) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match db.add_unspent_outputs(vec![uo, outputs[0].clone()]) {
+        Err(OutputManagerStorageError::DuplicateOutput) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(db.get_balance().unwrap().available_balance, MicroTari::from(6000));
+}
+
+#[test]
+pub fn test_add_unspent_outputs_memory_db() {
+    test_add_unspent_outputs(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_add_unspent_outputs_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_add_unspent_outputs(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_invalidate_output<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let (_ti, uo1) = make_input(&mut rng.clone(), MicroTari::from(1000), &factories.commitment);
+    db.add_unspent_output(uo1.clone()).unwrap();
+    let (_ti, uo2) = make_input(&mut rng.clone(), MicroTari::from(2000), &factories.commitment);
+    db.add_unspent_output(uo2.clone()).unwrap();
+    assert_eq!(db.get_balance().unwrap().available_balance, MicroTari::from(3000));
+
+    db.invalidate_output(&uo2.spending_key).unwrap();
+
+    assert_eq!(db.get_balance().unwrap().available_balance, MicroTari::from(1000));
+    let unspent = db.fetch_sorted_unspent_outputs().unwrap();
+    assert_eq!(unspent.len(), 1);
+    assert_eq!(unspent[0].spending_key, uo1.spending_key);
+    let invalidated = db.fetch_invalidated_outputs().unwrap();
+    assert_eq!(invalidated.len(), 1);
+    assert_eq!(invalidated[0].spending_key, uo2.spending_key);
+    // An invalidated output can't be selected, frozen or invalidated again
+    assert!(db.encumber_outputs(1, &vec![uo2.clone()], None).is_err());
+    assert!(db.freeze_output(&uo2.spending_key).is_err());
+    assert!(db.invalidate_output(&uo2.spending_key).is_err());
+    assert_eq!(db.fetch_all_spending_keys().unwrap().len(), 2);
+}
+
+#[test]
+pub fn test_invalidate_output_memory_db() {
+    test_invalidate_output(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_invalidate_output_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_invalidate_output(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+pub fn test_export_import_outputs<T: OutputManagerBackend>(source: T, destination: T) {
+    let mut db = OutputManagerDatabase::new(source);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    db.set_key_manager_state(KeyManagerState {
+        master_seed: PrivateKey::random(&mut rng),
+        branch_seed: "blah".to_string(),
+        primary_key_index: 3,
+    })
+    .unwrap();
+    db.increment_key_index_for_branch("change").unwrap();
+    db.increment_key_index_for_branch("change").unwrap();
+    let mut outputs = Vec::new();
+    for value in [1000, 2000, 3000, 4000, 5000].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    db.freeze_output(&outputs[1].spending_key).unwrap();
+    db.invalidate_output(&outputs[4].spending_key).unwrap();
+    let (_ti, change) = make_input(&mut rng.clone(), MicroTari::from(500), &factories.commitment);
+    db.encumber_outputs(1, &vec![outputs[2].clone()], Some(change)).unwrap();
+    db.encumber_outputs(2, &vec![outputs[3].clone()], None).unwrap();
+    db.confirm_pending_transaction_outputs(2).unwrap();
+
+    let backup = db.export_outputs().unwrap();
+    assert_eq!(backup.unspent_outputs.len(), 1);
+    assert_eq!(backup.spent_outputs.len(), 1);
+    assert_eq!(backup.frozen_outputs.len(), 1);
+    assert_eq!(backup.invalidated_outputs.len(), 1);
+    assert_eq!(backup.pending_transactions.len(), 1);
+    assert_eq!(backup.branch_key_indices.get("change"), Some(&2));
+    let backup: OutputBackup = serde_json::from_str(&serde_json::to_string(&backup).unwrap()).unwrap();
+
+    // Nothing is imported from a backup that cannot be imported in full, so the import can be retried
+    let mut imported = OutputManagerDatabase::new(destination);
+    let mut corrupt_backup = backup.clone();
+    corrupt_backup.spent_outputs.push(outputs[0].clone());
+    assert_eq!(
+        imported.import_outputs(corrupt_backup),
+        Err(OutputManagerStorageError::DuplicateOutput)
+    );
+    assert!(imported.fetch_all_spending_keys().unwrap().is_empty());
+    assert!(imported.get_key_manager_state().unwrap().is_none());
+
+    // The import is a single write, so it can be part of a batch
+    imported.begin_batch();
+    imported.import_outputs(backup.clone()).unwrap();
+    imported.commit_batch().unwrap();
+    assert_eq!(imported.get_balance().unwrap(), db.get_balance().unwrap());
+    assert_eq!(
+        imported.get_key_manager_state().unwrap(),
+        db.get_key_manager_state().unwrap()
+    );
+    assert_eq!(imported.get_key_index_for_branch("change").unwrap(), 2);
+    assert_eq!(
+        imported.fetch_sorted_unspent_outputs().unwrap(),
+        db.fetch_sorted_unspent_outputs().unwrap()
+    );
+    assert_eq!(
+        imported.fetch_spent_outputs().unwrap(),
+        db.fetch_spent_outputs().unwrap()
+    );
+    assert_eq!(imported.fetch_frozen_outputs().unwrap(), vec![outputs[1].clone()]);
+    assert_eq!(imported.fetch_invalidated_outputs().unwrap(), vec![outputs[4].clone()]);
+    assert_eq!(
+        imported
+            .fetch_pending_transaction_outputs(1)
+            .unwrap()
+            .outputs_to_be_spent,
+        vec![outputs[2].clone()]
+    );
+
+    // A backup can't be merged into a database that already holds outputs
+    match imported.import_outputs(backup) {
+        Err(OutputManagerStorageError::StoreNotEmpty) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+pub fn test_export_import_outputs_memory_db() {
+    test_export_import_outputs(OutputManagerMemoryDatabase::new(), OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_export_import_outputs_sqlite_db() {
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    let source_name = format!("{}.sqlite3", random_string(8).as_str());
+    let destination_name = format!("{}.sqlite3", random_string(8).as_str());
+    test_export_import_outputs(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, source_name).to_string()).unwrap(),
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, destination_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_encumber_specific_outputs<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200, 300, 400].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+
+    let spending_keys = vec![outputs[0].spending_key.clone(), outputs[2].spending_key.clone()];
+    db.encumber_specific_outputs(1, &spending_keys).unwrap();
+
+    let unspent_outputs = db.fetch_sorted_unspent_outputs().unwrap();
+    assert_eq!(unspent_outputs.len(), 2);
+    assert_eq!(unspent_outputs[0].spending_key, outputs[1].spending_key);
+    assert_eq!(unspent_outputs[1].spending_key, outputs[3].spending_key);
+
+    let pending_tx = db.fetch_pending_transaction_outputs(1).unwrap();
+    assert_eq!(pending_tx.outputs_to_be_spent.len(), 2);
+    assert!(spending_keys
+        .iter()
+        .all(|key| pending_tx.outputs_to_be_spent.iter().any(|o| &o.spending_key == key)));
+    assert!(pending_tx.outputs_to_be_received.is_empty());
+
+    match db.encumber_specific_outputs(2, &[outputs[1].spending_key.clone(), outputs[0].spending_key.clone()]) {
+        Err(OutputManagerStorageError::OutputAlreadyEncumbered) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let unknown_key = PrivateKey::random(&mut rng);
+    match db.encumber_specific_outputs(2, &[outputs[1].spending_key.clone(), unknown_key]) {
+        Err(OutputManagerStorageError::ValuesNotFound) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Failed calls do not encumber any outputs
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap().len(), 2);
+    assert_eq!(db.fetch_all_pending_transaction_outputs().unwrap().len(), 1);
+}
+
+#[test]
+pub fn test_encumber_specific_outputs_memory_db() {
+    test_encumber_specific_outputs(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_encumber_specific_outputs_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_encumber_specific_outputs(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_encumbered_amount<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200, 300, 400].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    let (_ti, change) = make_input(&mut rng.clone(), MicroTari::from(50), &factories.commitment);
+
+    db.encumber_outputs(1, &vec![outputs[1].clone(), outputs[3].clone()], Some(change))
+        .unwrap();
+    db.encumber_specific_outputs(2, &[outputs[0].spending_key.clone()])
+        .unwrap();
+
+    // The change output is to be received and is not encumbered
+    assert_eq!(db.encumbered_amount(1).unwrap(), MicroTari::from(600));
+    assert_eq!(db.encumbered_amount(2).unwrap(), MicroTari::from(100));
+    match db.encumbered_amount(3) {
+        Err(OutputManagerStorageError::PendingTransactionNotFound) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+pub fn test_encumbered_amount_memory_db() {
+    test_encumbered_amount(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_encumbered_amount_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_encumbered_amount(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+pub fn test_fetch_all_spending_keys<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200, 300, 400].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    let (_ti, change1) = make_input(&mut rng.clone(), MicroTari::from(50), &factories.commitment);
+    let (_ti, change2) = make_input(&mut rng.clone(), MicroTari::from(60), &factories.commitment);
+
+    // outputs[0] is spent, outputs[1] is encumbered, outputs[2] is frozen and outputs[3] is unspent
+    db.encumber_outputs(1, &vec![outputs[0].clone()], Some(change1.clone()))
+        .unwrap();
+    db.confirm_pending_transaction_outputs(1).unwrap();
+    db.encumber_outputs(2, &vec![outputs[1].clone()], Some(change2.clone()))
+        .unwrap();
+    db.freeze_output(&outputs[2].spending_key).unwrap();
+    // A pending incoming output that reuses the spending key of an unspent output is only reported once
+    db.accept_incoming_pending_transaction(
+        &3,
+        &MicroTari::from(70),
+        &outputs[3].spending_key,
+        OutputFeatures::default(),
+    )
+    .unwrap();
+
+    let keys = db.fetch_all_spending_keys().unwrap();
+    let expected = outputs
+        .iter()
+        .chain([change1, change2].iter())
+        .map(|o| o.spending_key.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(keys.len(), expected.len());
+    assert!(expected.iter().all(|k| keys.contains(k)));
+}
+
+#[test]
+pub fn test_fetch_all_spending_keys_memory_db() {
+    test_fetch_all_spending_keys(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_fetch_all_spending_keys_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_fetch_all_spending_keys(
+        OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap(),
+    );
+}
+
+pub fn test_frozen_outputs<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200, 300].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+
+    db.freeze_output(&outputs[2].spending_key).unwrap();
+    match db.freeze_output(&outputs[2].spending_key) {
+        Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(_))) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Frozen outputs are not available for selection
+    let unspent_outputs = db.fetch_sorted_unspent_outputs().unwrap();
+    assert_eq!(unspent_outputs.len(), 2);
+    assert!(unspent_outputs
+        .iter()
+        .all(|o| o.spending_key != outputs[2].spending_key));
+    assert_eq!(
+        db.largest_spendable_output(0, MicroTari::from(1))
+            .unwrap()
+            .unwrap()
+            .value,
+        MicroTari::from(200)
+    );
+    match db.encumber_specific_outputs(1, &[outputs[2].spending_key.clone()]) {
+        Err(OutputManagerStorageError::ValuesNotFound) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    let frozen_outputs = db.fetch_frozen_outputs().unwrap();
+    assert_eq!(frozen_outputs.len(), 1);
+    assert_eq!(frozen_outputs[0].spending_key, outputs[2].spending_key);
+
+    // Frozen outputs are still counted in the total balance
+    let balance = db.get_balance().unwrap();
+    assert_eq!(balance, Balance {
+        available_balance: MicroTari::from(300),
+        pending_incoming_balance: MicroTari::from(0),
+        pending_outgoing_balance: MicroTari::from(0),
+        frozen_balance: MicroTari::from(300),
+        time_locked_balance: MicroTari::from(0),
+    });
+    assert_eq!(balance.available_balance + balance.frozen_balance, MicroTari::from(600));
+
+    db.unfreeze_output(&outputs[2].spending_key).unwrap();
+    match db.unfreeze_output(&outputs[2].spending_key) {
+        Err(OutputManagerStorageError::ValueNotFound(DbKey::UnspentOutput(_))) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap().len(), 3);
+    assert!(db.fetch_frozen_outputs().unwrap().is_empty());
+    let balance = db.get_balance().unwrap();
+    assert_eq!(balance.available_balance, MicroTari::from(600));
+    assert_eq!(balance.frozen_balance, MicroTari::from(0));
+}
+
+#[test]
+pub fn test_frozen_outputs_memory_db() {
+    test_frozen_outputs(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_frozen_outputs_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_frozen_outputs(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+pub fn test_plan_split<T: OutputManagerBackend>(backend: T) {
+    let mut db = OutputManagerDatabase::new(backend);
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    match db.plan_split(4, MicroTari::from(20)) {
+        Err(OutputManagerStorageError::InvalidSplit(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(1_000_000), &factories.commitment);
+    db.add_unspent_output(uo.clone()).unwrap();
+
+    let plan = db.plan_split(4, MicroTari::from(20)).unwrap();
+    assert_eq!(plan.inputs, vec![uo.clone()]);
+    assert_eq!(plan.fee, Fee::calculate(MicroTari::from(20), 1, 4));
+    assert_eq!(plan.output_amounts.len(), 4);
+    let total = plan
+        .output_amounts
+        .iter()
+        .fold(plan.fee, |acc, amount| acc + amount.clone());
+    assert_eq!(total, MicroTari::from(1_000_000));
+    let max = plan.output_amounts.iter().max().unwrap().clone();
+    let min = plan.output_amounts.iter().min().unwrap().clone();
+    assert!(u64::from(max) - u64::from(min) <= 1);
+
+    match db.plan_split(0, MicroTari::from(20)) {
+        Err(OutputManagerStorageError::InvalidSplit(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    match db.plan_split(1_000_000, MicroTari::from(20)) {
+        Err(OutputManagerStorageError::InvalidSplit(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+
+    // Planning does not touch the stored outputs
+    assert_eq!(db.fetch_sorted_unspent_outputs().unwrap(), vec![uo]);
+    assert!(db.fetch_all_pending_transaction_outputs().unwrap().is_empty());
+}
+
+#[test]
+pub fn test_plan_split_memory_db() {
+    test_plan_split(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_plan_split_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_plan_split(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
+
+#[test]
+pub fn test_find_commitment_collisions_memory_db() {
+    let mut db = OutputManagerDatabase::new(OutputManagerMemoryDatabase::new());
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    assert!(db.find_commitment_collisions().unwrap().is_empty());
+
+    // The memory backend does not check pending transaction outputs for duplicates, which allows an output sharing
+    // the commitment of an unspent output to be injected
+    db.add_pending_transaction_outputs(PendingTransactionOutputs {
+        tx_id: 1,
+        outputs_to_be_spent: Vec::new(),
+        outputs_to_be_received: vec![outputs[1].clone()],
+        timestamp: Utc::now().naive_utc(),
+    })
+    .unwrap();
+
+    let expected = outputs[1]
+        .as_transaction_input(&factories.commitment, OutputFeatures::default())
+        .commitment()
+        .clone();
+    assert_eq!(db.find_commitment_collisions().unwrap(), vec![expected]);
+}
+
+#[test]
+pub fn test_validate_balance_memory_db() {
+    let mut db = OutputManagerDatabase::new(OutputManagerMemoryDatabase::new());
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [100, 200].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    db.encumber_outputs(1, &vec![outputs[0].clone()], None).unwrap();
+    assert_eq!(db.validate_balance().unwrap(), db.get_balance().unwrap());
+
+    // Inject a pending transaction that encumbers an output that is still unspent. The pending outgoing balance now
+    // exceeds the funds held by the wallet.
+    db.add_pending_transaction_outputs(PendingTransactionOutputs {
+        tx_id: 2,
+        outputs_to_be_spent: vec![outputs[1].clone()],
+        outputs_to_be_received: Vec::new(),
+        timestamp: Utc::now().naive_utc(),
+    })
+    .unwrap();
+    let balance = db.get_balance().unwrap();
+    assert!(balance.pending_outgoing_balance > balance.available_balance + balance.pending_incoming_balance);
+    match db.validate_balance() {
+        Err(OutputManagerStorageError::InconsistentBalance(_)) => {},
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+/// A backend that counts the calls to `write` and `write_many` before delegating to the inner backend
+struct RecordingBackend<T> {
+    inner: T,
+    write_calls: Arc<AtomicUsize>,
+    write_many_calls: Arc<AtomicUsize>,
+}
+
+impl<T: OutputManagerBackend> OutputManagerBackend for RecordingBackend<T> {
+    fn fetch(&self, key: &DbKey) -> Result<Option<DbValue>, OutputManagerStorageError> {
+        self.inner.fetch(key)
+    }
+
+    fn write(&mut self, op: WriteOperation) -> Result<Option<DbValue>, OutputManagerStorageError> {
+        self.write_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.write(op)
+    }
+
+    fn write_many(&mut self, ops: Vec<WriteOperation>) -> Result<(), OutputManagerStorageError> {
+        self.write_many_calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.write_many(ops)
+    }
+
+    fn confirm_transaction(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.inner.confirm_transaction(tx_id)
+    }
+
+    fn confirm_received_outputs(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.inner.confirm_received_outputs(tx_id)
+    }
+
+    fn encumber_outputs(
+        &mut self,
+        tx_id: TxId,
+        outputs_to_send: &Vec<UnblindedOutput>,
+        change_output: Option<UnblindedOutput>,
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        self.inner.encumber_outputs(tx_id, outputs_to_send, change_output)
+    }
+
+    fn cancel_pending_transaction(&mut self, tx_id: TxId) -> Result<(), OutputManagerStorageError> {
+        self.inner.cancel_pending_transaction(tx_id)
+    }
+
+    fn timeout_pending_transactions(&mut self, period: Duration) -> Result<(), OutputManagerStorageError> {
+        self.inner.timeout_pending_transactions(period)
+    }
+
+    fn set_output_frozen(
+        &mut self,
+        spending_key: &BlindingFactor,
+        frozen: bool,
+    ) -> Result<(), OutputManagerStorageError>
+    {
+        self.inner.set_output_frozen(spending_key, frozen)
+    }
+
+    fn invalidate_output(&mut self, spending_key: &BlindingFactor) -> Result<(), OutputManagerStorageError> {
+        self.inner.invalidate_output(spending_key)
+    }
+
+    fn increment_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        self.inner.increment_key_index()
+    }
+
+    fn increment_key_index_for_branch(&mut self, branch: &str) -> Result<(), OutputManagerStorageError> {
+        self.inner.increment_key_index_for_branch(branch)
+    }
+
+    fn decrement_key_index(&mut self) -> Result<(), OutputManagerStorageError> {
+        self.inner.decrement_key_index()
+    }
+}
+
+pub fn test_batched_writes<T: OutputManagerBackend>(backend: T) {
+    let write_calls = Arc::new(AtomicUsize::new(0));
+    let write_many_calls = Arc::new(AtomicUsize::new(0));
+    let mut db = OutputManagerDatabase::new(RecordingBackend {
+        inner: backend,
+        write_calls: write_calls.clone(),
+        write_many_calls: write_many_calls.clone(),
+    });
+    let factories = CryptoFactories::default();
+    let mut rng = rand::OsRng::new().unwrap();
+
+    let mut outputs = Vec::new();
+    for value in [1000, 2000].iter() {
+        let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(*value), &factories.commitment);
+        db.add_unspent_output(uo.clone()).unwrap();
+        outputs.push(uo);
+    }
+    assert_eq!(write_calls.load(Ordering::SeqCst), 2);
+
+    db.begin_batch();
+    assert!(db.is_batching());
+    let incoming_spending_key = PrivateKey::random(&mut rng);
+    db.accept_incoming_pending_transaction(
+        &1,
+        &MicroTari::from(500),
+        &incoming_spending_key,
+        OutputFeatures::default(),
+    )
+    .unwrap();
+    db.encumber_outputs(2, &vec![outputs[0].clone()], None).unwrap();
+
+    // Nothing is written to the backend until the batch is committed
+    assert_eq!(write_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(write_many_calls.load(Ordering::SeqCst), 0);
+    assert!(db.fetch_all_pending_transaction_outputs().unwrap().is_empty());
+
+    db.commit_batch().unwrap();
+    assert!(!db.is_batching());
+    assert_eq!(write_calls.load(Ordering::SeqCst), 2);
+    assert_eq!(write_many_calls.load(Ordering::SeqCst), 1);
+
+    let unspent_outputs = db.fetch_sorted_unspent_outputs().unwrap();
+    assert_eq!(unspent_outputs.len(), 1);
+    assert_eq!(unspent_outputs[0].spending_key, outputs[1].spending_key);
+    assert_eq!(db.get_balance().unwrap(), Balance {
+        available_balance: MicroTari::from(2000),
+        pending_incoming_balance: MicroTari::from(500),
+        pending_outgoing_balance: MicroTari::from(1000),
+        frozen_balance: MicroTari::from(0),
+        time_locked_balance: MicroTari::from(0),
+    });
+
+    // Committing without a batch in progress does not touch the backend
+    db.commit_batch().unwrap();
+    assert_eq!(write_many_calls.load(Ordering::SeqCst), 1);
+
+    // Encumbering is validated against the writes staged in the batch, so an output cannot be encumbered twice
+    db.begin_batch();
+    db.encumber_outputs(3, &vec![outputs[1].clone()], None).unwrap();
+    assert_eq!(
+        db.encumber_outputs(4, &vec![outputs[1].clone()], None),
+        Err(OutputManagerStorageError::ValuesNotFound)
+    );
+    // Operations that cannot be buffered are rejected while the batch is in progress
+    assert_eq!(
+        db.freeze_output(&outputs[1].spending_key),
+        Err(OutputManagerStorageError::BatchInProgress)
+    );
+    assert_eq!(
+        db.cancel_pending_transaction_outputs(2),
+        Err(OutputManagerStorageError::BatchInProgress)
+    );
+    assert_eq!(
+        db.increment_key_index(),
+        Err(OutputManagerStorageError::BatchInProgress)
+    );
+    db.commit_batch().unwrap();
+    assert!(db.fetch_sorted_unspent_outputs().unwrap().is_empty());
+    assert_eq!(
+        db.fetch_pending_transaction_outputs(3).unwrap().outputs_to_be_spent,
+        vec![outputs[1].clone()]
+    );
+    assert!(db.fetch_pending_transaction_outputs(4).is_err());
+
+    // A batch is committed atomically, so none of its writes are applied if one of them fails
+    let (_ti, uo) = make_input(&mut rng.clone(), MicroTari::from(3000), &factories.commitment);
+    db.begin_batch();
+    db.add_pending_transaction_outputs(PendingTransactionOutputs {
+        tx_id: 5,
+        outputs_to_be_spent: Vec::new(),
+        outputs_to_be_received: Vec::new(),
+        timestamp: Utc::now().naive_utc(),
+    })
+    .unwrap();
+    db.add_unspent_output(uo.clone()).unwrap();
+    db.add_unspent_output(uo).unwrap();
+    assert_eq!(db.commit_batch(), Err(OutputManagerStorageError::DuplicateOutput));
+    assert!(!db.is_batching());
+    assert!(db.fetch_sorted_unspent_outputs().unwrap().is_empty());
+    assert!(db.fetch_pending_transaction_outputs(5).is_err());
+}
+
+#[test]
+pub fn test_batched_writes_memory_db() {
+    test_batched_writes(OutputManagerMemoryDatabase::new());
+}
+
+#[test]
+pub fn test_batched_writes_sqlite_db() {
+    let db_name = format!("{}.sqlite3", random_string(8).as_str());
+    let temp_dir = TempDir::new(random_string(8).as_str()).unwrap();
+    let db_folder = temp_dir.path().to_str().unwrap().to_string();
+    test_batched_writes(OutputManagerSqliteDatabase::new(format!("{}/{}", db_folder, db_name).to_string()).unwrap());
+}
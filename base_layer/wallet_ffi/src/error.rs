@@ -25,6 +25,7 @@ use tari_comms::{
     multiaddr,
     peer_manager::{node_id::NodeIdError, node_identity::NodeIdentityError},
 };
+use tari_transactions::tari_amount::MicroTari;
 use tari_utilities::{hex::HexError, ByteArrayError};
 use tari_wallet::{
     contacts_service::error::{ContactsServiceError, ContactsServiceStorageError},
@@ -55,184 +56,207 @@ pub enum InterfaceError {
 pub struct LibWalletError {
     pub code: i32,
     pub message: String,
+    pub category: LibWalletErrorCategory,
+}
+
+/// The broad area of the wallet in which a LibWalletError originated. FFI client applications can use the category to
+/// decide how to handle an error without matching on every individual error code. The integer values are returned by
+/// `wallet_error_category` and must remain stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibWalletErrorCategory {
+    /// An error reading from or writing to one of the wallet databases
+    Storage = 0,
+    /// An error in the comms stack or in the network addresses supplied to it
+    Network = 1,
+    /// A supplied value or requested operation was not valid
+    Validation = 2,
+    /// An error handling the node identity or its keys
+    KeyManagement = 3,
+    /// An error in the way the FFI interface was called
+    Interface = 4,
+    /// An error that has not been explicitly mapped to a category
+    Unknown = 5,
+    /// An unexpected response from, or a failure to communicate with, one of the wallet services
+    Service = 6,
+}
+
+impl LibWalletError {
+    /// Creates an error with the given code and message. The category is derived from the code, so that it always
+    /// agrees with `LibWalletErrorCategory::from_code`.
+    fn new(code: i32, message: String) -> Self {
+        Self {
+            code,
+            message,
+            category: LibWalletErrorCategory::from_code(code),
+        }
+    }
+}
+
+impl LibWalletErrorCategory {
+    /// Returns the category of the LibWalletError with the given error code. Codes that are not known, including the
+    /// catch-all code 999, map to `Unknown`.
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            1..=4 => LibWalletErrorCategory::Interface,
+            102 | 109 | 201..=203 | 205 | 207 | 501..=699 => LibWalletErrorCategory::Validation,
+            103..=108 | 204 | 208 | 209 | 401..=411 => LibWalletErrorCategory::Storage,
+            301 | 801..=899 => LibWalletErrorCategory::Network,
+            701..=799 => LibWalletErrorCategory::KeyManagement,
+            206 | 412 | 413 => LibWalletErrorCategory::Service,
+            _ => LibWalletErrorCategory::Unknown,
+        }
+    }
 }
 
 impl From<InterfaceError> for LibWalletError {
     fn from(v: InterfaceError) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", v));
         match v {
-            InterfaceError::NullError(_) => Self {
-                code: 1,
-                message: format!("{:?}", v).to_string(),
-            },
-            InterfaceError::AllocationError => Self {
-                code: 2,
-                message: format!("{:?}", v).to_string(),
-            },
-            InterfaceError::PositionInvalidError => Self {
-                code: 3,
-                message: format!("{:?}", v).to_string(),
-            },
-            InterfaceError::TokioError(_) => Self {
-                code: 4,
-                message: format!("{:?}", v).to_string(),
-            },
+            InterfaceError::NullError(_) => Self::new(1, format!("{:?}", v).to_string()),
+            InterfaceError::AllocationError => Self::new(2, format!("{:?}", v).to_string()),
+            InterfaceError::PositionInvalidError => Self::new(3, format!("{:?}", v).to_string()),
+            InterfaceError::TokioError(_) => Self::new(4, format!("{:?}", v).to_string()),
         }
     }
 }
 
 /// This implementation maps the internal WalletError to a set of LibWalletErrors. The mapping is explicitly manager
 /// here and error code 999 is a catch-all code for any errors that are not explicitly mapped
+///
+/// The Contacts Service errors are mapped to the following codes:
+/// - 401: The contact was not found
+/// - 402: A contact with the same public key already exists
+/// - 403: The storage operation is not supported
+/// - 404: A stored value could not be converted
+/// - 405: Not all of the values of a batch operation were found
+/// - 406: The requested value was not found in storage
+/// - 407: The storage returned an unexpected result
+/// - 408: A database connection could not be taken from the connection pool
+/// - 409: A database query failed
+/// - 410: The database connection could not be established
+/// - 411: The database migrations failed
+/// - 412: The Contacts Service returned an unexpected response
+/// - 413: The request could not be sent to or answered by the Contacts Service
 impl From<WalletError> for LibWalletError {
     fn from(w: WalletError) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", w));
         match w {
             // Output Manager Service Errors
-            WalletError::OutputManagerError(OutputManagerError::NotEnoughFunds) => Self {
-                code: 101,
-                message: format!("{:?}", w),
-            },
-            WalletError::OutputManagerError(OutputManagerError::IncompleteTransaction) => Self {
-                code: 102,
-                message: format!("{:?}", w),
+            WalletError::OutputManagerError(OutputManagerError::InsufficientFunds { required, available }) => {
+                Self::new(109, insufficient_funds_message(required, available))
             },
-            WalletError::OutputManagerError(OutputManagerError::DuplicateOutput) => Self {
-                code: 103,
-                message: format!("{:?}", w),
+            WalletError::OutputManagerError(OutputManagerError::IncompleteTransaction) => {
+                Self::new(102, format!("{:?}", w))
             },
+            WalletError::OutputManagerError(OutputManagerError::DuplicateOutput) => Self::new(103, format!("{:?}", w)),
             WalletError::OutputManagerError(OutputManagerError::OutputManagerStorageError(
                 OutputManagerStorageError::ValuesNotFound,
-            )) => Self {
-                code: 104,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(104, format!("{:?}", w)),
             WalletError::OutputManagerError(OutputManagerError::OutputManagerStorageError(
                 OutputManagerStorageError::OutputAlreadySpent,
-            )) => Self {
-                code: 105,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(105, format!("{:?}", w)),
             WalletError::OutputManagerError(OutputManagerError::OutputManagerStorageError(
                 OutputManagerStorageError::PendingTransactionNotFound,
-            )) => Self {
-                code: 106,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(106, format!("{:?}", w)),
             WalletError::OutputManagerError(OutputManagerError::OutputManagerStorageError(
                 OutputManagerStorageError::DuplicateOutput,
-            )) => Self {
-                code: 107,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(107, format!("{:?}", w)),
             WalletError::OutputManagerError(OutputManagerError::OutputManagerStorageError(
                 OutputManagerStorageError::ValueNotFound(_),
-            )) => Self {
-                code: 108,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(108, format!("{:?}", w)),
             // Transaction Service Errors
-            WalletError::TransactionServiceError(TransactionServiceError::InvalidStateError) => Self {
-                code: 201,
-                message: format!("{:?}", w),
+            WalletError::TransactionServiceError(TransactionServiceError::InvalidStateError) => {
+                Self::new(201, format!("{:?}", w))
             },
-            WalletError::TransactionServiceError(TransactionServiceError::TransactionProtocolError(_)) => Self {
-                code: 202,
-                message: format!("{:?}", w),
+            WalletError::TransactionServiceError(TransactionServiceError::TransactionProtocolError(_)) => {
+                Self::new(202, format!("{:?}", w))
             },
-            WalletError::TransactionServiceError(TransactionServiceError::RepeatedMessageError) => Self {
-                code: 203,
-                message: format!("{:?}", w),
+            WalletError::TransactionServiceError(TransactionServiceError::RepeatedMessageError) => {
+                Self::new(203, format!("{:?}", w))
             },
-            WalletError::TransactionServiceError(TransactionServiceError::TransactionDoesNotExistError) => Self {
-                code: 204,
-                message: format!("{:?}", w),
+            WalletError::TransactionServiceError(TransactionServiceError::TransactionDoesNotExistError) => {
+                Self::new(204, format!("{:?}", w))
             },
             WalletError::TransactionServiceError(TransactionServiceError::OutputManagerError(
-                OutputManagerError::NotEnoughFunds,
-            )) => Self {
-                code: 205,
-                message: format!("{:?}", w),
+                OutputManagerError::InsufficientFunds { required, available },
+            )) => Self::new(205, insufficient_funds_message(required, available)),
+            WalletError::TransactionServiceError(TransactionServiceError::OutputManagerError(_)) => {
+                Self::new(206, format!("{:?}", w))
             },
-            WalletError::TransactionServiceError(TransactionServiceError::OutputManagerError(_)) => Self {
-                code: 206,
-                message: format!("{:?}", w),
-            },
-            WalletError::TransactionServiceError(TransactionServiceError::TransactionError(_)) => Self {
-                code: 207,
-                message: format!("{:?}", w),
+            WalletError::TransactionServiceError(TransactionServiceError::TransactionError(_)) => {
+                Self::new(207, format!("{:?}", w))
             },
             WalletError::TransactionServiceError(TransactionServiceError::TransactionStorageError(
                 TransactionStorageError::DuplicateOutput,
-            )) => Self {
-                code: 208,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(208, format!("{:?}", w)),
             WalletError::TransactionServiceError(TransactionServiceError::TransactionStorageError(
                 TransactionStorageError::ValueNotFound(_),
-            )) => Self {
-                code: 209,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(209, format!("{:?}", w)),
             // Comms Stack errors
-            WalletError::MultiaddrError(_) => Self {
-                code: 301,
-                message: format!("{:?}", w),
-            },
-            WalletError::ContactsServiceError(ContactsServiceError::ContactNotFound) => Self {
-                code: 401,
-                message: format!("{:?}", w),
+            WalletError::MultiaddrError(_) => Self::new(301, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactNotFound) => {
+                Self::new(401, format!("{:?}", w))
             },
             WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
                 ContactsServiceStorageError::DuplicateContact,
-            )) => Self {
-                code: 402,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(402, format!("{:?}", w)),
             WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
                 ContactsServiceStorageError::OperationNotSupported,
-            )) => Self {
-                code: 403,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(403, format!("{:?}", w)),
             WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
                 ContactsServiceStorageError::ConversionError,
-            )) => Self {
-                code: 404,
-                message: format!("{:?}", w),
-            },
+            )) => Self::new(404, format!("{:?}", w)),
             WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
                 ContactsServiceStorageError::ValuesNotFound,
-            )) => Self {
-                code: 405,
-                message: format!("{:?}", w),
+            )) => Self::new(405, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::ValueNotFound(_),
+            )) => Self::new(406, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::UnexpectedResult(_),
+            )) => Self::new(407, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::R2d2Error,
+            )) => Self::new(408, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::DieselError(_),
+            )) => Self::new(409, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::DieselConnectionError(_),
+            )) => Self::new(410, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::ContactsServiceStorageError(
+                ContactsServiceStorageError::DatabaseMigrationError(_),
+            )) => Self::new(411, format!("{:?}", w)),
+            WalletError::ContactsServiceError(ContactsServiceError::UnexpectedApiResponse) => {
+                Self::new(412, format!("{:?}", w))
             },
-            // This is the catch all error code. Any error that is not explicitly mapped above will be given this code
-            _ => Self {
-                code: 999,
-                message: format!("{:?}", w).to_string(),
+            WalletError::ContactsServiceError(ContactsServiceError::TransportChannelError(_)) => {
+                Self::new(413, format!("{:?}", w))
             },
+            // This is the catch all error code. Any error that is not explicitly mapped above will be given this code
+            _ => Self::new(999, format!("{:?}", w).to_string()),
         }
     }
 }
 
+fn insufficient_funds_message(required: MicroTari, available: MicroTari) -> String {
+    format!(
+        "Insufficient funds: {} required, {} available, {} short",
+        required,
+        available,
+        required.checked_sub(available).unwrap_or_default()
+    )
+}
+
 /// This implementation maps the internal HexError to a set of LibWalletErrors. The mapping is explicitly manager
 /// here and error code 999 is a catch-all code for any errors that are not explicitly mapped
 impl From<HexError> for LibWalletError {
     fn from(h: HexError) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", h));
         match h {
-            HexError::LengthError => Self {
-                code: 501,
-                message: format!("{:?}", h).to_string(),
-            },
-            HexError::HexConversionError => Self {
-                code: 502,
-                message: format!("{:?}", h).to_string(),
-            },
-            HexError::InvalidCharacter(_) => Self {
-                code: 503,
-                message: format!("{:?}", h).to_string(),
-            },
+            HexError::LengthError => Self::new(501, format!("{:?}", h).to_string()),
+            HexError::HexConversionError => Self::new(502, format!("{:?}", h).to_string()),
+            HexError::InvalidCharacter(_) => Self::new(503, format!("{:?}", h).to_string()),
         }
     }
 }
@@ -243,14 +267,8 @@ impl From<ByteArrayError> for LibWalletError {
     fn from(b: ByteArrayError) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", b));
         match b {
-            ByteArrayError::IncorrectLength => Self {
-                code: 601,
-                message: format!("{:?}", b).to_string(),
-            },
-            ByteArrayError::ConversionError(_) => Self {
-                code: 602,
-                message: format!("{:?}", b).to_string(),
-            },
+            ByteArrayError::IncorrectLength => Self::new(601, format!("{:?}", b).to_string()),
+            ByteArrayError::ConversionError(_) => Self::new(602, format!("{:?}", b).to_string()),
         }
     }
 }
@@ -259,18 +277,11 @@ impl From<NodeIdentityError> for LibWalletError {
     fn from(n: NodeIdentityError) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", n));
         match n {
-            NodeIdentityError::NodeIdError(NodeIdError::IncorrectByteCount) => Self {
-                code: 701,
-                message: format!("{:?}", n).to_string(),
-            },
-            NodeIdentityError::NodeIdError(NodeIdError::OutOfBounds) => Self {
-                code: 702,
-                message: format!("{:?}", n).to_string(),
-            },
-            NodeIdentityError::PoisonedAccess => Self {
-                code: 703,
-                message: format!("{:?}", n).to_string(),
+            NodeIdentityError::NodeIdError(NodeIdError::IncorrectByteCount) => {
+                Self::new(701, format!("{:?}", n).to_string())
             },
+            NodeIdentityError::NodeIdError(NodeIdError::OutOfBounds) => Self::new(702, format!("{:?}", n).to_string()),
+            NodeIdentityError::PoisonedAccess => Self::new(703, format!("{:?}", n).to_string()),
         }
     }
 }
@@ -279,26 +290,136 @@ impl From<multiaddr::Error> for LibWalletError {
     fn from(n: multiaddr::Error) -> Self {
         error!(target: LOG_TARGET, "{}", format!("{:?}", n));
         match n {
-            multiaddr::Error::ParsingError(_) => Self {
-                code: 801,
-                message: format!("{:?}", n).to_string(),
-            },
-            multiaddr::Error::InvalidMultiaddr => Self {
-                code: 802,
-                message: format!("{:?}", n).to_string(),
-            },
-            multiaddr::Error::MissingAddress => Self {
-                code: 803,
-                message: format!("{:?}", n).to_string(),
-            },
-            multiaddr::Error::UnknownProtocol => Self {
-                code: 804,
-                message: format!("{:?}", n).to_string(),
-            },
-            multiaddr::Error::UnknownProtocolString => Self {
-                code: 805,
-                message: format!("{:?}", n).to_string(),
-            },
+            multiaddr::Error::ParsingError(_) => Self::new(801, format!("{:?}", n).to_string()),
+            multiaddr::Error::InvalidMultiaddr => Self::new(802, format!("{:?}", n).to_string()),
+            multiaddr::Error::MissingAddress => Self::new(803, format!("{:?}", n).to_string()),
+            multiaddr::Error::UnknownProtocol => Self::new(804, format!("{:?}", n).to_string()),
+            multiaddr::Error::UnknownProtocolString => Self::new(805, format!("{:?}", n).to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wallet_error_category;
+    use libc::c_int;
+    use tari_service_framework::reply_channel::TransportChannelError;
+    use tari_wallet::contacts_service::storage::database::DbKey;
+
+    fn contacts_error_code(error: ContactsServiceError) -> i32 {
+        LibWalletError::from(WalletError::ContactsServiceError(error)).code
+    }
+
+    fn contacts_storage_error_code(error: ContactsServiceStorageError) -> i32 {
+        contacts_error_code(ContactsServiceError::ContactsServiceStorageError(error))
+    }
+
+    #[test]
+    fn multiaddr_errors_are_network_errors() {
+        let error = LibWalletError::from(WalletError::MultiaddrError(multiaddr::Error::InvalidMultiaddr));
+        assert_eq!(error.category, LibWalletErrorCategory::Network);
+        assert_eq!(
+            LibWalletErrorCategory::from_code(error.code),
+            LibWalletErrorCategory::Network
+        );
+
+        let error = LibWalletError::from(multiaddr::Error::MissingAddress);
+        assert_eq!(error.category, LibWalletErrorCategory::Network);
+        assert_eq!(
+            LibWalletErrorCategory::from_code(error.code),
+            LibWalletErrorCategory::Network
+        );
+    }
+
+    #[test]
+    fn output_manager_storage_errors_are_storage_errors() {
+        let error = LibWalletError::from(WalletError::OutputManagerError(
+            OutputManagerError::OutputManagerStorageError(OutputManagerStorageError::ValuesNotFound),
+        ));
+        assert_eq!(error.code, 104);
+        assert_eq!(error.category, LibWalletErrorCategory::Storage);
+        assert_eq!(
+            LibWalletErrorCategory::from_code(error.code),
+            LibWalletErrorCategory::Storage
+        );
+    }
+
+    #[test]
+    fn mapped_codes_have_a_category() {
+        // Every code that a From impl gives a LibWalletError, except the catch-all code 999
+        let codes = [
+            1, 2, 3, 4, 102, 103, 104, 105, 106, 107, 108, 109, 201, 202, 203, 204, 205, 206, 207, 208, 209, 301, 401,
+            402, 403, 404, 405, 406, 407, 408, 409, 410, 411, 412, 413, 501, 502, 503, 601, 602, 701, 702, 703, 801,
+            802, 803, 804, 805,
+        ];
+        for code in codes.iter() {
+            let category = unsafe { wallet_error_category(*code) };
+            assert_ne!(
+                category,
+                LibWalletErrorCategory::Unknown as c_int,
+                "Error code {} has no category",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn unmapped_codes_are_unknown() {
+        assert_eq!(LibWalletErrorCategory::from_code(0), LibWalletErrorCategory::Unknown);
+        assert_eq!(LibWalletErrorCategory::from_code(999), LibWalletErrorCategory::Unknown);
+    }
+
+    #[test]
+    fn contacts_service_error_codes() {
+        assert_eq!(contacts_error_code(ContactsServiceError::ContactNotFound), 401);
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::DuplicateContact),
+            402
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::OperationNotSupported),
+            403
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::ConversionError),
+            404
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::ValuesNotFound),
+            405
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::ValueNotFound(DbKey::Contacts)),
+            406
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::UnexpectedResult("".to_string())),
+            407
+        );
+        assert_eq!(contacts_storage_error_code(ContactsServiceStorageError::R2d2Error), 408);
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::DieselError(
+                diesel::result::Error::NotFound
+            )),
+            409
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::DieselConnectionError(
+                diesel::ConnectionError::BadConnection("".to_string())
+            )),
+            410
+        );
+        assert_eq!(
+            contacts_storage_error_code(ContactsServiceStorageError::DatabaseMigrationError("".to_string())),
+            411
+        );
+        assert_eq!(contacts_error_code(ContactsServiceError::UnexpectedApiResponse), 412);
+        assert_eq!(
+            contacts_error_code(ContactsServiceError::TransportChannelError(
+                TransportChannelError::ChannelClosed
+            )),
+            413
+        );
+    }
+}
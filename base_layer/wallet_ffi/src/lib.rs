@@ -112,7 +112,7 @@ extern crate lazy_static;
 extern crate libc;
 extern crate tari_wallet;
 mod error;
-use error::LibWalletError;
+use error::{LibWalletError, LibWalletErrorCategory};
 
 use libc::{c_char, c_int, c_longlong, c_uchar, c_uint, c_ulonglong};
 use std::{
@@ -2647,6 +2647,19 @@ pub unsafe extern "C" fn wallet_destroy(wallet: *mut TariWallet) {
     }
 }
 
+/// Gets the category of an error code returned by one of the functions in this library
+///
+/// ## Arguments
+/// `code` - The error code
+///
+/// ## Returns
+/// `c_int` - Returns the category of the error: 0 = Storage, 1 = Network, 2 = Validation, 3 = Key Management,
+/// 4 = Interface, 5 = Unknown, 6 = Service. Codes that are not known are given the Unknown category
+#[no_mangle]
+pub unsafe extern "C" fn wallet_error_category(code: c_int) -> c_int {
+    LibWalletErrorCategory::from_code(code) as c_int
+}
+
 #[cfg(test)]
 mod test {
     extern crate libc;
@@ -347,6 +347,10 @@ bool wallet_test_receive_transaction(struct TariWallet *wallet,int* error_out);
 // Frees memory for a TariWallet
 void wallet_destroy(struct TariWallet *wallet);
 
+// Gets the category of an error code: 0 = Storage, 1 = Network, 2 = Validation, 3 = Key Management, 4 = Interface,
+// 5 = Unknown, 6 = Service
+int wallet_error_category(int code);
+
 #ifdef __cplusplus
 }
 #endif
@@ -0,0 +1,173 @@
+// Copyright 2019. The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//! # Exponential backoff
+//!
+//! [ExponentialBackoff] calculates the delays between successive attempts of an operation. The delay starts at a base
+//! delay, is multiplied by a constant factor after every attempt and is clamped to a maximum delay. Optionally, the
+//! number of attempts can be limited and a random jitter can be applied to spread out retries from many callers.
+
+use rand::Rng;
+use std::{cmp::min, time::Duration};
+
+/// Calculates exponentially increasing delays between attempts of an operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExponentialBackoff {
+    base_delay: Duration,
+    factor: f64,
+    max_delay: Duration,
+    max_attempts: Option<usize>,
+    jitter: f64,
+    attempts: usize,
+}
+
+impl ExponentialBackoff {
+    /// Create a new backoff. The first delay is `base_delay` and every subsequent delay is `factor` times the previous
+    /// one, up to `max_delay`. A factor below one is treated as one.
+    pub fn new(base_delay: Duration, factor: f64, max_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            factor: factor.max(1.0),
+            max_delay,
+            max_attempts: None,
+            jitter: 0.0,
+            attempts: 0,
+        }
+    }
+
+    /// Limit the number of delays returned by `next_delay`. By default the number of delays is unlimited.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Apply a random jitter to the delays returned by `next_delay`. A jittered delay is chosen uniformly from
+    /// `[delay * (1 - jitter), delay]`, so it never exceeds the maximum delay. The jitter is clamped to `[0, 1]`.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.max(0.0).min(1.0);
+        self
+    }
+
+    /// Returns the delay to wait before the next attempt, or `None` if the maximum number of attempts has been reached
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.max_attempts.map(|max| self.attempts >= max).unwrap_or(false) {
+            return None;
+        }
+        let delay = self.delay_for_attempt(self.attempts);
+        self.attempts += 1;
+        Some(self.apply_jitter(delay))
+    }
+
+    /// Returns the delay (without jitter) for the given zero-based attempt
+    pub fn delay_for_attempt(&self, attempt: usize) -> Duration {
+        // Cap the exponent so that the multiplier does not overflow. The delay is clamped to the maximum long before.
+        let multiplier = self.factor.powi(min(attempt, 1024) as i32);
+        let delay_nanos = self.base_delay.as_nanos() as f64 * multiplier;
+        if !delay_nanos.is_finite() || delay_nanos >= self.max_delay.as_nanos() as f64 {
+            return self.max_delay;
+        }
+        Duration::from_nanos(delay_nanos.round() as u64)
+    }
+
+    /// Returns the given delay with the configured jitter applied
+    pub fn apply_jitter(&self, delay: Duration) -> Duration {
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let scale = rand::thread_rng().gen_range(1.0 - self.jitter, 1.0);
+        delay.mul_f64(scale)
+    }
+
+    /// The number of delays returned by `next_delay` so far
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Reset the number of attempts, so that the next delay is the base delay again
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+impl Default for ExponentialBackoff {
+    /// A delay of two seconds, doubling after every attempt up to an hour, without jitter or a limit on the number of
+    /// attempts
+    fn default() -> Self {
+        Self::new(Duration::from_secs(2), 2.0, Duration::from_secs(60 * 60))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delay_progression() {
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(100), 2.0, Duration::from_secs(60));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(200)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(400)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(800)));
+        assert_eq!(backoff.attempts(), 4);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+
+        let backoff = ExponentialBackoff::new(Duration::from_secs(2), 1.5, Duration::from_secs(60));
+        assert_eq!(backoff.delay_for_attempt(0), Duration::from_secs(2));
+        assert_eq!(backoff.delay_for_attempt(2), Duration::from_millis(4500));
+    }
+
+    #[test]
+    fn max_delay() {
+        let mut backoff = ExponentialBackoff::new(Duration::from_secs(1), 10.0, Duration::from_secs(30));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(10)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(30)));
+        assert_eq!(backoff.next_delay(), Some(Duration::from_secs(30)));
+        assert_eq!(backoff.delay_for_attempt(usize::max_value()), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn max_attempts() {
+        let mut backoff =
+            ExponentialBackoff::new(Duration::from_millis(10), 2.0, Duration::from_secs(1)).with_max_attempts(2);
+        assert!(backoff.next_delay().is_some());
+        assert!(backoff.next_delay().is_some());
+        assert_eq!(backoff.next_delay(), None);
+        backoff.reset();
+        assert!(backoff.next_delay().is_some());
+    }
+
+    #[test]
+    fn jitter_bounds() {
+        let mut backoff = ExponentialBackoff::new(Duration::from_secs(1), 2.0, Duration::from_secs(4)).with_jitter(0.5);
+        for attempt in 0..20 {
+            let max = backoff.delay_for_attempt(attempt);
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay <= max);
+            assert!(delay >= max / 2);
+        }
+        let backoff = ExponentialBackoff::new(Duration::from_secs(1), 2.0, Duration::from_secs(4)).with_jitter(5.0);
+        let delay = backoff.apply_jitter(Duration::from_secs(1));
+        assert!(delay <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2019. The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//! # Bounded concurrency helpers
+//!
+//! Helpers to drive a collection of futures to completion while limiting the number of futures that are polled
+//! concurrently.
+
+use futures::{stream, Future, StreamExt, TryStreamExt};
+
+/// Drive the given futures to completion with at most `concurrency` futures in flight at any one time. The results
+/// are returned in the same order as the given futures. The first error encountered is returned, and no further
+/// futures are started once an error has occurred. A `concurrency` of zero is treated as one.
+pub async fn buffered_try_join_all<I, F, T, E>(futures: I, concurrency: usize) -> Result<Vec<T>, E>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Result<T, E>>,
+{
+    stream::iter(futures)
+        .buffered(std::cmp::max(concurrency, 1))
+        .try_collect()
+        .await
+}
+
+/// Drive the given futures to completion with at most `concurrency` futures in flight at any one time. Unlike
+/// [buffered_try_join_all], every future is driven to completion and all the results are returned in the same order
+/// as the given futures. A `concurrency` of zero is treated as one.
+pub async fn buffered_join_all<I, F>(futures: I, concurrency: usize) -> Vec<F::Output>
+where
+    I: IntoIterator<Item = F>,
+    F: Future,
+{
+    stream::iter(futures)
+        .buffered(std::cmp::max(concurrency, 1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+            Mutex,
+        },
+        time::{Duration, Instant},
+    };
+    use tokio::{runtime::Runtime, timer};
+
+    /// Keeps track of the current and maximum number of futures in flight
+    #[derive(Default)]
+    struct InFlight {
+        counts: Mutex<(usize, usize)>,
+    }
+
+    impl InFlight {
+        async fn run<T>(self: Arc<Self>, value: T, delay_ms: u64) -> T {
+            {
+                let mut counts = self.counts.lock().unwrap();
+                counts.0 += 1;
+                counts.1 = std::cmp::max(counts.0, counts.1);
+            }
+            timer::delay(Instant::now() + Duration::from_millis(delay_ms)).await;
+            self.counts.lock().unwrap().0 -= 1;
+            value
+        }
+
+        fn max(&self) -> usize {
+            self.counts.lock().unwrap().1
+        }
+    }
+
+    #[test]
+    fn concurrency_cap_and_ordering() {
+        let rt = Runtime::new().unwrap();
+        let in_flight = Arc::new(InFlight::default());
+        // Later futures complete sooner, to check that the results are still ordered
+        let futures = (0..10u64)
+            .map(|i| {
+                let in_flight = Arc::clone(&in_flight);
+                async move { Result::<_, ()>::Ok(in_flight.run(i, 20 - i).await) }
+            })
+            .collect::<Vec<_>>();
+
+        let results = rt.block_on(buffered_try_join_all(futures, 3)).unwrap();
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+        assert_eq!(in_flight.max(), 3);
+    }
+
+    #[test]
+    fn short_circuit_on_error() {
+        let rt = Runtime::new().unwrap();
+        let started = Arc::new(AtomicUsize::new(0));
+        let futures = (0..10usize)
+            .map(|i| {
+                let started = Arc::clone(&started);
+                async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    if i == 2 {
+                        Err(i)
+                    } else {
+                        Ok(i)
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(rt.block_on(buffered_try_join_all(futures, 1)), Err(2));
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn join_all_collects_all_results() {
+        let rt = Runtime::new().unwrap();
+        let in_flight = Arc::new(InFlight::default());
+        let futures = (0..6usize)
+            .map(|i| {
+                let in_flight = Arc::clone(&in_flight);
+                async move {
+                    let i = in_flight.run(i, 5).await;
+                    if i % 2 == 0 {
+                        Ok(i)
+                    } else {
+                        Err(i)
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let results = rt.block_on(buffered_join_all(futures, 2));
+        assert_eq!(results, vec![Ok(0), Err(1), Ok(2), Err(3), Ok(4), Err(5)]);
+        assert_eq!(in_flight.max(), 2);
+    }
+}
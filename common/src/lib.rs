@@ -54,7 +54,10 @@ mod configuration;
 #[macro_use]
 mod logging;
 
+pub mod backoff;
+pub mod concurrency;
 pub mod dir_utils;
+pub mod retry;
 pub use configuration::{
     default_config,
     install_default_config_file,
@@ -0,0 +1,217 @@
+// Copyright 2019. The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+//! # Retry utilities
+//!
+//! [retry_with_backoff] repeatedly calls a fallible asynchronous function, waiting an exponentially increasing
+//! amount of time between attempts, until it succeeds, returns an error that should not be retried, or the maximum
+//! number of attempts is reached.
+
+use crate::backoff::ExponentialBackoff;
+use std::{
+    cmp::max,
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::timer;
+
+/// Controls how many times and how quickly an operation is retried by [retry_with_backoff]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffPolicy {
+    /// The maximum number of attempts, including the first. A value of zero is treated as one.
+    pub max_attempts: usize,
+    /// The delay before the first retry. The delay doubles for each subsequent retry.
+    pub base_delay: Duration,
+    /// The upper bound of the delay between attempts
+    pub max_delay: Duration,
+}
+
+impl BackoffPolicy {
+    pub fn new(max_attempts: usize, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+        }
+    }
+
+    /// Returns the delay to wait before the given retry (the first retry is 1)
+    pub fn delay_for_retry(&self, retry: usize) -> Duration {
+        if retry == 0 {
+            return Duration::from_millis(0);
+        }
+        ExponentialBackoff::new(self.base_delay, 2.0, self.max_delay).delay_for_attempt(retry - 1)
+    }
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Call `f` until it returns `Ok`, an error for which `is_retryable` returns false, or `policy.max_attempts` attempts
+/// have been made. The delay between attempts is determined by the `BackoffPolicy`. The result of the last attempt is
+/// returned.
+pub async fn retry_with_backoff<F, Fut, T, E, R>(policy: BackoffPolicy, mut f: F, is_retryable: R) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    R: Fn(&E) -> bool,
+{
+    let max_attempts = max(policy.max_attempts, 1);
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(v) => return Ok(v),
+            Err(err) => {
+                if attempt >= max_attempts || !is_retryable(&err) {
+                    return Err(err);
+                }
+                timer::delay(Instant::now() + policy.delay_for_retry(attempt)).await;
+                attempt += 1;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::runtime::Runtime;
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Transient,
+        Fatal,
+    }
+
+    fn is_transient(err: &TestError) -> bool {
+        *err == TestError::Transient
+    }
+
+    fn test_policy(max_attempts: usize) -> BackoffPolicy {
+        BackoffPolicy::new(max_attempts, Duration::from_millis(1), Duration::from_millis(5))
+    }
+
+    #[test]
+    fn delay_for_retry() {
+        let policy = BackoffPolicy::new(10, Duration::from_millis(100), Duration::from_secs(1));
+        assert_eq!(policy.delay_for_retry(0), Duration::from_millis(0));
+        assert_eq!(policy.delay_for_retry(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_for_retry(2), Duration::from_millis(200));
+        assert_eq!(policy.delay_for_retry(3), Duration::from_millis(400));
+        assert_eq!(policy.delay_for_retry(5), Duration::from_secs(1));
+        assert_eq!(policy.delay_for_retry(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn success_after_retries() {
+        let rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        let result = rt.block_on(retry_with_backoff(
+            test_policy(5),
+            move || {
+                let attempts = Arc::clone(&attempts_clone);
+                async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                        Err(TestError::Transient)
+                    } else {
+                        Ok(123)
+                    }
+                }
+            },
+            is_transient,
+        ));
+        assert_eq!(result, Ok(123));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn give_up_after_max_attempts() {
+        let rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        let result: Result<(), _> = rt.block_on(retry_with_backoff(
+            test_policy(4),
+            move || {
+                let attempts = Arc::clone(&attempts_clone);
+                async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(TestError::Transient)
+                }
+            },
+            is_transient,
+        ));
+        assert_eq!(result, Err(TestError::Transient));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn non_retryable_error() {
+        let rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        let result: Result<(), _> = rt.block_on(retry_with_backoff(
+            test_policy(4),
+            move || {
+                let attempts = Arc::clone(&attempts_clone);
+                async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(TestError::Fatal)
+                }
+            },
+            is_transient,
+        ));
+        assert_eq!(result, Err(TestError::Fatal));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn closure_decides_retry() {
+        let rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = Arc::clone(&attempts);
+        let retryable = TestError::Fatal;
+        let result: Result<(), _> = rt.block_on(retry_with_backoff(
+            test_policy(3),
+            move || {
+                let attempts = Arc::clone(&attempts_clone);
+                async move {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    Err(TestError::Fatal)
+                }
+            },
+            |err| *err == retryable,
+        ));
+        assert_eq!(result, Err(TestError::Fatal));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -32,6 +32,7 @@ use crate::{
     discovery::DhtDiscoveryError,
     outbound::{OutboundMessageRequester, SendMessageParams},
     proto::{dht::JoinMessage, envelope::DhtMessageType, store_forward::StoredMessagesRequest},
+    signature_cache::SignatureCache,
     DhtConfig,
 };
 use chrono::{DateTime, Utc};
@@ -46,7 +47,7 @@ use futures::{
     StreamExt,
 };
 use log::*;
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 use tari_comms::{
     peer_manager::{
         NodeId,
@@ -63,7 +64,6 @@ use tari_comms::{
 use tari_shutdown::ShutdownSignal;
 use tari_utilities::ByteArray;
 use tokio_executor::blocking;
-use ttl_cache::TtlCache;
 
 const LOG_TARGET: &'static str = "comms::dht::actor";
 
@@ -105,6 +105,74 @@ pub enum DhtRequest {
     SignatureCacheInsert(Vec<u8>, oneshot::Sender<bool>),
     /// Fetch selected peers according to the broadcast strategy
     SelectPeers(BroadcastStrategy, oneshot::Sender<Vec<Peer>>),
+    /// Fetch a snapshot of the (unexpired) signatures currently in the signature cache
+    SnapshotSignatureCache(oneshot::Sender<SignatureCacheSnapshot>),
+}
+
+/// A point-in-time copy of the signatures contained in the signature cache
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignatureCacheSnapshot {
+    signatures: HashSet<Vec<u8>>,
+}
+
+impl SignatureCacheSnapshot {
+    pub fn new(signatures: HashSet<Vec<u8>>) -> Self {
+        Self { signatures }
+    }
+
+    /// Returns the number of signatures in this snapshot
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Returns true if this snapshot contains no signatures
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Returns true if the signature was in the cache when this snapshot was taken
+    pub fn contains(&self, signature: &[u8]) -> bool {
+        self.signatures.contains(signature)
+    }
+
+    /// Returns an iterator over the signatures in this snapshot
+    pub fn iter(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.signatures.iter()
+    }
+
+    /// Compares this snapshot to a later snapshot, returning the signatures that were added to and removed from
+    /// (e.g. expired) the cache between the two snapshots.
+    pub fn diff(&self, later: &SignatureCacheSnapshot) -> SignatureCacheDiff {
+        let mut added = later
+            .signatures
+            .difference(&self.signatures)
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut removed = self
+            .signatures
+            .difference(&later.signatures)
+            .cloned()
+            .collect::<Vec<_>>();
+        added.sort();
+        removed.sort();
+        SignatureCacheDiff { added, removed }
+    }
+}
+
+/// The difference between two `SignatureCacheSnapshot`s. Signatures are sorted to make the diff deterministic.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignatureCacheDiff {
+    /// Signatures in the later snapshot that are not in the earlier snapshot
+    pub added: Vec<Vec<u8>>,
+    /// Signatures in the earlier snapshot that are not in the later snapshot
+    pub removed: Vec<Vec<u8>>,
+}
+
+impl SignatureCacheDiff {
+    /// Returns true if the snapshots contained the same signatures
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
 }
 
 #[derive(Clone)]
@@ -138,6 +206,13 @@ impl DhtRequester {
         reply_rx.await.map_err(|_| DhtActorError::ReplyCanceled)
     }
 
+    pub async fn snapshot_signature_cache(&mut self) -> Result<SignatureCacheSnapshot, DhtActorError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.sender.send(DhtRequest::SnapshotSignatureCache(reply_tx)).await?;
+
+        reply_rx.await.map_err(|_| DhtActorError::ReplyCanceled)
+    }
+
     pub async fn send_request_stored_messages(&mut self) -> Result<(), DhtActorError> {
         self.sender
             .send(DhtRequest::SendRequestStoredMessages(None))
@@ -153,7 +228,7 @@ pub struct DhtActor<'a> {
     config: DhtConfig,
     shutdown_signal: Option<ShutdownSignal>,
     request_rx: Fuse<mpsc::Receiver<DhtRequest>>,
-    signature_cache: TtlCache<Vec<u8>, ()>,
+    signature_cache: SignatureCache,
     pending_jobs: FuturesUnordered<BoxFuture<'a, Result<(), DhtActorError>>>,
 }
 
@@ -168,7 +243,7 @@ impl<'a> DhtActor<'a> {
     ) -> Self
     {
         Self {
-            signature_cache: TtlCache::new(config.signature_cache_capacity),
+            signature_cache: SignatureCache::new(config.signature_cache_capacity, config.signature_cache_ttl),
             config,
             outbound_requester,
             peer_manager,
@@ -232,13 +307,17 @@ impl<'a> DhtActor<'a> {
             SignatureCacheInsert(signature, reply_tx) => {
                 // No locks needed here. Downside is this isn't really async, however this should be
                 // fine as it is very quick
-                let already_exists = self
-                    .signature_cache
-                    .insert(signature, (), self.config.signature_cache_ttl)
-                    .is_some();
+                let already_exists = self.signature_cache.insert(signature);
                 let result = reply_tx.send(already_exists).map_err(|_| DhtActorError::ReplyCanceled);
                 Box::pin(future::ready(result))
             },
+            SnapshotSignatureCache(reply_tx) => {
+                let signatures = self.signature_cache.iter().cloned().collect();
+                let result = reply_tx
+                    .send(SignatureCacheSnapshot::new(signatures))
+                    .map_err(|_| DhtActorError::ReplyCanceled);
+                Box::pin(future::ready(result))
+            },
             SelectPeers(broadcast_strategy, reply_tx) => {
                 let peer_manager = Arc::clone(&self.peer_manager);
                 let node_identity = Arc::clone(&self.node_identity);
@@ -531,6 +610,55 @@ mod test {
         });
     }
 
+    #[test]
+    fn snapshot_signature_cache() {
+        runtime::test_async(|rt| {
+            let node_identity = make_node_identity();
+            let peer_manager = make_peer_manager();
+            let (out_tx, _) = mpsc::channel(1);
+            let (actor_tx, actor_rx) = mpsc::channel(1);
+            let mut requester = DhtRequester::new(actor_tx);
+            let outbound_requester = OutboundMessageRequester::new(out_tx);
+            let shutdown = Shutdown::new();
+            let actor = DhtActor::new(
+                Default::default(),
+                node_identity,
+                peer_manager,
+                outbound_requester,
+                actor_rx,
+                shutdown.to_signal(),
+            );
+
+            rt.spawn(actor.run());
+
+            rt.block_on(async move {
+                requester.insert_message_signature(vec![1u8, 2, 3]).await.unwrap();
+                requester.insert_message_signature(vec![4u8, 5, 6]).await.unwrap();
+                let before = requester.snapshot_signature_cache().await.unwrap();
+                assert_eq!(before.len(), 2);
+                assert!(before.contains(&[1u8, 2, 3]));
+                assert!(before.contains(&[4u8, 5, 6]));
+
+                requester.insert_message_signature(vec![9u8]).await.unwrap();
+                requester.insert_message_signature(vec![7u8, 8]).await.unwrap();
+                // Duplicate inserts do not change the cache
+                requester.insert_message_signature(vec![1u8, 2, 3]).await.unwrap();
+                let after = requester.snapshot_signature_cache().await.unwrap();
+                assert_eq!(after.len(), 4);
+
+                let diff = before.diff(&after);
+                assert_eq!(diff.added, vec![vec![7u8, 8], vec![9u8]]);
+                assert!(diff.removed.is_empty());
+
+                let diff = after.diff(&before);
+                assert!(diff.added.is_empty());
+                assert_eq!(diff.removed, vec![vec![7u8, 8], vec![9u8]]);
+
+                assert!(after.diff(&after).is_empty());
+            });
+        });
+    }
+
     #[test]
     fn select_peers() {
         runtime::test_async(|rt| {
@@ -30,6 +30,8 @@ pub const SAF_LOW_PRIORITY_MSG_STORAGE_TTL: Duration = Duration::from_secs(6 * 6
 pub const SAF_HIGH_PRIORITY_MSG_STORAGE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 /// The default number of peer nodes that a message has to be closer to, to be considered a neighbour
 pub const DEFAULT_NUM_NEIGHBOURING_NODES: usize = 8;
+/// The default maximum size in bytes of an inbound message body
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct DhtConfig {
@@ -72,6 +74,13 @@ pub struct DhtConfig {
     /// The duration to wait for a peer discovery to complete before giving up.
     /// Default: 2 minutes
     pub discovery_request_timeout: Duration,
+    /// The maximum size in bytes of an inbound message body. Larger messages are discarded before deduplication.
+    /// Default: 4MiB
+    pub max_message_size: usize,
+    /// If true, messages which originate from this node bypass deduplication so that they can be re-published (e.g.
+    /// by store-and-forward). Duplicate messages from other origins are still discarded.
+    /// Default: false
+    pub republish_allowed: bool,
 }
 
 impl Default for DhtConfig {
@@ -89,6 +98,8 @@ impl Default for DhtConfig {
             broadcast_cooldown_max_attempts: 3,
             broadcast_cooldown_period: Duration::from_secs(60 * 30),
             discovery_request_timeout: Duration::from_secs(2 * 60),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            republish_allowed: false,
         }
     }
 }
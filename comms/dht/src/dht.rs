@@ -171,9 +171,14 @@ impl Dht {
             self.config.saf_msg_cache_storage_capacity,
         ));
 
+        let mut dedup_layer = inbound::DedupLayer::new(self.dht_requester(), self.config.max_message_size);
+        if self.config.republish_allowed {
+            dedup_layer = dedup_layer.with_republish_allowed(self.node_identity.public_key().clone());
+        }
+
         ServiceBuilder::new()
             .layer(inbound::DeserializeLayer::new())
-            .layer(inbound::DedupLayer::new(self.dht_requester()))
+            .layer(dedup_layer)
             .layer(tower_filter::FilterLayer::new(self.unsupported_saf_messages_filter()))
             .layer(inbound::DecryptionLayer::new(Arc::clone(&self.node_identity)))
             .layer(store_forward::ForwardLayer::new(
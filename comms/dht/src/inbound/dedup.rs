@@ -20,29 +20,86 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use crate::{actor::DhtRequester, inbound::DhtInboundMessage};
+use crate::{
+    actor::DhtRequester,
+    inbound::{DhtInboundMessage, MessageProvenance},
+};
+use digest::Digest;
 use futures::{task::Context, Future, Poll};
 use log::*;
+use tari_comms::types::{Challenge, CommsPublicKey};
 use tari_comms_middleware::MiddlewareError;
+use tari_utilities::ByteArray;
 use tower::{layer::Layer, Service, ServiceExt};
 
 const LOG_TARGET: &'static str = "comms::dht::dedup";
 
+/// Determines how messages that do not have an origin signature are deduplicated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsignedMessagePolicy {
+    /// Messages without an origin signature are never considered duplicates
+    Bypass,
+    /// Messages without an origin signature are deduplicated using a hash of the origin public key and message body
+    ContentHash,
+}
+
+impl Default for UnsignedMessagePolicy {
+    fn default() -> Self {
+        UnsignedMessagePolicy::ContentHash
+    }
+}
+
+/// Determines which key is used to look up signed messages in the signature cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupKeyMode {
+    /// Messages are deduplicated using the origin signature only
+    Signature,
+    /// Messages are deduplicated using a hash of the origin signature, origin public key and message body. This
+    /// guards against messages which reuse a signature with a different body, should the signature not have been
+    /// validated.
+    ContentHash,
+}
+
+impl Default for DedupKeyMode {
+    fn default() -> Self {
+        DedupKeyMode::Signature
+    }
+}
+
 /// # DHT Deduplication middleware
 ///
 /// Takes in a `DhtInboundMessage` and checks the message signature cache for duplicates.
-/// If a duplicate message is detected, it is discarded.
+/// If a duplicate message is detected, it is discarded. Messages with a body larger than `max_message_size` are
+/// discarded before any further processing. The cache key is determined by the `DedupKeyMode` and messages without an
+/// origin signature are handled according to the `UnsignedMessagePolicy`. If a republish public key is set, messages
+/// originating from that key (i.e. our own messages) are never considered duplicates.
 #[derive(Clone)]
 pub struct DedupMiddleware<S> {
     next_service: S,
     dht_requester: DhtRequester,
+    max_message_size: usize,
+    key_mode: DedupKeyMode,
+    unsigned_message_policy: UnsignedMessagePolicy,
+    republish_public_key: Option<CommsPublicKey>,
 }
 
 impl<S> DedupMiddleware<S> {
-    pub fn new(service: S, dht_requester: DhtRequester) -> Self {
+    pub fn new(
+        service: S,
+        dht_requester: DhtRequester,
+        max_message_size: usize,
+        key_mode: DedupKeyMode,
+        unsigned_message_policy: UnsignedMessagePolicy,
+        republish_public_key: Option<CommsPublicKey>,
+    ) -> Self
+    {
         Self {
             next_service: service,
             dht_requester,
+            max_message_size,
+            key_mode,
+            unsigned_message_policy,
+            republish_public_key,
         }
     }
 }
@@ -62,7 +119,15 @@ where
     }
 
     fn call(&mut self, msg: DhtInboundMessage) -> Self::Future {
-        Self::process_message(self.next_service.clone(), self.dht_requester.clone(), msg)
+        Self::process_message(
+            self.next_service.clone(),
+            self.dht_requester.clone(),
+            self.max_message_size,
+            self.key_mode,
+            self.unsigned_message_policy,
+            self.republish_public_key.clone(),
+            msg,
+        )
     }
 }
 
@@ -74,13 +139,50 @@ where
     pub async fn process_message(
         next_service: S,
         mut dht_requester: DhtRequester,
-        message: DhtInboundMessage,
+        max_message_size: usize,
+        key_mode: DedupKeyMode,
+        unsigned_message_policy: UnsignedMessagePolicy,
+        republish_public_key: Option<CommsPublicKey>,
+        mut message: DhtInboundMessage,
     ) -> Result<(), MiddlewareError>
     {
+        if message.body.len() > max_message_size {
+            warn!(
+                target: LOG_TARGET,
+                "Received message of {} bytes from peer {} which exceeds the maximum message size of {} bytes. \
+                 Message discarded.",
+                message.body.len(),
+                message.source_peer.node_id,
+                max_message_size
+            );
+            return Ok(());
+        }
+
+        if republish_public_key.as_ref() == Some(&message.dht_header.origin_public_key) {
+            trace!(
+                target: LOG_TARGET,
+                "Message from peer {} originated from this node. Skipping deduplication.",
+                message.source_peer.node_id
+            );
+            message.provenance = Some(MessageProvenance::new(false));
+            return next_service.oneshot(message).await.map_err(Into::into);
+        }
+
+        let cache_key = match Self::cache_key(&message, key_mode, unsigned_message_policy) {
+            Some(key) => key,
+            None => {
+                trace!(
+                    target: LOG_TARGET,
+                    "Message from peer {} has no origin signature. Skipping deduplication.",
+                    message.source_peer.node_id
+                );
+                message.provenance = Some(MessageProvenance::new(false));
+                return next_service.oneshot(message).await.map_err(Into::into);
+            },
+        };
+
         trace!(target: LOG_TARGET, "Checking inbound message cache for duplicates");
-        // WARN: It is assumed that the message signature has been checked (i.e. by the DeserializeMiddleware)
-        let signature = message.dht_header.origin_signature.clone();
-        if dht_requester.insert_message_signature(signature).await? {
+        if dht_requester.insert_message_signature(cache_key).await? {
             warn!(
                 target: LOG_TARGET,
                 "Received duplicate message from peer {} (source={}). Message discarded.",
@@ -89,17 +191,79 @@ where
             );
             return Ok(());
         }
+        message.provenance = Some(MessageProvenance::new(true));
         next_service.oneshot(message).await.map_err(Into::into)
     }
+
+    /// Returns the key used to look up the message in the signature cache, or None if the message should not be
+    /// deduplicated.
+    fn cache_key(
+        message: &DhtInboundMessage,
+        key_mode: DedupKeyMode,
+        unsigned_message_policy: UnsignedMessagePolicy,
+    ) -> Option<Vec<u8>>
+    {
+        if message.dht_header.origin_signature.is_empty() {
+            return match unsigned_message_policy {
+                UnsignedMessagePolicy::Bypass => None,
+                UnsignedMessagePolicy::ContentHash => Some(Self::content_hash(message)),
+            };
+        }
+
+        match key_mode {
+            // WARN: It is assumed that the message signature has been checked (i.e. by the DeserializeMiddleware)
+            DedupKeyMode::Signature => Some(message.dht_header.origin_signature.clone()),
+            DedupKeyMode::ContentHash => Some(Self::content_hash(message)),
+        }
+    }
+
+    /// Hash of the origin signature, origin public key and body of the message
+    fn content_hash(message: &DhtInboundMessage) -> Vec<u8> {
+        Challenge::new()
+            .chain(&message.dht_header.origin_signature)
+            .chain(message.dht_header.origin_public_key.as_bytes())
+            .chain(&message.body)
+            .result()
+            .to_vec()
+    }
 }
 
 pub struct DedupLayer {
     dht_requester: DhtRequester,
+    max_message_size: usize,
+    key_mode: DedupKeyMode,
+    unsigned_message_policy: UnsignedMessagePolicy,
+    republish_public_key: Option<CommsPublicKey>,
 }
 
 impl DedupLayer {
-    pub fn new(dht_requester: DhtRequester) -> Self {
-        Self { dht_requester }
+    pub fn new(dht_requester: DhtRequester, max_message_size: usize) -> Self {
+        Self {
+            dht_requester,
+            max_message_size,
+            key_mode: Default::default(),
+            unsigned_message_policy: Default::default(),
+            republish_public_key: None,
+        }
+    }
+
+    /// Sets the mode used to determine the signature cache key for signed messages
+    pub fn with_key_mode(mut self, key_mode: DedupKeyMode) -> Self {
+        self.key_mode = key_mode;
+        self
+    }
+
+    /// Sets the policy used to deduplicate messages which do not have an origin signature
+    pub fn with_unsigned_message_policy(mut self, unsigned_message_policy: UnsignedMessagePolicy) -> Self {
+        self.unsigned_message_policy = unsigned_message_policy;
+        self
+    }
+
+    /// Allows messages originating from the given (i.e. this node's) public key to be re-published by letting them
+    /// bypass the signature cache
+    pub fn with_republish_allowed(mut self, node_public_key: CommsPublicKey) -> Self {
+        self.republish_public_key = Some(node_public_key);
+        self
     }
 }
 
@@ -107,7 +271,14 @@ impl<S> Layer<S> for DedupLayer {
     type Service = DedupMiddleware<S>;
 
     fn layer(&self, service: S) -> Self::Service {
-        DedupMiddleware::new(service, self.dht_requester.clone())
+        DedupMiddleware::new(
+            service,
+            self.dht_requester.clone(),
+            self.max_message_size,
+            self.key_mode,
+            self.unsigned_message_policy,
+            self.republish_public_key.clone(),
+        )
     }
 }
 
@@ -118,6 +289,7 @@ mod test {
         envelope::DhtMessageFlags,
         test_utils::{create_dht_actor_mock, make_dht_inbound_message, make_node_identity, service_spy, DhtMockState},
     };
+    use chrono::Utc;
     use tari_test_utils::panic_context;
     use tokio::runtime::Runtime;
 
@@ -132,7 +304,7 @@ mod test {
         mock.set_shared_state(mock_state.clone());
         rt.spawn(mock.run());
 
-        let mut dedup = DedupLayer::new(dht_requester).layer(spy.to_service::<MiddlewareError>());
+        let mut dedup = DedupLayer::new(dht_requester, 1024).layer(spy.to_service::<MiddlewareError>());
 
         panic_context!(cx);
 
@@ -150,4 +322,171 @@ mod test {
         drop(dedup);
         rt.shutdown_on_idle();
     }
+
+    #[test]
+    fn first_seen_message_provenance() {
+        let rt = Runtime::new().unwrap();
+        let spy = service_spy();
+
+        let (dht_requester, mut mock) = create_dht_actor_mock(1);
+        let mock_state = DhtMockState::new();
+        mock_state.set_signature_cache_insert(false);
+        mock.set_shared_state(mock_state.clone());
+        rt.spawn(mock.run());
+
+        let mut dedup = DedupLayer::new(dht_requester, 1024).layer(spy.to_service::<MiddlewareError>());
+
+        let node_identity = make_node_identity();
+        let msg = make_dht_inbound_message(&node_identity, Vec::new(), DhtMessageFlags::empty());
+        assert!(msg.provenance.is_none());
+        let before = Utc::now();
+        rt.block_on(dedup.call(msg)).unwrap();
+
+        let msg = spy.pop_request().unwrap();
+        let provenance = msg.provenance.unwrap();
+        assert!(provenance.is_first_seen);
+        assert!(provenance.admitted_at >= before);
+        assert!(provenance.admitted_at <= Utc::now());
+
+        drop(dedup);
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn discard_oversized_message() {
+        let rt = Runtime::new().unwrap();
+        let spy = service_spy();
+
+        let (dht_requester, mut mock) = create_dht_actor_mock(1);
+        let mock_state = DhtMockState::new();
+        mock_state.set_signature_cache_insert(false);
+        mock.set_shared_state(mock_state.clone());
+        rt.spawn(mock.run());
+
+        let mut dedup = DedupLayer::new(dht_requester, 10).layer(spy.to_service::<MiddlewareError>());
+
+        let node_identity = make_node_identity();
+        let msg = make_dht_inbound_message(&node_identity, vec![0u8; 11], DhtMessageFlags::empty());
+        rt.block_on(dedup.call(msg)).unwrap();
+        assert_eq!(spy.call_count(), 0);
+
+        let msg = make_dht_inbound_message(&node_identity, vec![0u8; 10], DhtMessageFlags::empty());
+        rt.block_on(dedup.call(msg)).unwrap();
+        assert_eq!(spy.call_count(), 1);
+
+        drop(dedup);
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn unsigned_message_policy() {
+        let rt = Runtime::new().unwrap();
+        let spy = service_spy();
+
+        let (dht_requester, mut mock) = create_dht_actor_mock(1);
+        let mock_state = DhtMockState::new();
+        mock_state.set_signature_cache_insert(false);
+        mock.set_shared_state(mock_state.clone());
+        rt.spawn(mock.run());
+
+        let node_identity = make_node_identity();
+        let mut msg1 = make_dht_inbound_message(&node_identity, vec![1], DhtMessageFlags::empty());
+        msg1.dht_header.origin_signature = Vec::new();
+        let mut msg2 = make_dht_inbound_message(&node_identity, vec![2], DhtMessageFlags::empty());
+        msg2.dht_header.origin_signature = Vec::new();
+
+        let mut dedup = DedupLayer::new(dht_requester.clone(), 1024).layer(spy.to_service::<MiddlewareError>());
+        rt.block_on(dedup.call(msg1.clone())).unwrap();
+        rt.block_on(dedup.call(msg2.clone())).unwrap();
+        assert_eq!(spy.call_count(), 2);
+        // Each message is checked against the cache using a distinct key
+        let keys = mock_state.signature_cache_inserts();
+        assert_eq!(keys.len(), 2);
+        assert_ne!(keys[0], keys[1]);
+        assert!(keys.iter().all(|k| !k.is_empty()));
+
+        let mut dedup = DedupLayer::new(dht_requester, 1024)
+            .with_unsigned_message_policy(UnsignedMessagePolicy::Bypass)
+            .layer(spy.to_service::<MiddlewareError>());
+        // The signature cache is not consulted, even if it would report a duplicate
+        mock_state.set_signature_cache_insert(true);
+        rt.block_on(dedup.call(msg1)).unwrap();
+        rt.block_on(dedup.call(msg2)).unwrap();
+        assert_eq!(spy.call_count(), 4);
+        assert_eq!(mock_state.signature_cache_inserts().len(), 2);
+
+        drop(dedup);
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn republish_allowed() {
+        let rt = Runtime::new().unwrap();
+        let spy = service_spy();
+
+        let (dht_requester, mut mock) = create_dht_actor_mock(1);
+        let mock_state = DhtMockState::new();
+        mock_state.set_signature_cache_tracking(true);
+        mock.set_shared_state(mock_state.clone());
+        rt.spawn(mock.run());
+
+        let node_identity = make_node_identity();
+        let mut dedup = DedupLayer::new(dht_requester, 1024)
+            .with_republish_allowed(node_identity.public_key().clone())
+            .layer(spy.to_service::<MiddlewareError>());
+
+        let own_msg = make_dht_inbound_message(&node_identity, vec![1], DhtMessageFlags::empty());
+        rt.block_on(dedup.call(own_msg.clone())).unwrap();
+        rt.block_on(dedup.call(own_msg)).unwrap();
+        assert_eq!(spy.call_count(), 2);
+        assert!(mock_state.signature_cache_inserts().is_empty());
+
+        let foreign_msg = make_dht_inbound_message(&make_node_identity(), vec![1], DhtMessageFlags::empty());
+        rt.block_on(dedup.call(foreign_msg.clone())).unwrap();
+        assert_eq!(spy.call_count(), 3);
+        rt.block_on(dedup.call(foreign_msg)).unwrap();
+        assert_eq!(spy.call_count(), 3);
+        assert_eq!(mock_state.signature_cache_inserts().len(), 2);
+
+        drop(dedup);
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn content_hash_key_mode() {
+        let rt = Runtime::new().unwrap();
+        let spy = service_spy();
+
+        let (dht_requester, mut mock) = create_dht_actor_mock(1);
+        let mock_state = DhtMockState::new();
+        mock_state.set_signature_cache_tracking(true);
+        mock.set_shared_state(mock_state.clone());
+        rt.spawn(mock.run());
+
+        let node_identity = make_node_identity();
+        let msg1 = make_dht_inbound_message(&node_identity, vec![1], DhtMessageFlags::empty());
+        let mut msg2 = make_dht_inbound_message(&node_identity, vec![2], DhtMessageFlags::empty());
+        msg2.dht_header.origin_signature = msg1.dht_header.origin_signature.clone();
+
+        let mut dedup = DedupLayer::new(dht_requester.clone(), 1024)
+            .with_key_mode(DedupKeyMode::ContentHash)
+            .layer(spy.to_service::<MiddlewareError>());
+        rt.block_on(dedup.call(msg1.clone())).unwrap();
+        rt.block_on(dedup.call(msg2.clone())).unwrap();
+        assert_eq!(spy.call_count(), 2);
+        // Replaying the exact message is still detected
+        rt.block_on(dedup.call(msg1.clone())).unwrap();
+        assert_eq!(spy.call_count(), 2);
+
+        let mut dedup = DedupLayer::new(dht_requester, 1024)
+            .with_key_mode(DedupKeyMode::Signature)
+            .layer(spy.to_service::<MiddlewareError>());
+        rt.block_on(dedup.call(msg1)).unwrap();
+        assert_eq!(spy.call_count(), 3);
+        rt.block_on(dedup.call(msg2)).unwrap();
+        assert_eq!(spy.call_count(), 3);
+
+        drop(dedup);
+        rt.shutdown_on_idle();
+    }
 }
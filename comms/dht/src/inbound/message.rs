@@ -21,14 +21,36 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::{consts::DHT_ENVELOPE_HEADER_VERSION, envelope::DhtMessageHeader};
+use chrono::{DateTime, Utc};
 use tari_comms::{message::EnvelopeBody, peer_manager::Peer};
 
+/// Records how an inbound message was admitted by the deduplication middleware
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageProvenance {
+    /// True if the message was checked against the signature cache and had not been seen before, false if
+    /// deduplication was skipped for this message
+    pub is_first_seen: bool,
+    /// The time at which the message was admitted
+    pub admitted_at: DateTime<Utc>,
+}
+
+impl MessageProvenance {
+    pub fn new(is_first_seen: bool) -> Self {
+        Self {
+            is_first_seen,
+            admitted_at: Utc::now(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DhtInboundMessage {
     pub version: u32,
     pub source_peer: Peer,
     pub dht_header: DhtMessageHeader,
     pub body: Vec<u8>,
+    /// Set by the deduplication middleware once the message has been admitted
+    pub provenance: Option<MessageProvenance>,
 }
 impl DhtInboundMessage {
     pub fn new(dht_header: DhtMessageHeader, source_peer: Peer, body: Vec<u8>) -> Self {
@@ -37,6 +59,7 @@ impl DhtInboundMessage {
             dht_header,
             source_peer,
             body,
+            provenance: None,
         }
     }
 }
@@ -50,6 +73,7 @@ pub struct DecryptedDhtMessage {
     pub source_peer: Peer,
     pub dht_header: DhtMessageHeader,
     pub decryption_result: Result<EnvelopeBody, Vec<u8>>,
+    pub provenance: Option<MessageProvenance>,
 }
 
 impl DecryptedDhtMessage {
@@ -59,6 +83,7 @@ impl DecryptedDhtMessage {
             source_peer: message.source_peer,
             dht_header: message.dht_header,
             decryption_result: Ok(decrypted_message),
+            provenance: message.provenance,
         }
     }
 
@@ -68,6 +93,7 @@ impl DecryptedDhtMessage {
             source_peer: message.source_peer,
             dht_header: message.dht_header,
             decryption_result: Err(message.body),
+            provenance: message.provenance,
         }
     }
 
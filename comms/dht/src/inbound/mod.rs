@@ -29,8 +29,8 @@ mod message;
 
 pub use self::{
     decryption::DecryptionLayer,
-    dedup::DedupLayer,
+    dedup::{DedupKeyMode, DedupLayer, UnsignedMessagePolicy},
     deserialize::DeserializeLayer,
     dht_handler::DhtHandlerLayer,
-    message::{DecryptedDhtMessage, DhtInboundMessage},
+    message::{DecryptedDhtMessage, DhtInboundMessage, MessageProvenance},
 };
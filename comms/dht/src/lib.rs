@@ -123,6 +123,7 @@ mod consts;
 mod dht;
 mod discovery;
 mod proto;
+mod signature_cache;
 
 pub mod broadcast_strategy;
 pub mod domain_message;
@@ -131,7 +132,7 @@ pub mod inbound;
 pub mod outbound;
 pub mod store_forward;
 
-pub use actor::{DhtActorError, DhtRequest, DhtRequester};
+pub use actor::{DhtActorError, DhtRequest, DhtRequester, SignatureCacheDiff, SignatureCacheSnapshot};
 pub use builder::DhtBuilder;
 pub use config::DhtConfig;
 pub use dht::Dht;
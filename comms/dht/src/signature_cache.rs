@@ -0,0 +1,110 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::time::Duration;
+use ttl_cache::TtlCache;
+
+/// A cache of message signatures used to detect duplicate messages. Signatures expire after the configured TTL.
+pub struct SignatureCache {
+    cache: TtlCache<Vec<u8>, ()>,
+    ttl: Duration,
+}
+
+impl SignatureCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            cache: TtlCache::new(capacity),
+            ttl,
+        }
+    }
+
+    /// Inserts the signature into the cache, resetting its TTL. Returns true if the signature was already in the
+    /// cache, otherwise false.
+    pub fn insert(&mut self, signature: Vec<u8>) -> bool {
+        self.cache.insert(signature, (), self.ttl).is_some()
+    }
+
+    /// Returns true if the (unexpired) signature is in the cache, otherwise false
+    pub fn contains(&self, signature: &[u8]) -> bool {
+        self.cache.contains_key(signature)
+    }
+
+    /// Returns the number of unexpired signatures in the cache
+    pub fn len(&mut self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns true if the cache contains no unexpired signatures, otherwise false
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the unexpired signatures in the cache
+    pub fn iter(&mut self) -> impl Iterator<Item = &Vec<u8>> {
+        self.cache.iter().map(|(signature, _)| signature)
+    }
+
+    /// Removes all signatures from the cache
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn insert() {
+        let mut cache = SignatureCache::new(10, Duration::from_secs(60));
+        assert!(cache.is_empty());
+        assert_eq!(cache.insert(vec![1, 2, 3]), false);
+        assert_eq!(cache.insert(vec![1, 2, 3]), true);
+        assert_eq!(cache.insert(vec![4, 5, 6]), false);
+        assert!(cache.contains(&[1, 2, 3]));
+        assert!(cache.contains(&[4, 5, 6]));
+        assert!(!cache.contains(&[7, 8, 9]));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn clear() {
+        let mut cache = SignatureCache::new(10, Duration::from_secs(60));
+        cache.insert(vec![1, 2, 3]);
+        cache.insert(vec![4, 5, 6]);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(!cache.contains(&[1, 2, 3]));
+        assert_eq!(cache.insert(vec![1, 2, 3]), false);
+    }
+
+    #[test]
+    fn expiry() {
+        let mut cache = SignatureCache::new(10, Duration::from_millis(1));
+        cache.insert(vec![1, 2, 3]);
+        thread::sleep(Duration::from_millis(10));
+        assert!(!cache.contains(&[1, 2, 3]));
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.insert(vec![1, 2, 3]), false);
+    }
+}
@@ -37,6 +37,8 @@ pub fn create_dht_actor_mock(buf_size: usize) -> (DhtRequester, DhtActorMock) {
 #[derive(Default, Debug, Clone)]
 pub struct DhtMockState {
     signature_cache_insert: Arc<AtomicBool>,
+    signature_cache_inserts: Arc<RwLock<Vec<Vec<u8>>>>,
+    signature_cache_tracking: Arc<AtomicBool>,
     call_count: Arc<AtomicUsize>,
     select_peers: Arc<RwLock<Vec<Peer>>>,
 }
@@ -45,6 +47,8 @@ impl DhtMockState {
     pub fn new() -> Self {
         Self {
             signature_cache_insert: Arc::new(AtomicBool::new(false)),
+            signature_cache_inserts: Arc::new(RwLock::new(Vec::new())),
+            signature_cache_tracking: Arc::new(AtomicBool::new(false)),
             call_count: Arc::new(AtomicUsize::new(0)),
             select_peers: Arc::new(RwLock::new(Vec::new())),
         }
@@ -55,6 +59,18 @@ impl DhtMockState {
         self
     }
 
+    /// When enabled, a signature cache insert reports a duplicate if the signature has previously been inserted,
+    /// rather than returning the value set by `set_signature_cache_insert`
+    pub fn set_signature_cache_tracking(&self, v: bool) -> &Self {
+        self.signature_cache_tracking.store(v, Ordering::SeqCst);
+        self
+    }
+
+    /// Returns the signatures which have been inserted into the signature cache, in order
+    pub fn signature_cache_inserts(&self) -> Vec<Vec<u8>> {
+        acquire_read_lock!(self.signature_cache_inserts).clone()
+    }
+
     pub fn set_select_peers_response(&self, peers: Vec<Peer>) -> &Self {
         *acquire_write_lock!(self.select_peers) = peers;
         self
@@ -97,14 +113,23 @@ impl DhtActorMock {
         self.state.inc_call_count();
         match req {
             SendJoin => {},
-            SignatureCacheInsert(_, reply_tx) => {
-                let v = self.state.signature_cache_insert.load(Ordering::SeqCst);
+            SignatureCacheInsert(signature, reply_tx) => {
+                let mut inserts = acquire_write_lock!(self.state.signature_cache_inserts);
+                let v = if self.state.signature_cache_tracking.load(Ordering::SeqCst) {
+                    inserts.contains(&signature)
+                } else {
+                    self.state.signature_cache_insert.load(Ordering::SeqCst)
+                };
+                inserts.push(signature);
                 reply_tx.send(v).unwrap();
             },
             SelectPeers(_, reply_tx) => {
                 let lock = acquire_read_lock!(self.state.select_peers);
                 reply_tx.send(lock.clone()).unwrap();
             },
+            SnapshotSignatureCache(reply_tx) => {
+                reply_tx.send(Default::default()).unwrap();
+            },
             SendRequestStoredMessages(_) => {},
         }
     }
@@ -22,6 +22,8 @@
 
 use std::time::Duration;
 
+pub use tari_common::backoff::ExponentialBackoff;
+
 pub type BoxedBackoff = Box<dyn Backoff + Send + Sync>;
 
 pub trait Backoff {
@@ -34,30 +36,14 @@ impl Backoff for BoxedBackoff {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct ExponentialBackoff {
-    factor: f32,
-}
-
-impl ExponentialBackoff {
-    pub fn new(factor: f32) -> Self {
-        Self { factor }
-    }
-}
-
-impl Default for ExponentialBackoff {
-    fn default() -> Self {
-        Self::new(1.5)
-    }
-}
-
+/// No delay is given before the first attempt, and the jitter of the backoff is applied to every other delay. The
+/// maximum number of attempts of the backoff is not taken into account, as the caller decides when to stop.
 impl Backoff for ExponentialBackoff {
     fn calculate_backoff(&self, attempts: usize) -> Duration {
         if attempts == 0 {
             return Duration::from_secs(0);
         }
-        let secs = (self.factor as f64) * (f64::powf(2.0, attempts as f64) - 1.0);
-        Duration::from_secs(secs.ceil() as u64)
+        self.apply_jitter(self.delay_for_attempt(attempts - 1))
     }
 }
 
@@ -88,20 +74,25 @@ mod test {
         let backoff = ExponentialBackoff::default();
         assert_eq!(backoff.calculate_backoff(0).as_secs(), 0);
         assert_eq!(backoff.calculate_backoff(1).as_secs(), 2);
-        assert_eq!(backoff.calculate_backoff(2).as_secs(), 5);
-        assert_eq!(backoff.calculate_backoff(3).as_secs(), 11);
-        assert_eq!(backoff.calculate_backoff(4).as_secs(), 23);
-        assert_eq!(backoff.calculate_backoff(5).as_secs(), 47);
-        assert_eq!(backoff.calculate_backoff(6).as_secs(), 95);
-        assert_eq!(backoff.calculate_backoff(7).as_secs(), 191);
-        assert_eq!(backoff.calculate_backoff(8).as_secs(), 383);
-        assert_eq!(backoff.calculate_backoff(9).as_secs(), 767);
-        assert_eq!(backoff.calculate_backoff(10).as_secs(), 1535);
+        assert_eq!(backoff.calculate_backoff(2).as_secs(), 4);
+        assert_eq!(backoff.calculate_backoff(3).as_secs(), 8);
+        assert_eq!(backoff.calculate_backoff(10).as_secs(), 1024);
+        assert_eq!(backoff.calculate_backoff(20).as_secs(), 3600);
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let backoff = ExponentialBackoff::new(Duration::from_secs(1), 2.0, Duration::from_secs(5));
+        assert_eq!(backoff.calculate_backoff(0), Duration::from_secs(0));
+        assert_eq!(backoff.calculate_backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff.calculate_backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff.calculate_backoff(3), Duration::from_secs(4));
+        assert_eq!(backoff.calculate_backoff(4), Duration::from_secs(5));
     }
 
     #[test]
     fn zero_backoff() {
-        let backoff = ExponentialBackoff::new(0.0);
+        let backoff = ExponentialBackoff::new(Duration::from_secs(0), 2.0, Duration::from_secs(60));
         assert_eq!(backoff.calculate_backoff(0).as_secs(), 0);
         assert_eq!(backoff.calculate_backoff(1).as_secs(), 0);
         assert_eq!(backoff.calculate_backoff(200).as_secs(), 0);
@@ -0,0 +1,98 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use crate::peer_manager::NodeId;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Keeps track of peers which are banned, and until when they are banned. Expired bans are removed lazily when they are
+/// checked.
+#[derive(Debug, Default)]
+pub struct BannedPeers {
+    bans: HashMap<NodeId, Instant>,
+}
+
+impl BannedPeers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Ban the peer for the given duration from now. A subsequent ban for the same peer replaces this one.
+    pub fn ban(&mut self, node_id: NodeId, duration: Duration) {
+        self.ban_until(node_id, Instant::now() + duration);
+    }
+
+    /// Ban the peer until the given instant
+    pub fn ban_until(&mut self, node_id: NodeId, until: Instant) {
+        self.bans.insert(node_id, until);
+    }
+
+    /// Returns true if the peer is currently banned
+    pub fn is_banned(&mut self, node_id: &NodeId) -> bool {
+        self.is_banned_at(node_id, Instant::now())
+    }
+
+    /// Returns true if the peer is banned at the given instant. The ban is removed if it has expired by `now`.
+    pub fn is_banned_at(&mut self, node_id: &NodeId, now: Instant) -> bool {
+        match self.bans.get(node_id) {
+            Some(until) if now < *until => true,
+            Some(_) => {
+                self.bans.remove(node_id);
+                false
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ban_expiry() {
+        let mut banned_peers = BannedPeers::new();
+        let node_id = NodeId::default();
+        let now = Instant::now();
+        banned_peers.ban_until(node_id.clone(), now + Duration::from_secs(60));
+
+        assert!(banned_peers.is_banned_at(&node_id, now));
+        assert!(banned_peers.is_banned_at(&node_id, now + Duration::from_secs(59)));
+        assert!(!banned_peers.is_banned_at(&node_id, now + Duration::from_secs(60)));
+        // The expired ban was removed
+        assert!(!banned_peers.is_banned_at(&node_id, now));
+    }
+
+    #[test]
+    fn ban_replaced() {
+        let mut banned_peers = BannedPeers::new();
+        let node_id = NodeId::default();
+        let now = Instant::now();
+        banned_peers.ban_until(node_id.clone(), now + Duration::from_secs(60));
+        banned_peers.ban_until(node_id.clone(), now + Duration::from_secs(10));
+
+        assert!(banned_peers.is_banned_at(&node_id, now + Duration::from_secs(5)));
+        assert!(!banned_peers.is_banned_at(&node_id, now + Duration::from_secs(10)));
+    }
+}
@@ -28,10 +28,12 @@ use crate::{
         dial_state::DialState,
         manager::{ConnectionManagerConfig, ConnectionManagerEvent},
         peer_connection::create_peer_connection,
+        reputation::PeerReputation,
         utils::short_str,
     },
     multiaddr::Multiaddr,
-    peer_manager::{Peer, PeerId},
+    noise::NoiseError,
+    peer_manager::{NodeId, Peer, PeerId},
     transports::Transport,
     types::CommsPublicKey,
 };
@@ -47,7 +49,7 @@ use futures::{
     StreamExt,
 };
 use log::*;
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{collections::HashMap, io, sync::Arc, time::Instant};
 use tari_shutdown::{Shutdown, ShutdownSignal};
 use tokio::{runtime::TaskExecutor, timer};
 
@@ -66,6 +68,7 @@ pub struct Dialer<TTransport, TBackoff> {
     config: ConnectionManagerConfig,
     transport: TTransport,
     backoff: Arc<TBackoff>,
+    reputation: PeerReputation,
     request_rx: Fuse<mpsc::Receiver<DialerRequest>>,
     cancel_signals: HashMap<PeerId, Shutdown>,
     conn_man_notifier: mpsc::Sender<ConnectionManagerEvent>,
@@ -76,6 +79,7 @@ pub struct Dialer<TTransport, TBackoff> {
 impl<TTransport, TSocket, TBackoff> Dialer<TTransport, TBackoff>
 where
     TTransport: Transport<Output = (TSocket, CommsPublicKey, Multiaddr)> + Unpin + Send + Sync + Clone + 'static,
+    TTransport::Error: Into<io::Error>,
     TSocket: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
     TBackoff: Backoff + Send + Sync + 'static,
 {
@@ -84,6 +88,7 @@ where
         config: ConnectionManagerConfig,
         transport: TTransport,
         backoff: Arc<TBackoff>,
+        reputation: PeerReputation,
         request_rx: mpsc::Receiver<DialerRequest>,
         conn_man_notifier: mpsc::Sender<ConnectionManagerEvent>,
         shutdown: ShutdownSignal,
@@ -94,6 +99,7 @@ where
             config,
             transport,
             backoff,
+            reputation,
             request_rx: request_rx.fuse(),
             cancel_signals: Default::default(),
             conn_man_notifier,
@@ -281,16 +287,19 @@ where
         self.cancel_signals.insert(peer.id(), dial_cancel);
 
         let backoff = Arc::clone(&self.backoff);
+        let reputation = self.reputation.clone();
         let max_attempts = self.config.max_dial_attempts;
 
         let dial_state = DialState::new(peer, reply_tx, cancel_signal);
-        pending_dials.push(Self::dial_peer_with_retry(dial_state, transport, backoff, max_attempts).boxed());
+        pending_dials
+            .push(Self::dial_peer_with_retry(dial_state, transport, backoff, reputation, max_attempts).boxed());
     }
 
     async fn dial_peer_with_retry(
         dial_state: DialState,
         transport: TTransport,
         backoff: Arc<TBackoff>,
+        reputation: PeerReputation,
         max_attempts: usize,
     ) -> Option<(DialState, DialResult<TTransport::Output>)>
     {
@@ -314,13 +323,15 @@ where
             futures::select! {
                 _ = delay => {
                     debug!(target: LOG_TARGET, "[Attempt {}] Connecting to peer '{}'", current_state.num_attempts(), current_state.peer.node_id.short_str());
-                    match Self::dial_peer(current_state, current_transport).await {
+                    match Self::dial_peer(current_state, current_transport, &reputation).await {
                         Some((state, _, Ok(socket_and_address))) => {
                             break Some((state, Ok(socket_and_address)));
                         },
                         Some((mut state, t, Err(err))) => {
+                            debug!(target: LOG_TARGET, "[Attempt {}] Failed to connect to peer '{}' because '{}'", state.num_attempts(), state.peer.node_id.short_str(), err);
                             if state.num_attempts() > max_attempts {
-                                break Some((state, Err(ConnectionManagerError::ConnectFailedMaximumAttemptsReached)));
+                                // Report why the last attempt failed, rather than only that the attempts ran out
+                                break Some((state, Err(err)));
                             }
 
                             state.inc_attempts();
@@ -341,23 +352,36 @@ where
         }
     }
 
-    /// Attempts to dial a peer sequentially on all addresses.
+    /// Attempts to dial a peer sequentially on all addresses. Addresses are ordered by their dial success ratio, and
+    /// the transport's order (e.g. by address family) is only used between addresses with equal ratios. The outcome of
+    /// each address dial is recorded in the given `PeerReputation`.
     /// Returns ownership of the given `DialState` and a success or failure result for the dial,
-    /// or None if the dial was cancelled inflight
+    /// or None if the dial was cancelled inflight. If every address failed, the error of the last failed address is
+    /// returned as a `HandshakeFailed` error if the transport connected but the noise handshake failed, otherwise as a
+    /// `DialIoError`.
     async fn dial_peer(
         dial_state: DialState,
         transport: TTransport,
+        reputation: &PeerReputation,
     ) -> Option<(DialState, TTransport, DialResult<TTransport::Output>)>
     {
-        let mut addr_iter = dial_state.peer.addresses.address_iter();
+        let node_id = dial_state.peer.node_id.clone();
+        let addresses = Self::order_dial_addresses(&transport, reputation, &dial_state.peer);
+        let mut addr_iter = addresses.iter();
         let cancel_signal = dial_state.get_cancel_signal();
+        let mut last_err = None;
         loop {
             let result = match addr_iter.next() {
                 Some(address) => {
                     let either = future::select(transport.dial(address.clone()), cancel_signal.clone()).await;
                     match either {
-                        Either::Left((Ok((socket, public_key, peer_addr)), _)) => Ok((socket, public_key, peer_addr)),
+                        Either::Left((Ok((socket, public_key, peer_addr)), _)) => {
+                            reputation.record_success(&node_id, address);
+                            Ok((socket, public_key, peer_addr))
+                        },
                         Either::Left((Err(err), _)) => {
+                            let err: io::Error = err.into();
+                            reputation.record_failure(&node_id, address);
                             debug!(
                                 target: LOG_TARGET,
                                 "(Attempt {}) Dial failed on address '{}' for peer '{}' because '{}'",
@@ -366,6 +390,7 @@ where
                                 dial_state.peer.node_id.short_str(),
                                 err,
                             );
+                            last_err = Some(Self::classify_dial_error(&node_id, err));
                             // Try the next address
                             continue;
                         },
@@ -386,7 +411,9 @@ where
                     }
                 },
                 // No more addresses to try - returning failure
-                None => Err(ConnectionManagerError::DialConnectFailedAllAddresses),
+                None => Err(last_err
+                    .take()
+                    .unwrap_or(ConnectionManagerError::DialConnectFailedAllAddresses)),
             };
 
             drop(addr_iter);
@@ -394,4 +421,212 @@ where
             break Some((dial_state, transport, result));
         }
     }
+
+    /// Returns the addresses of the peer in the order in which they should be dialed. The transport's preferred order
+    /// is applied first and the addresses are then stably sorted by success ratio, so that a preference (e.g. for an
+    /// address family) never overrides the reputation of an address.
+    fn order_dial_addresses(transport: &TTransport, reputation: &PeerReputation, peer: &Peer) -> Vec<Multiaddr> {
+        let addresses = transport.order_dial_addresses(peer.addresses.address_iter().cloned().collect());
+        reputation.order_addresses(&peer.node_id, &addresses)
+    }
+
+    /// Distinguishes a failed noise handshake on an established transport connection from a transport-level failure
+    fn classify_dial_error(node_id: &NodeId, err: io::Error) -> ConnectionManagerError {
+        let is_handshake_error = err.get_ref().map(|inner| inner.is::<NoiseError>()).unwrap_or(false);
+        if is_handshake_error {
+            ConnectionManagerError::HandshakeFailed(node_id.clone(), err.to_string())
+        } else {
+            err.into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        backoff::ConstantBackoff,
+        noise::NoiseConfig,
+        peer_manager::{PeerFeatures, PeerFlags},
+        test_utils::node_identity::build_node_identity,
+        transports::{NoiseTransport, TcpTransport},
+    };
+    use std::time::Duration;
+    use tari_test_utils::unpack_enum;
+    use tokio::{net::TcpListener, runtime::Runtime};
+
+    fn build_peer(address: Multiaddr) -> Peer {
+        let node_identity = build_node_identity(PeerFeatures::COMMUNICATION_NODE);
+        Peer::new(
+            node_identity.public_key().clone(),
+            node_identity.node_id().clone(),
+            address.into(),
+            PeerFlags::default(),
+            PeerFeatures::COMMUNICATION_NODE,
+        )
+    }
+
+    #[test]
+    fn dial_refused_preserves_io_error_kind() {
+        let rt = Runtime::new().unwrap();
+        // Bind to a free port and drop the listener, so that connections to the port are refused
+        let port = rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        });
+        let address = format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().unwrap();
+        let peer = build_peer(address);
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (reply_tx, _reply_rx) = oneshot::channel();
+        let cancel = Shutdown::new();
+        let dial_state = DialState::new(peer, reply_tx, cancel.to_signal());
+        let reputation = PeerReputation::new();
+
+        let (_, _, result) = rt
+            .block_on(Dialer::<_, ConstantBackoff>::dial_peer(
+                dial_state,
+                transport,
+                &reputation,
+            ))
+            .unwrap();
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::DialIoError(kind, _msg) = err);
+        assert_eq!(kind, io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn dial_with_retry_preserves_io_error_kind() {
+        let rt = Runtime::new().unwrap();
+        let port = rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        });
+        let address = format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().unwrap();
+        let peer = build_peer(address);
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (reply_tx, _reply_rx) = oneshot::channel();
+        let cancel = Shutdown::new();
+        let dial_state = DialState::new(peer, reply_tx, cancel.to_signal());
+        let backoff = Arc::new(ConstantBackoff::new(Duration::from_millis(10)));
+
+        let (state, result) = rt
+            .block_on(Dialer::<_, ConstantBackoff>::dial_peer_with_retry(
+                dial_state,
+                transport,
+                backoff,
+                PeerReputation::new(),
+                1,
+            ))
+            .unwrap();
+        assert!(state.num_attempts() > 1);
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::DialIoError(kind, _msg) = err);
+        assert_eq!(kind, io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn reputation_overrides_address_family_preference() {
+        let ipv4_address = "/ip4/127.0.0.1/tcp/8000".parse::<Multiaddr>().unwrap();
+        let ipv6_address = "/ip6/::1/tcp/8001".parse::<Multiaddr>().unwrap();
+        let mut peer = build_peer(ipv4_address.clone());
+        peer.addresses = vec![ipv4_address.clone(), ipv6_address.clone()].into();
+        // IPv6 addresses are preferred by default
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let reputation = PeerReputation::new();
+
+        // Without any history, the address family preference breaks the tie
+        let addresses = Dialer::<_, ConstantBackoff>::order_dial_addresses(&transport, &reputation, &peer);
+        assert_eq!(addresses, vec![ipv6_address.clone(), ipv4_address.clone()]);
+
+        // A failing address of the preferred family is dialed last
+        reputation.record_failure(&peer.node_id, &ipv6_address);
+        let addresses = Dialer::<_, ConstantBackoff>::order_dial_addresses(&transport, &reputation, &peer);
+        assert_eq!(addresses, vec![ipv4_address.clone(), ipv6_address.clone()]);
+
+        // Once both addresses have the same success ratio, the preference applies again
+        reputation.record_failure(&peer.node_id, &ipv4_address);
+        let addresses = Dialer::<_, ConstantBackoff>::order_dial_addresses(&transport, &reputation, &peer);
+        assert_eq!(addresses, vec![ipv6_address, ipv4_address]);
+    }
+
+    #[test]
+    fn dial_handshake_failure() {
+        let rt = Runtime::new().unwrap();
+        // Accept the TCP connection and close it immediately, so that the noise handshake fails
+        let mut listener = rt.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        rt.spawn(async move {
+            let socket = listener.incoming().next().await;
+            drop(socket);
+        });
+        let address = format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().unwrap();
+        let peer = build_peer(address);
+        let node_id = peer.node_id.clone();
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (reply_tx, _reply_rx) = oneshot::channel();
+        let cancel = Shutdown::new();
+        let dial_state = DialState::new(peer, reply_tx, cancel.to_signal());
+        let reputation = PeerReputation::new();
+
+        let (_, _, result) = rt
+            .block_on(Dialer::<_, ConstantBackoff>::dial_peer(
+                dial_state,
+                transport,
+                &reputation,
+            ))
+            .unwrap();
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::HandshakeFailed(failed_node_id, _msg) = err);
+        assert_eq!(failed_node_id, node_id);
+    }
+    #[test]
+    fn dial_with_retry_preserves_handshake_failure() {
+        let rt = Runtime::new().unwrap();
+        // Close every accepted connection, so that the noise handshake fails on each attempt
+        let mut listener = rt.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        rt.spawn(async move {
+            let mut incoming = listener.incoming();
+            while let Some(socket) = incoming.next().await {
+                drop(socket);
+            }
+        });
+        let address = format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().unwrap();
+        let peer = build_peer(address);
+        let node_id = peer.node_id.clone();
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (reply_tx, _reply_rx) = oneshot::channel();
+        let cancel = Shutdown::new();
+        let dial_state = DialState::new(peer, reply_tx, cancel.to_signal());
+        let backoff = Arc::new(ConstantBackoff::new(Duration::from_millis(10)));
+
+        let (state, result) = rt
+            .block_on(Dialer::<_, ConstantBackoff>::dial_peer_with_retry(
+                dial_state,
+                transport,
+                backoff,
+                PeerReputation::new(),
+                1,
+            ))
+            .unwrap();
+        assert!(state.num_attempts() > 1);
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::HandshakeFailed(failed_node_id, _msg) = err);
+        assert_eq!(failed_node_id, node_id);
+    }
 }
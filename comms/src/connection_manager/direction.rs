@@ -0,0 +1,109 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use crate::connection::ConnectionDirection;
+use std::sync::{Arc, RwLock};
+
+/// The directions in which the connection manager allows connections to be established
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDirectionPolicy {
+    /// Accept inbound connections and dial outbound connections
+    Both,
+    /// Dial outbound connections, but reject all inbound connections. This is useful for a node behind a restrictive
+    /// firewall.
+    OutboundOnly,
+    /// Accept inbound connections, but do not dial peers. This is useful for a public relay node.
+    InboundOnly,
+}
+
+impl ConnectionDirectionPolicy {
+    /// Returns true if connections in the given direction are allowed by this policy
+    pub fn allows(self, direction: ConnectionDirection) -> bool {
+        use ConnectionDirectionPolicy::*;
+        match (self, direction) {
+            (Both, _) => true,
+            (OutboundOnly, ConnectionDirection::Outbound) => true,
+            (InboundOnly, ConnectionDirection::Inbound) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for ConnectionDirectionPolicy {
+    fn default() -> Self {
+        ConnectionDirectionPolicy::Both
+    }
+}
+
+/// The `ConnectionDirectionPolicy` shared between the connection manager and the listener. Clones of this struct share
+/// the same underlying policy.
+#[derive(Debug, Clone, Default)]
+pub struct SharedDirectionPolicy {
+    inner: Arc<RwLock<ConnectionDirectionPolicy>>,
+}
+
+impl SharedDirectionPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the current policy
+    pub fn get(&self) -> ConnectionDirectionPolicy {
+        *self.inner.read().expect("SharedDirectionPolicy lock poisoned")
+    }
+
+    /// Replace the current policy
+    pub fn set(&self, policy: ConnectionDirectionPolicy) {
+        *self.inner.write().expect("SharedDirectionPolicy lock poisoned") = policy;
+    }
+
+    /// Returns true if connections in the given direction are allowed by the current policy
+    pub fn allows(&self, direction: ConnectionDirection) -> bool {
+        self.get().allows(direction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allows() {
+        use ConnectionDirection::*;
+        assert!(ConnectionDirectionPolicy::Both.allows(Inbound));
+        assert!(ConnectionDirectionPolicy::Both.allows(Outbound));
+        assert!(!ConnectionDirectionPolicy::OutboundOnly.allows(Inbound));
+        assert!(ConnectionDirectionPolicy::OutboundOnly.allows(Outbound));
+        assert!(ConnectionDirectionPolicy::InboundOnly.allows(Inbound));
+        assert!(!ConnectionDirectionPolicy::InboundOnly.allows(Outbound));
+    }
+
+    #[test]
+    fn shared_policy() {
+        let policy = SharedDirectionPolicy::new();
+        assert_eq!(policy.get(), ConnectionDirectionPolicy::Both);
+        let cloned = policy.clone();
+        cloned.set(ConnectionDirectionPolicy::OutboundOnly);
+        assert_eq!(policy.get(), ConnectionDirectionPolicy::OutboundOnly);
+        assert!(!policy.allows(ConnectionDirection::Inbound));
+    }
+}
@@ -20,8 +20,9 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use crate::peer_manager::PeerManagerError;
+use crate::peer_manager::{NodeId, PeerManagerError};
 use derive_error::Error;
+use std::io;
 
 #[derive(Debug, Error, Clone)]
 pub enum ConnectionManagerError {
@@ -36,15 +37,36 @@ pub enum ConnectionManagerError {
     DialReplyChannelClosed,
     /// Failed to connect on all addresses for peer
     DialConnectFailedAllAddresses,
-    /// Failed to connect to peer within the maximum number of attempts
-    ConnectFailedMaximumAttemptsReached,
     /// Failed to perform yamux upgrade on socket
     #[error(msg_embedded, no_from, non_std)]
     YamuxUpgradeFailure(String),
+    /// Yamux connection error
+    #[error(msg_embedded, no_from, non_std)]
+    YamuxConnectionError(String),
     /// Establisher channel is closed or full
     EstablisherChannelError,
     #[error(msg_embedded, no_from, non_std)]
     TransportError(String),
+    /// The transport failed to dial the peer. The `io::ErrorKind` and message of the transport error are preserved.
+    #[error(no_from, non_std)]
+    DialIoError(io::ErrorKind, String),
+    /// The transport connected to the peer, but the subsequent noise handshake failed. This indicates that the address
+    /// is reachable but the peer is misbehaving or is not a compatible node.
+    #[error(no_from, non_std)]
+    HandshakeFailed(NodeId, String),
     /// The peer authenticated to a public key which did not match the dialed peer's public key
     DialedPublicKeyMismatch,
+    /// The peer is banned
+    PeerBanned,
+    /// The connection direction policy of the connection manager does not allow peers to be dialed
+    OutboundConnectionsDisabled,
+    /// The request could not be sent to the connection manager within the request timeout because the request
+    /// channel was full
+    RequestTimeout,
+}
+
+impl From<io::Error> for ConnectionManagerError {
+    fn from(err: io::Error) -> Self {
+        ConnectionManagerError::DialIoError(err.kind(), err.to_string())
+    }
 }
@@ -20,7 +20,7 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use super::error::ConnectionManagerError;
+use super::{direction::SharedDirectionPolicy, error::ConnectionManagerError};
 use crate::{
     connection::ConnectionDirection,
     connection_manager::{next::ConnectionManagerEvent, peer_connection::create_peer_connection},
@@ -42,6 +42,7 @@ pub struct PeerListener<TTransport> {
     shutdown_signal: Option<ShutdownSignal>,
     transport: TTransport,
     transport_address: Option<Multiaddr>,
+    direction_policy: SharedDirectionPolicy,
 }
 
 impl<TTransport, TSocket> PeerListener<TTransport>
@@ -54,6 +55,7 @@ where
         listen_address: Multiaddr,
         transport: TTransport,
         event_tx: mpsc::Sender<ConnectionManagerEvent>,
+        direction_policy: SharedDirectionPolicy,
         shutdown_signal: ShutdownSignal,
     ) -> Self
     {
@@ -64,6 +66,7 @@ where
             conn_man_notifier: event_tx,
             shutdown_signal: Some(shutdown_signal),
             transport_address: None,
+            direction_policy,
         }
     }
 
@@ -83,6 +86,11 @@ where
                     futures::select! {
                         inbound_result = inbound.select_next_some() => {
                             if let Some(inbound_future) = log_if_error!(target: LOG_TARGET, inbound_result, "Inbound connection failed because '{error}'",) {
+                                if !self.direction_policy.allows(ConnectionDirection::Inbound) {
+                                    // Dropping the inbound future closes the socket before the transport upgrade
+                                    debug!(target: LOG_TARGET, "Rejecting inbound connection because inbound connections are not allowed by the direction policy");
+                                    continue;
+                                }
                                 // TODO: Add inbound_future to FuturesUnordered stream to allow multiple peers to connect simultaneously
                                 if let Some((socket, public_key, peer_addr)) = log_if_error!(target: LOG_TARGET, inbound_future.await,  "Inbound connection failed because '{error}'",) {
                                     self.handle_inbound_connection(socket, public_key, peer_addr).await;
@@ -152,3 +160,82 @@ where
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        connection_manager::direction::ConnectionDirectionPolicy,
+        noise::NoiseConfig,
+        peer_manager::PeerFeatures,
+        test_utils::node_identity::build_node_identity,
+        transports::{NoiseTransport, TcpSocket, TcpTransport},
+    };
+    use std::{
+        io,
+        time::{Duration, Instant},
+    };
+    use tari_shutdown::Shutdown;
+    use tokio::{future::FutureExt, net::TcpListener, runtime::Runtime, timer};
+
+    fn build_transport() -> NoiseTransport<TcpTransport, TcpSocket> {
+        NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        )
+    }
+
+    #[test]
+    fn inbound_rejected_in_outbound_only_mode() {
+        let rt = Runtime::new().unwrap();
+        // Bind to a free port and drop the listener, so that the peer listener can listen on the port
+        let port = rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        });
+        let address = format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().unwrap();
+        let (event_tx, mut event_rx) = mpsc::channel(1);
+        let direction_policy = SharedDirectionPolicy::new();
+        direction_policy.set(ConnectionDirectionPolicy::OutboundOnly);
+        let mut shutdown = Shutdown::new();
+        let listener = PeerListener::new(
+            rt.executor(),
+            address.clone(),
+            build_transport(),
+            event_tx,
+            direction_policy.clone(),
+            shutdown.to_signal(),
+        );
+        rt.spawn(listener.run());
+
+        let transport = build_transport();
+        let result = rt.block_on(async {
+            // Retry until the listener is accepting connections
+            loop {
+                match transport.dial(address.clone()).await {
+                    Err(ref err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                        timer::delay(Instant::now() + Duration::from_millis(10)).await;
+                    },
+                    result => break result,
+                }
+            }
+        });
+        // The listener closed the socket before the noise handshake completed
+        assert!(result.is_err());
+        let result = rt.block_on(event_rx.next().timeout(Duration::from_millis(100)));
+        assert!(result.is_err());
+
+        direction_policy.set(ConnectionDirectionPolicy::Both);
+        assert!(rt.block_on(transport.dial(address)).is_ok());
+        let event = rt
+            .block_on(event_rx.next().timeout(Duration::from_secs(5)))
+            .unwrap()
+            .unwrap();
+        match event {
+            ConnectionManagerEvent::PeerConnected(_) => {},
+            _ => panic!("Unexpected event. Expected `ConnectionManagerEvent::PeerConnected`"),
+        }
+
+        shutdown.trigger().unwrap();
+    }
+}
@@ -23,11 +23,15 @@
 use super::dialer::DialerRequest;
 use crate::{
     backoff::Backoff,
+    connection::ConnectionDirection,
     connection_manager::{
+        ban::BannedPeers,
         dialer::Dialer,
+        direction::SharedDirectionPolicy,
         error::ConnectionManagerError,
         listener::PeerListener,
         peer_connection::PeerConnection,
+        reputation::PeerReputation,
         requester::ConnectionManagerRequest,
     },
     peer_manager::{AsyncPeerManager, NodeId},
@@ -36,26 +40,45 @@ use crate::{
 };
 use futures::{
     channel::{mpsc, oneshot},
-    stream::Fuse,
+    future::BoxFuture,
+    stream::{Fuse, FuturesUnordered},
     AsyncRead,
     AsyncWrite,
+    FutureExt,
+    SinkExt,
     StreamExt,
 };
 use log::*;
 use multiaddr::Multiaddr;
-use std::{collections::HashMap, sync::Arc};
-use tari_shutdown::ShutdownSignal;
-use tokio::runtime::TaskExecutor;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tari_shutdown::{Shutdown, ShutdownSignal};
+use tokio::{runtime::TaskExecutor, timer};
 
 const LOG_TARGET: &str = "comms::connection_manager::manager";
 
 const EVENT_CHANNEL_SIZE: usize = 32;
 const ESTABLISHER_CHANNEL_SIZE: usize = 32;
+const CONNECTIVITY_EVENT_CHANNEL_SIZE: usize = 32;
 
 pub enum ConnectionManagerEvent {
     PeerConnected(Box<PeerConnection>),
     PeerDisconnected(Box<CommsPublicKey>),
     PeerConnectFailed(Box<CommsPublicKey>, ConnectionManagerError),
+    PeerInboundSubstream(Box<CommsPublicKey>, yamux::Stream),
+}
+
+/// Events published on the connectivity event stream of the connection manager
+#[derive(Debug, Clone)]
+pub enum ConnectivityEvent {
+    /// A scheduled reconnect to the peer succeeded
+    ReconnectSucceeded(Box<NodeId>),
+    /// A scheduled reconnect to the peer failed
+    ReconnectFailed(Box<NodeId>, ConnectionManagerError),
 }
 
 #[derive(Debug, Clone)]
@@ -87,12 +110,22 @@ pub struct ConnectionManager<TTransport, TBackoff> {
     listener: Option<PeerListener<TTransport>>,
     peer_manager: AsyncPeerManager,
     active_connections: HashMap<NodeId, PeerConnection>,
+    reputation: PeerReputation,
+    banned_peers: BannedPeers,
+    direction_policy: SharedDirectionPolicy,
+    scheduled_reconnects: HashSet<NodeId>,
+    pending_reconnects: FuturesUnordered<BoxFuture<'static, NodeId>>,
+    connectivity_tx: mpsc::Sender<ConnectivityEvent>,
+    connectivity_rx: Option<mpsc::Receiver<ConnectivityEvent>>,
     shutdown_signal: Option<ShutdownSignal>,
+    /// Shutdown for the dialer and listener. This is triggered when the connection manager shuts down.
+    internal_shutdown: Shutdown,
 }
 
 impl<TTransport, TSocket, TBackoff> ConnectionManager<TTransport, TBackoff>
 where
     TTransport: Transport<Output = (TSocket, CommsPublicKey, Multiaddr)> + Unpin + Send + Sync + Clone + 'static,
+    TTransport::Error: Into<io::Error>,
     TSocket: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
     TBackoff: Backoff + Send + Sync + 'static,
 {
@@ -107,16 +140,21 @@ where
     ) -> Self
     {
         let (event_tx, event_rx) = mpsc::channel(EVENT_CHANNEL_SIZE);
+        let (connectivity_tx, connectivity_rx) = mpsc::channel(CONNECTIVITY_EVENT_CHANNEL_SIZE);
+        let internal_shutdown = Shutdown::new();
 
+        let reputation = PeerReputation::new();
+        let direction_policy = SharedDirectionPolicy::new();
         let (establisher_tx, establisher_rx) = mpsc::channel(ESTABLISHER_CHANNEL_SIZE);
         let establisher = Dialer::new(
             executor.clone(),
             config.clone(),
             transport.clone(),
             backoff,
+            reputation.clone(),
             establisher_rx,
             event_tx.clone(),
-            shutdown_signal.clone(),
+            internal_shutdown.to_signal(),
         );
 
         let listener = PeerListener::new(
@@ -124,7 +162,8 @@ where
             config.listener_address.clone(),
             transport,
             event_tx,
-            shutdown_signal.clone(),
+            direction_policy.clone(),
+            internal_shutdown.to_signal(),
         );
 
         Self {
@@ -138,9 +177,23 @@ where
             establisher: Some(establisher),
             listener: Some(listener),
             active_connections: Default::default(),
+            reputation,
+            banned_peers: BannedPeers::new(),
+            direction_policy,
+            scheduled_reconnects: HashSet::new(),
+            pending_reconnects: FuturesUnordered::new(),
+            connectivity_tx,
+            connectivity_rx: Some(connectivity_rx),
+            internal_shutdown,
         }
     }
 
+    /// Returns the connectivity event stream, on which the results of scheduled reconnects are published. The stream
+    /// can only be taken once, after which `None` is returned.
+    pub fn take_connectivity_events(&mut self) -> Option<mpsc::Receiver<ConnectivityEvent>> {
+        self.connectivity_rx.take()
+    }
+
     pub async fn run(mut self) {
         let mut shutdown = self
             .shutdown_signal
@@ -154,19 +207,43 @@ where
         loop {
             futures::select! {
                 event = self.event_rx.select_next_some() => {
-                    unimplemented!();
+                    self.handle_event(event);
                 },
 
                 request = self.request_rx.select_next_some() => {
                     self.handle_request(request).await;
                 },
 
+                node_id = self.pending_reconnects.select_next_some() => {
+                    self.reconnect(node_id).await;
+                },
+
                 _ = shutdown => {
                     info!(target: LOG_TARGET, "ConnectionManager is shutting down because it received the shutdown signal");
                     break;
                 }
             }
+
+            if self.internal_shutdown.is_triggered() {
+                info!(
+                    target: LOG_TARGET,
+                    "ConnectionManager is shutting down because a shutdown was requested"
+                );
+                break;
+            }
         }
+
+        self.shutdown_services();
+    }
+
+    /// Signal the dialer and listener to shut down
+    fn shutdown_services(&mut self) {
+        log_if_error_fmt!(
+            level: warn,
+            target: LOG_TARGET,
+            self.internal_shutdown.trigger(),
+            "Failed to trigger shutdown for the dialer and listener",
+        );
     }
 
     fn run_listener(&mut self) {
@@ -190,20 +267,169 @@ where
     async fn handle_request(&mut self, request: ConnectionManagerRequest) {
         use ConnectionManagerRequest::*;
         match request {
-            DialPeer(node_id, reply_tx) => match self.get_active_connection(&node_id) {
-                Some(conn) => {
+            DialPeer(node_id, reply_tx) => {
+                if self.banned_peers.is_banned(&node_id) {
+                    debug!(
+                        target: LOG_TARGET,
+                        "Refusing to dial banned peer '{}'",
+                        node_id.short_str()
+                    );
                     log_if_error_fmt!(
                         target: LOG_TARGET,
-                        reply_tx.send(Ok(conn.clone())),
+                        reply_tx.send(Err(ConnectionManagerError::PeerBanned)),
                         "Failed to send reply for dial request for peer '{}'",
                         node_id.short_str()
                     );
+                    return;
+                }
+
+                match self.get_active_connection(&node_id) {
+                    Some(conn) => {
+                        log_if_error_fmt!(
+                            target: LOG_TARGET,
+                            reply_tx.send(Ok(conn.clone())),
+                            "Failed to send reply for dial request for peer '{}'",
+                            node_id.short_str()
+                        );
+                    },
+                    None => self.dial_peer(node_id, reply_tx).await,
+                }
+            },
+            BanPeer(node_id, duration, reply_tx) => {
+                self.ban_peer(node_id.clone(), duration).await;
+                log_if_error_fmt!(
+                    target: LOG_TARGET,
+                    reply_tx.send(Ok(())),
+                    "Failed to send reply for ban request for peer '{}'",
+                    node_id.short_str()
+                );
+            },
+            ResetPeerReputation(node_id) => {
+                debug!(
+                    target: LOG_TARGET,
+                    "Resetting address reputation for peer '{}'",
+                    node_id.short_str()
+                );
+                self.reputation.reset(&node_id);
+            },
+            ScheduleReconnect(node_id, delay) => self.schedule_reconnect(node_id, delay),
+            SetDirectionPolicy(policy, reply_tx) => {
+                info!(
+                    target: LOG_TARGET,
+                    "Setting connection direction policy to {:?}", policy
+                );
+                self.direction_policy.set(policy);
+                log_if_error_fmt!(
+                    target: LOG_TARGET,
+                    reply_tx.send(()),
+                    "Failed to send reply for set direction policy request",
+                );
+            },
+            ConnectionManagerRequest::Shutdown => self.shutdown_services(),
+        }
+    }
+
+    fn handle_event(&mut self, event: ConnectionManagerEvent) {
+        use ConnectionManagerEvent::*;
+        match event {
+            PeerConnected(conn) => match NodeId::from_key(conn.peer_public_key()) {
+                Ok(node_id) => {
+                    debug!(target: LOG_TARGET, "Peer '{}' connected", node_id.short_str());
+                    self.active_connections.insert(node_id, *conn);
                 },
-                None => self.dial_peer(node_id, reply_tx).await,
+                Err(err) => warn!(
+                    target: LOG_TARGET,
+                    "Ignoring connection from peer with an invalid public key: {:?}", err
+                ),
+            },
+            PeerDisconnected(public_key) => {
+                if let Ok(node_id) = NodeId::from_key(&*public_key) {
+                    debug!(target: LOG_TARGET, "Peer '{}' disconnected", node_id.short_str());
+                    self.active_connections.remove(&node_id);
+                }
+            },
+            PeerConnectFailed(public_key, err) => {
+                debug!(
+                    target: LOG_TARGET,
+                    "Connection to peer '{}' failed because '{:?}'", public_key, err
+                );
+            },
+            PeerInboundSubstream(public_key, substream) => {
+                // No protocols can be registered with the connection manager yet, so there is nothing to hand the
+                // substream to. Dropping it closes the substream on the remote side.
+                debug!(
+                    target: LOG_TARGET,
+                    "Closing inbound substream from peer '{}' because no protocol handler is registered", public_key
+                );
+                drop(substream);
             },
         }
     }
 
+    async fn ban_peer(&mut self, node_id: NodeId, duration: Duration) {
+        info!(
+            target: LOG_TARGET,
+            "Banning peer '{}' for {:.0?}",
+            node_id.short_str(),
+            duration
+        );
+        if let Some(mut conn) = self.active_connections.remove(&node_id) {
+            log_if_error_fmt!(
+                level: warn,
+                target: LOG_TARGET,
+                conn.disconnect().await,
+                "Failed to disconnect banned peer '{}'",
+                node_id.short_str()
+            );
+        }
+        self.banned_peers.ban(node_id, duration);
+    }
+
+    fn schedule_reconnect(&mut self, node_id: NodeId, delay: Duration) {
+        if !self.scheduled_reconnects.insert(node_id.clone()) {
+            debug!(
+                target: LOG_TARGET,
+                "Reconnect to peer '{}' is already scheduled",
+                node_id.short_str()
+            );
+            return;
+        }
+        debug!(
+            target: LOG_TARGET,
+            "Scheduling reconnect to peer '{}' in {:.0?}",
+            node_id.short_str(),
+            delay
+        );
+        let reconnect = timer::delay(Instant::now() + delay).map(move |_| node_id);
+        self.pending_reconnects.push(Box::pin(reconnect));
+    }
+
+    /// Dial the peer for a scheduled reconnect and publish the result on the connectivity event stream
+    async fn reconnect(&mut self, node_id: NodeId) {
+        self.scheduled_reconnects.remove(&node_id);
+        debug!(target: LOG_TARGET, "Reconnecting to peer '{}'", node_id.short_str());
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.handle_request(ConnectionManagerRequest::DialPeer(node_id.clone(), reply_tx))
+            .await;
+
+        let mut connectivity_tx = self.connectivity_tx.clone();
+        self.executor.spawn(async move {
+            let event = match reply_rx.await {
+                Ok(Ok(_)) => ConnectivityEvent::ReconnectSucceeded(Box::new(node_id)),
+                Ok(Err(err)) => ConnectivityEvent::ReconnectFailed(Box::new(node_id), err),
+                Err(_) => {
+                    ConnectivityEvent::ReconnectFailed(Box::new(node_id), ConnectionManagerError::ActorRequestCanceled)
+                },
+            };
+            log_if_error!(
+                level: debug,
+                target: LOG_TARGET,
+                connectivity_tx.send(event).await,
+                "Failed to publish connectivity event because '{error}'",
+            );
+        });
+    }
+
     fn get_active_connection(&self, node_id: &NodeId) -> Option<&PeerConnection> {
         self.active_connections.get(node_id)
     }
@@ -214,6 +440,21 @@ where
         reply_tx: oneshot::Sender<Result<PeerConnection, ConnectionManagerError>>,
     )
     {
+        if !self.direction_policy.allows(ConnectionDirection::Outbound) {
+            debug!(
+                target: LOG_TARGET,
+                "Not dialing peer '{}' because outbound connections are not allowed by the direction policy",
+                node_id.short_str()
+            );
+            log_if_error_fmt!(
+                target: LOG_TARGET,
+                reply_tx.send(Err(ConnectionManagerError::OutboundConnectionsDisabled)),
+                "Failed to send reply for dial request for peer '{}'",
+                node_id.short_str()
+            );
+            return;
+        }
+
         match self.peer_manager.find_by_node_id(&node_id).await {
             Ok(peer) => {
                 if let Err(err) = self
@@ -255,16 +496,26 @@ mod test {
     use super::*;
     use crate::{
         backoff::ConstantBackoff,
-        connection_manager::requester::ConnectionManagerRequester,
+        connection_manager::{
+            direction::ConnectionDirectionPolicy,
+            protocol::ProtocolId,
+            requester::ConnectionManagerRequester,
+            test_utils::loopback_peer_connection,
+        },
         noise::NoiseConfig,
         peer_manager::{PeerFeatures, PeerManagerError},
         test_utils::{node_identity::build_node_identity, test_node::build_peer_manager},
         transports::{NoiseTransport, TcpTransport},
     };
-    use std::time::Duration;
+    use futures::io::AsyncWriteExt;
+    use std::{net::SocketAddr, thread, time::Duration};
     use tari_shutdown::Shutdown;
     use tari_test_utils::unpack_enum;
-    use tokio::runtime::Runtime;
+    use tokio::{
+        future::FutureExt,
+        net::{TcpListener, TcpStream},
+        runtime::Runtime,
+    };
 
     #[test]
     fn connect_to_nonexistent_peer() {
@@ -306,4 +557,284 @@ mod test {
 
         rt.shutdown_on_idle();
     }
+
+    #[test]
+    fn ban_peer() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let transport = NoiseTransport::new(
+            transport,
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (request_tx, request_rx) = mpsc::channel(1);
+        let mut requester = ConnectionManagerRequester::new(request_tx);
+        let mut shutdown = Shutdown::new();
+
+        let connection_manager = ConnectionManager::new(
+            Default::default(),
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+
+        rt.spawn(connection_manager.run());
+
+        rt.block_on(requester.ban_peer(NodeId::default(), Duration::from_secs(60)))
+            .unwrap();
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::PeerBanned = err);
+
+        // A short ban prevents the dial until it expires. The peer does not exist, so once the ban has expired the
+        // dial fails when the peer is looked up.
+        rt.block_on(requester.ban_peer(NodeId::default(), Duration::from_millis(200)))
+            .unwrap();
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::PeerBanned = err);
+
+        thread::sleep(Duration::from_millis(300));
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        match err {
+            ConnectionManagerError::PeerManagerError(PeerManagerError::PeerNotFoundError) => {},
+            _ => panic!(
+                "Unexpected error. Expected \
+                 `ConnectionManagerError::PeerManagerError(PeerManagerError::PeerNotFoundError)`"
+            ),
+        }
+
+        shutdown.trigger().unwrap();
+
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn dial_refused_in_inbound_only_mode() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let transport = NoiseTransport::new(
+            transport,
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (request_tx, request_rx) = mpsc::channel(1);
+        let mut requester = ConnectionManagerRequester::new(request_tx);
+        let mut shutdown = Shutdown::new();
+
+        let connection_manager = ConnectionManager::new(
+            Default::default(),
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+
+        rt.spawn(connection_manager.run());
+
+        rt.block_on(requester.set_direction_policy(ConnectionDirectionPolicy::InboundOnly))
+            .unwrap();
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::OutboundConnectionsDisabled = err);
+
+        shutdown.trigger().unwrap();
+
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn shutdown_request() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let transport = NoiseTransport::new(
+            transport,
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (request_tx, request_rx) = mpsc::channel(1);
+        let mut requester = ConnectionManagerRequester::new(request_tx);
+        let shutdown = Shutdown::new();
+
+        let connection_manager = ConnectionManager::new(
+            Default::default(),
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+
+        let (done_tx, done_rx) = oneshot::channel();
+        rt.spawn(async move {
+            connection_manager.run().await;
+            done_tx.send(()).unwrap();
+        });
+
+        rt.block_on(requester.shutdown()).unwrap();
+        rt.block_on(done_rx).unwrap();
+
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::SendToActorFailed = err);
+
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn schedule_reconnect() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let transport = NoiseTransport::new(
+            transport,
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (request_tx, request_rx) = mpsc::channel(1);
+        let mut requester = ConnectionManagerRequester::new(request_tx);
+        let mut shutdown = Shutdown::new();
+
+        let mut connection_manager = ConnectionManager::new(
+            Default::default(),
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+        let mut connectivity_events = connection_manager.take_connectivity_events().unwrap();
+        assert!(connection_manager.take_connectivity_events().is_none());
+
+        rt.spawn(connection_manager.run());
+
+        let delay = Duration::from_millis(100);
+        rt.block_on(requester.schedule_reconnect(NodeId::default(), delay))
+            .unwrap();
+        rt.block_on(requester.schedule_reconnect(NodeId::default(), delay))
+            .unwrap();
+
+        // The reconnect does not happen before the delay has elapsed
+        let result = rt.block_on(connectivity_events.next().timeout(Duration::from_millis(20)));
+        assert!(result.is_err());
+
+        // The peer does not exist, so the reconnect fails when the peer is looked up
+        let event = rt
+            .block_on(connectivity_events.next().timeout(Duration::from_secs(5)))
+            .unwrap()
+            .unwrap();
+        unpack_enum!(ConnectivityEvent::ReconnectFailed(node_id, err) = event);
+        assert_eq!(*node_id, NodeId::default());
+        match err {
+            ConnectionManagerError::PeerManagerError(PeerManagerError::PeerNotFoundError) => {},
+            _ => panic!(
+                "Unexpected error. Expected \
+                 `ConnectionManagerError::PeerManagerError(PeerManagerError::PeerNotFoundError)`"
+            ),
+        }
+
+        // The second request was coalesced into the first, so there is no further reconnect
+        let result = rt.block_on(connectivity_events.next().timeout(Duration::from_millis(200)));
+        assert!(result.is_err());
+
+        shutdown.trigger().unwrap();
+
+        rt.shutdown_on_idle();
+    }
+
+    #[test]
+    fn handle_peer_connection_events() {
+        let rt = Runtime::new().unwrap();
+        let transport = NoiseTransport::new(
+            TcpTransport::new(),
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (_request_tx, request_rx) = mpsc::channel(1);
+        let shutdown = Shutdown::new();
+        let mut connection_manager = ConnectionManager::new(
+            Default::default(),
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+
+        let ((mut outbound_conn, _), (inbound_conn, mut inbound_events)) =
+            rt.block_on(loopback_peer_connection(rt.executor())).unwrap();
+        let node_id = NodeId::from_key(inbound_conn.peer_public_key()).unwrap();
+
+        connection_manager.handle_event(ConnectionManagerEvent::PeerConnected(Box::new(inbound_conn.clone())));
+        assert!(connection_manager.get_active_connection(&node_id).is_some());
+
+        // An inbound substream is closed rather than crashing the connection manager
+        let mut substream = rt
+            .block_on(outbound_conn.open_substream(ProtocolId::from_static(b"/tari/test")))
+            .unwrap();
+        rt.block_on(substream.write_all(b"ping")).unwrap();
+        let event = rt.block_on(inbound_events.next()).unwrap();
+        match &event {
+            ConnectionManagerEvent::PeerInboundSubstream(_, _) => {},
+            _ => panic!("Unexpected event. Expected `ConnectionManagerEvent::PeerInboundSubstream`"),
+        }
+        connection_manager.handle_event(event);
+
+        connection_manager.handle_event(ConnectionManagerEvent::PeerDisconnected(Box::new(
+            inbound_conn.peer_public_key().clone(),
+        )));
+        assert!(connection_manager.get_active_connection(&node_id).is_none());
+    }
+    #[test]
+    fn shutdown_ends_listener() {
+        let rt = Runtime::new().unwrap();
+        // Bind to a free port and drop the listener, so that the connection manager can listen on the port
+        let port = rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        });
+        let socket_addr = format!("127.0.0.1:{}", port).parse::<SocketAddr>().unwrap();
+        let mut shutdown = Shutdown::new();
+        let mut transport = TcpTransport::new();
+        transport.set_shutdown_signal(shutdown.to_signal());
+        let transport = NoiseTransport::new(
+            transport,
+            NoiseConfig::new(build_node_identity(PeerFeatures::COMMUNICATION_NODE)),
+        );
+        let (_request_tx, request_rx) = mpsc::channel(1);
+        let connection_manager = ConnectionManager::new(
+            ConnectionManagerConfig {
+                listener_address: format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap(),
+                ..Default::default()
+            },
+            rt.executor(),
+            transport,
+            Arc::new(ConstantBackoff::new(Duration::from_secs(1))),
+            request_rx,
+            build_peer_manager().into(),
+            shutdown.to_signal(),
+        );
+        rt.spawn(connection_manager.run());
+
+        let is_listening = || rt.block_on(TcpStream::connect(&socket_addr)).is_ok();
+        assert!(poll_until(is_listening));
+
+        shutdown.trigger().unwrap();
+
+        assert!(poll_until(|| !is_listening()));
+    }
+
+    /// Returns true if the condition is met within two seconds
+    fn poll_until<F: FnMut() -> bool>(mut condition: F) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        false
+    }
 }
@@ -25,18 +25,31 @@ pub mod deprecated;
 pub use deprecated::*;
 
 cfg_next! {
+    mod ban;
     mod dial_state;
     mod dialer;
+    mod direction;
     mod error;
     mod listener;
     mod manager;
     mod peer_connection;
     mod protocol;
+    mod reputation;
     mod requester;
     mod utils;
 
+    #[cfg(test)]
+    mod test_utils;
+
     pub mod next {
-        pub use super::manager::{ConnectionManager, ConnectionManagerConfig, ConnectionManagerEvent};
+        pub use super::manager::{
+            ConnectionManager,
+            ConnectionManagerConfig,
+            ConnectionManagerEvent,
+            ConnectivityEvent,
+        };
+        pub use super::direction::ConnectionDirectionPolicy;
+        pub use super::reputation::{AddressStats, PeerReputation};
         pub use super::requester::{ConnectionManagerRequester, ConnectionManagerRequest};
     }
 }
@@ -20,7 +20,10 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use super::{error::ConnectionManagerError, protocol::ProtocolId};
+use super::{
+    error::ConnectionManagerError,
+    protocol::{ProtocolId, Version},
+};
 use crate::{
     connection::ConnectionDirection,
     connection_manager::{manager::ConnectionManagerEvent, utils::short_str},
@@ -30,14 +33,18 @@ use crate::{
 };
 use futures::{
     channel::{mpsc, oneshot},
-    stream::Fuse,
+    future::BoxFuture,
+    stream::{Fuse, FuturesUnordered},
     AsyncRead,
     AsyncWrite,
     SinkExt,
     StreamExt,
 };
 use log::*;
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 use tokio::runtime::TaskExecutor;
 
 const LOG_TARGET: &str = "comms::connection_manager::peer_connection";
@@ -77,7 +84,10 @@ where
 #[derive(Debug)]
 pub enum PeerConnectionRequest {
     /// Open a new substream and negotiate the given protocol
-    OpenSubstream(ProtocolId, oneshot::Sender<()>),
+    OpenSubstream(
+        ProtocolId,
+        oneshot::Sender<Result<yamux::Stream, ConnectionManagerError>>,
+    ),
     /// Disconnect all substreams and close the transport connection
     Disconnect,
 }
@@ -88,6 +98,7 @@ pub struct PeerConnection {
     peer_public_key: Arc<CommsPublicKey>,
     request_tx: mpsc::Sender<PeerConnectionRequest>,
     address: Multiaddr,
+    negotiated_protocols: Arc<RwLock<HashMap<ProtocolId, Version>>>,
 }
 
 impl PeerConnection {
@@ -101,12 +112,52 @@ impl PeerConnection {
             request_tx,
             peer_public_key,
             address,
+            negotiated_protocols: Default::default(),
         }
     }
 
     pub fn peer_public_key(&self) -> &CommsPublicKey {
         &self.peer_public_key
     }
+
+    /// Returns the protocols and their versions which have been negotiated with the peer on this connection. The
+    /// connection manager does not negotiate protocol versions during connection setup, so this only contains the
+    /// versions recorded with `set_negotiated_protocol_version`.
+    pub fn negotiated_protocols(&self) -> HashMap<ProtocolId, Version> {
+        acquire_read_lock!(self.negotiated_protocols).clone()
+    }
+
+    /// Returns the negotiated version of the given protocol, or None if the protocol has not been negotiated
+    pub fn negotiated_protocol_version(&self, protocol_id: &ProtocolId) -> Option<Version> {
+        acquire_read_lock!(self.negotiated_protocols).get(protocol_id).cloned()
+    }
+
+    /// Record the version of a protocol negotiated with the peer. This is called by the protocol implementation that
+    /// negotiated the version, so that other users of the connection can query it. All clones of this connection
+    /// share the negotiated protocols.
+    pub fn set_negotiated_protocol_version(&self, protocol_id: ProtocolId, version: Version) {
+        acquire_write_lock!(self.negotiated_protocols).insert(protocol_id, version);
+    }
+
+    /// Open a new substream to the peer
+    pub async fn open_substream(&mut self, protocol_id: ProtocolId) -> Result<yamux::Stream, ConnectionManagerError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.request_tx
+            .send(PeerConnectionRequest::OpenSubstream(protocol_id, reply_tx))
+            .await
+            .map_err(|_| ConnectionManagerError::SendToActorFailed)?;
+        reply_rx
+            .await
+            .map_err(|_| ConnectionManagerError::ActorRequestCanceled)?
+    }
+
+    /// Request that the connection to the peer is closed
+    pub async fn disconnect(&mut self) -> Result<(), ConnectionManagerError> {
+        self.request_tx
+            .send(PeerConnectionRequest::Disconnect)
+            .await
+            .map_err(|_| ConnectionManagerError::SendToActorFailed)
+    }
 }
 
 /// Actor for an active connection to a peer.
@@ -115,6 +166,7 @@ pub struct PeerConnectionActor {
     request_rx: Fuse<mpsc::Receiver<PeerConnectionRequest>>,
     incoming_substreams: Fuse<IncomingSubstream<'static>>,
     connection: yamux::Control,
+    pending_substreams: FuturesUnordered<BoxFuture<'static, ()>>,
     event_notifier: mpsc::Sender<ConnectionManagerEvent>,
     shutdown: bool,
 }
@@ -133,6 +185,7 @@ impl PeerConnectionActor {
             peer_public_key,
             connection: connection.get_yamux_control(),
             incoming_substreams: connection.incoming().fuse(),
+            pending_substreams: FuturesUnordered::new(),
             request_rx: request_rx.fuse(),
             event_notifier,
             shutdown: false,
@@ -144,6 +197,8 @@ impl PeerConnectionActor {
             futures::select! {
                 request = self.request_rx.select_next_some() => self.handle_request(request).await,
 
+                _ = self.pending_substreams.select_next_some() => {},
+
                 maybe_substream = self.incoming_substreams.next() => {
                     match maybe_substream {
                         Some(Ok(substream)) => self.handle_incoming_substream(substream).await,
@@ -168,25 +223,42 @@ impl PeerConnectionActor {
     async fn handle_request(&mut self, request: PeerConnectionRequest) {
         use PeerConnectionRequest::*;
         match request {
-            OpenSubstream(proto, reply_tx) => {
-                let reply = self.open_substream(proto).await;
-                log_if_error_fmt!(
-                    target: LOG_TARGET,
-                    reply_tx.send(reply),
-                    "Reply oneshot closed when sending reply",
-                );
-            },
+            OpenSubstream(proto, reply_tx) => self.open_substream(proto, reply_tx),
             Disconnect => self.disconnect().await,
         }
     }
 
-    async fn handle_incoming_substream(&mut self, _substream: yamux::Stream) {
+    async fn handle_incoming_substream(&mut self, substream: yamux::Stream) {
         // TODO: Negotiate a protocol
-        unimplemented!();
+        self.notify_event(ConnectionManagerEvent::PeerInboundSubstream(
+            Box::new((*self.peer_public_key).clone()),
+            substream,
+        ))
+        .await;
     }
 
-    async fn open_substream(&mut self, _protocol: ProtocolId) {
-        unimplemented!()
+    fn open_substream(
+        &mut self,
+        _protocol: ProtocolId,
+        reply_tx: oneshot::Sender<Result<yamux::Stream, ConnectionManagerError>>,
+    )
+    {
+        // The incoming substream stream must continue to be polled for the yamux control to open a substream, so the
+        // open is driven alongside it rather than awaited here.
+        // TODO: Negotiate the protocol on the new substream
+        let mut control = self.connection.clone();
+        let open_fut = async move {
+            let result = control
+                .open_stream()
+                .await
+                .map_err(|err| ConnectionManagerError::YamuxConnectionError(err.to_string()));
+            log_if_error_fmt!(
+                target: LOG_TARGET,
+                reply_tx.send(result),
+                "Reply oneshot closed when sending reply",
+            );
+        };
+        self.pending_substreams.push(Box::pin(open_fut));
     }
 
     async fn notify_event(&mut self, event: ConnectionManagerEvent) {
@@ -213,3 +285,75 @@ impl PeerConnectionActor {
         .await;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::connection_manager::test_utils::loopback_peer_connection;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use tari_test_utils::unpack_enum;
+    use tokio::runtime::Runtime;
+
+    async fn round_trip(
+        conn: &mut PeerConnection,
+        remote_events: &mut mpsc::Receiver<ConnectionManagerEvent>,
+        expected_public_key: &CommsPublicKey,
+    )
+    {
+        let mut substream = conn
+            .open_substream(ProtocolId::from_static(b"/tari/test"))
+            .await
+            .unwrap();
+        substream.write_all(b"ping").await.unwrap();
+        substream.flush().await.unwrap();
+
+        let event = remote_events.next().await.unwrap();
+        unpack_enum!(ConnectionManagerEvent::PeerInboundSubstream(public_key, inbound) = event);
+        assert_eq!(&*public_key, expected_public_key);
+        let mut inbound = inbound;
+
+        let mut buf = [0u8; 4];
+        inbound.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        inbound.write_all(b"pong").await.unwrap();
+        inbound.flush().await.unwrap();
+        substream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[test]
+    fn open_substream_round_trip() {
+        let rt = Runtime::new().unwrap();
+        let ((mut outbound_conn, mut outbound_events), (mut inbound_conn, mut inbound_events)) =
+            rt.block_on(loopback_peer_connection(rt.executor())).unwrap();
+
+        rt.block_on(async move {
+            // The inbound side sees the outbound side's public key and vice versa
+            let outbound_public_key = inbound_conn.peer_public_key().clone();
+            let inbound_public_key = outbound_conn.peer_public_key().clone();
+
+            round_trip(&mut outbound_conn, &mut inbound_events, &outbound_public_key).await;
+            round_trip(&mut inbound_conn, &mut outbound_events, &inbound_public_key).await;
+        });
+    }
+
+    #[test]
+    fn negotiated_protocols() {
+        let rt = Runtime::new().unwrap();
+        let ((outbound_conn, _), (inbound_conn, _)) = rt.block_on(loopback_peer_connection(rt.executor())).unwrap();
+        let protocol = ProtocolId::from_static(b"/tari/test");
+        assert!(outbound_conn.negotiated_protocols().is_empty());
+        assert_eq!(outbound_conn.negotiated_protocol_version(&protocol), None);
+
+        outbound_conn.set_negotiated_protocol_version(protocol.clone(), 2);
+        // Clones of the connection share the negotiated protocols
+        let conn_clone = outbound_conn.clone();
+        assert_eq!(conn_clone.negotiated_protocol_version(&protocol), Some(2));
+        let negotiated = conn_clone.negotiated_protocols();
+        assert_eq!(negotiated.len(), 1);
+        assert_eq!(negotiated.get(&protocol), Some(&2));
+
+        assert!(inbound_conn.negotiated_protocols().is_empty());
+    }
+}
@@ -31,6 +31,9 @@ const LOG_TARGET: &str = "comms::connection_manager::protocol";
 /// Represents a protocol id string (e.g. /tari/transactions/1.0.0)
 pub type ProtocolId = bytes::Bytes;
 
+/// The version of a protocol agreed upon with a peer
+pub type Version = u32;
+
 const PROTOCOL_NOT_SUPPORTED: &[u8] = b"not-supported";
 const PROTOCOL_NEGOTIATION_TERMINATED: &[u8] = b"negotiation-terminated";
 const BUF_CAPACITY: usize = std::u8::MAX as usize + 1;
@@ -0,0 +1,159 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use crate::{multiaddr::Multiaddr, peer_manager::NodeId};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// Dial success and failure counts for a single address
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AddressStats {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl AddressStats {
+    /// The (smoothed) ratio of successful dials to all dials on this address. An address which has never been dialed
+    /// has a ratio of 0.5, so that it is tried after addresses that are known to work, but before addresses that are
+    /// known to fail.
+    pub fn success_ratio(&self) -> f64 {
+        f64::from(self.successes + 1) / f64::from(self.successes + self.failures + 2)
+    }
+}
+
+/// Keeps track of dial successes and failures for each peer address. This is used to try the historically-best
+/// address for a peer first. Clones of this struct share the same underlying state.
+#[derive(Debug, Clone, Default)]
+pub struct PeerReputation {
+    inner: Arc<RwLock<HashMap<NodeId, HashMap<Multiaddr, AddressStats>>>>,
+}
+
+impl PeerReputation {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record a successful dial to the given address of the given peer
+    pub fn record_success(&self, node_id: &NodeId, address: &Multiaddr) {
+        self.update_stats(node_id, address, |stats| stats.successes += 1);
+    }
+
+    /// Record a failed dial to the given address of the given peer
+    pub fn record_failure(&self, node_id: &NodeId, address: &Multiaddr) {
+        self.update_stats(node_id, address, |stats| stats.failures += 1);
+    }
+
+    /// Returns the dial stats for the given address of the given peer
+    pub fn get_stats(&self, node_id: &NodeId, address: &Multiaddr) -> AddressStats {
+        self.inner
+            .read()
+            .expect("PeerReputation lock poisoned")
+            .get(node_id)
+            .and_then(|addresses| addresses.get(address))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Clear all recorded dial stats for the given peer
+    pub fn reset(&self, node_id: &NodeId) {
+        self.inner
+            .write()
+            .expect("PeerReputation lock poisoned")
+            .remove(node_id);
+    }
+
+    /// Order the given addresses from the highest to the lowest success ratio. Addresses with equal success ratios
+    /// retain the order in which they were given.
+    pub fn order_addresses<'a, I>(&self, node_id: &NodeId, addresses: I) -> Vec<Multiaddr>
+    where I: IntoIterator<Item = &'a Multiaddr> {
+        let mut addresses = addresses
+            .into_iter()
+            .map(|addr| (addr.clone(), self.get_stats(node_id, addr).success_ratio()))
+            .collect::<Vec<_>>();
+        addresses.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        addresses.into_iter().map(|(addr, _)| addr).collect()
+    }
+
+    fn update_stats<F>(&self, node_id: &NodeId, address: &Multiaddr, f: F)
+    where F: FnOnce(&mut AddressStats) {
+        let mut lock = self.inner.write().expect("PeerReputation lock poisoned");
+        let stats = lock
+            .entry(node_id.clone())
+            .or_insert_with(HashMap::new)
+            .entry(address.clone())
+            .or_insert_with(AddressStats::default);
+        f(stats);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn order_addresses() {
+        let reputation = PeerReputation::new();
+        let node_id = NodeId::default();
+        let bad_address = "/ip4/127.0.0.1/tcp/8000".parse::<Multiaddr>().unwrap();
+        let good_address = "/ip4/127.0.0.1/tcp/8001".parse::<Multiaddr>().unwrap();
+        let addresses = vec![bad_address.clone(), good_address.clone()];
+
+        // No history, so the given order is kept
+        assert_eq!(reputation.order_addresses(&node_id, &addresses), addresses);
+
+        reputation.record_failure(&node_id, &bad_address);
+        reputation.record_failure(&node_id, &bad_address);
+        reputation.record_success(&node_id, &good_address);
+        assert_eq!(reputation.get_stats(&node_id, &bad_address), AddressStats {
+            successes: 0,
+            failures: 2
+        });
+        assert_eq!(reputation.order_addresses(&node_id, &addresses), vec![
+            good_address,
+            bad_address
+        ]);
+
+        reputation.reset(&node_id);
+        assert_eq!(reputation.order_addresses(&node_id, &addresses), addresses);
+    }
+
+    #[test]
+    fn untried_address_ordering() {
+        let reputation = PeerReputation::new();
+        let node_id = NodeId::default();
+        let failed_address = "/ip4/127.0.0.1/tcp/8000".parse::<Multiaddr>().unwrap();
+        let untried_address = "/ip4/127.0.0.1/tcp/8001".parse::<Multiaddr>().unwrap();
+        let working_address = "/ip4/127.0.0.1/tcp/8002".parse::<Multiaddr>().unwrap();
+        reputation.record_failure(&node_id, &failed_address);
+        reputation.record_success(&node_id, &working_address);
+
+        let addresses = vec![failed_address.clone(), untried_address.clone(), working_address.clone()];
+        assert_eq!(reputation.order_addresses(&node_id, &addresses), vec![
+            working_address,
+            untried_address,
+            failed_address
+        ]);
+    }
+}
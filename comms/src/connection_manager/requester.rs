@@ -20,28 +20,53 @@
 // WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use super::{error::ConnectionManagerError, peer_connection::PeerConnection};
+use super::{direction::ConnectionDirectionPolicy, error::ConnectionManagerError, peer_connection::PeerConnection};
 use crate::peer_manager::NodeId;
 use futures::{
     channel::{mpsc, oneshot},
     SinkExt,
 };
+use std::time::Duration;
+use tokio::future::FutureExt;
 
 /// Requests which are handled by the ConnectionManagerService
 pub enum ConnectionManagerRequest {
     DialPeer(NodeId, oneshot::Sender<Result<PeerConnection, ConnectionManagerError>>),
+    /// Disconnect the peer if it is connected, and refuse to dial it for the given duration
+    BanPeer(NodeId, Duration, oneshot::Sender<Result<(), ConnectionManagerError>>),
+    /// Clear the recorded dial successes and failures for the addresses of the given peer
+    ResetPeerReputation(NodeId),
+    /// Dial the peer after the given delay, unless a reconnect to the peer is already scheduled. The result is
+    /// published on the connectivity event stream.
+    ScheduleReconnect(NodeId, Duration),
+    /// Replace the connection direction policy, which determines whether inbound connections are accepted and whether
+    /// peers are dialed
+    SetDirectionPolicy(ConnectionDirectionPolicy, oneshot::Sender<()>),
+    /// Shut down the connection manager, including the dialer and listener
+    Shutdown,
 }
 
+/// The default maximum time to wait for the connection manager request channel to have capacity
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Responsible for constructing requests to the ConnectionManagerService
 #[derive(Clone)]
 pub struct ConnectionManagerRequester {
     sender: mpsc::Sender<ConnectionManagerRequest>,
+    request_timeout: Duration,
 }
 
 impl ConnectionManagerRequester {
+    /// Sets the maximum time to wait for the request channel to have capacity before a request fails with
+    /// `RequestTimeout`.
+    setter!(with_request_timeout, request_timeout, Duration);
+
     /// Create a new ConnectionManagerRequester
     pub fn new(sender: mpsc::Sender<ConnectionManagerRequest>) -> Self {
-        Self { sender }
+        Self {
+            sender,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
     }
 }
 
@@ -49,12 +74,89 @@ impl ConnectionManagerRequester {
     /// Attempt to connect to a remote peer
     pub async fn dial_peer(&mut self, node_id: NodeId) -> Result<PeerConnection, ConnectionManagerError> {
         let (reply_tx, reply_rx) = oneshot::channel();
-        self.sender
-            .send(ConnectionManagerRequest::DialPeer(node_id, reply_tx))
+        self.send_request(ConnectionManagerRequest::DialPeer(node_id, reply_tx))
+            .await?;
+        reply_rx
             .await
-            .map_err(|_| ConnectionManagerError::SendToActorFailed)?;
+            .map_err(|_| ConnectionManagerError::ActorRequestCanceled)?
+    }
+
+    /// Ban a peer for the given duration. The peer is disconnected if it is connected and any dial to the peer fails
+    /// with `PeerBanned` until the ban expires.
+    pub async fn ban_peer(&mut self, node_id: NodeId, duration: Duration) -> Result<(), ConnectionManagerError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send_request(ConnectionManagerRequest::BanPeer(node_id, duration, reply_tx))
+            .await?;
         reply_rx
             .await
             .map_err(|_| ConnectionManagerError::ActorRequestCanceled)?
     }
+
+    /// Clear the recorded dial successes and failures for all addresses of the given peer, so that the next dial
+    /// attempts the addresses in their default order
+    pub async fn reset_peer_reputation(&mut self, node_id: NodeId) -> Result<(), ConnectionManagerError> {
+        self.send_request(ConnectionManagerRequest::ResetPeerReputation(node_id))
+            .await
+    }
+
+    /// Ask the connection manager to reconnect to the peer after the given delay. A request for a peer that already has
+    /// a reconnect scheduled is coalesced into the scheduled reconnect. The result of the reconnect is published on the
+    /// connectivity event stream of the connection manager.
+    pub async fn schedule_reconnect(&mut self, node_id: NodeId, delay: Duration) -> Result<(), ConnectionManagerError> {
+        self.send_request(ConnectionManagerRequest::ScheduleReconnect(node_id, delay))
+            .await
+    }
+
+    /// Set the connection direction policy. In `OutboundOnly` mode inbound connections are rejected and in
+    /// `InboundOnly` mode dial requests fail with `OutboundConnectionsDisabled`. The policy is applied by the time
+    /// this call returns.
+    pub async fn set_direction_policy(
+        &mut self,
+        policy: ConnectionDirectionPolicy,
+    ) -> Result<(), ConnectionManagerError>
+    {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.send_request(ConnectionManagerRequest::SetDirectionPolicy(policy, reply_tx))
+            .await?;
+        reply_rx.await.map_err(|_| ConnectionManagerError::ActorRequestCanceled)
+    }
+
+    /// Shut down the connection manager. Any subsequent requests will fail with `SendToActorFailed`.
+    pub async fn shutdown(&mut self) -> Result<(), ConnectionManagerError> {
+        self.send_request(ConnectionManagerRequest::Shutdown).await
+    }
+
+    /// Send a request to the connection manager. If the request channel remains full for longer than the request
+    /// timeout, `RequestTimeout` is returned rather than waiting indefinitely.
+    async fn send_request(&mut self, request: ConnectionManagerRequest) -> Result<(), ConnectionManagerError> {
+        self.sender
+            .send(request)
+            .timeout(self.request_timeout)
+            .await
+            // Timeout?
+            .map_err(|_| ConnectionManagerError::RequestTimeout)?
+            // Channel error?
+            .map_err(|_| ConnectionManagerError::SendToActorFailed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tari_test_utils::unpack_enum;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn request_timeout_when_channel_full() {
+        let rt = Runtime::new().unwrap();
+        // A zero-sized buffer allows a single pending request for the one sender
+        let (request_tx, _request_rx) = mpsc::channel(0);
+        let mut requester = ConnectionManagerRequester::new(request_tx).with_request_timeout(Duration::from_millis(10));
+
+        rt.block_on(requester.reset_peer_reputation(NodeId::default())).unwrap();
+        // The request channel is full and is never read
+        let result = rt.block_on(requester.dial_peer(NodeId::default()));
+        unpack_enum!(Result::Err(err) = result);
+        unpack_enum!(ConnectionManagerError::RequestTimeout = err);
+    }
 }
@@ -0,0 +1,74 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use super::{
+    error::ConnectionManagerError,
+    manager::ConnectionManagerEvent,
+    peer_connection::{create_peer_connection, PeerConnection},
+};
+use crate::{
+    connection::ConnectionDirection,
+    peer_manager::PeerFeatures,
+    test_utils::{memory_socket::MemorySocket, node_identity::build_node_identity},
+};
+use futures::{channel::mpsc, future};
+use tokio::runtime::TaskExecutor;
+
+const EVENT_CHANNEL_SIZE: usize = 10;
+
+/// A `PeerConnection` and the receiver for the connection manager events it emits
+pub type LoopbackPeer = (PeerConnection, mpsc::Receiver<ConnectionManagerEvent>);
+
+/// Create a pair of `PeerConnection`s which are connected to each other over an in-memory socket. The first connection
+/// is outbound and the second is inbound. Substreams opened by one side are emitted as
+/// `ConnectionManagerEvent::PeerInboundSubstream` events on the other side's event receiver.
+pub async fn loopback_peer_connection(
+    executor: TaskExecutor,
+) -> Result<(LoopbackPeer, LoopbackPeer), ConnectionManagerError> {
+    let (outbound_socket, inbound_socket) = MemorySocket::new_pair();
+    let outbound_node_identity = build_node_identity(PeerFeatures::COMMUNICATION_NODE);
+    let inbound_node_identity = build_node_identity(PeerFeatures::COMMUNICATION_NODE);
+    let (outbound_event_tx, outbound_event_rx) = mpsc::channel(EVENT_CHANNEL_SIZE);
+    let (inbound_event_tx, inbound_event_rx) = mpsc::channel(EVENT_CHANNEL_SIZE);
+
+    let (outbound_conn, inbound_conn) = future::try_join(
+        create_peer_connection(
+            executor.clone(),
+            outbound_socket,
+            "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            inbound_node_identity.public_key().clone(),
+            ConnectionDirection::Outbound,
+            outbound_event_tx,
+        ),
+        create_peer_connection(
+            executor,
+            inbound_socket,
+            "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            outbound_node_identity.public_key().clone(),
+            ConnectionDirection::Inbound,
+            inbound_event_tx,
+        ),
+    )
+    .await?;
+
+    Ok(((outbound_conn, outbound_event_rx), (inbound_conn, inbound_event_rx)))
+}
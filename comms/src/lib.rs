@@ -38,7 +38,7 @@ pub mod types;
 cfg_next! {
     mod multiplexing;
     mod noise;
-    mod socks;
+    pub mod socks;
 
     pub mod transports;
 }
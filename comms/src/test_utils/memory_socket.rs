@@ -0,0 +1,119 @@
+// Copyright 2019, The Tari Project
+//
+// Redistribution and use in source and binary forms, with or without modification, are permitted provided that the
+// following conditions are met:
+//
+// 1. Redistributions of source code must retain the above copyright notice, this list of conditions and the following
+// disclaimer.
+//
+// 2. Redistributions in binary form must reproduce the above copyright notice, this list of conditions and the
+// following disclaimer in the documentation and/or other materials provided with the distribution.
+//
+// 3. Neither the name of the copyright holder nor the names of its contributors may be used to endorse or promote
+// products derived from this software without specific prior written permission.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+// DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+// SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY,
+// WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use bytes::Bytes;
+use futures::{
+    channel::mpsc,
+    io::{AsyncRead, AsyncWrite},
+    task::{Context, Poll},
+    StreamExt,
+};
+use std::{io, pin::Pin};
+
+/// An in-memory socket. Bytes written to one half of the pair can be read from the other half.
+#[derive(Debug)]
+pub struct MemorySocket {
+    incoming: mpsc::UnboundedReceiver<Bytes>,
+    outgoing: mpsc::UnboundedSender<Bytes>,
+    current_buffer: Bytes,
+}
+
+impl MemorySocket {
+    /// Create a pair of connected in-memory sockets
+    pub fn new_pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded();
+        let (b_tx, b_rx) = mpsc::unbounded();
+        let a = Self {
+            incoming: a_rx,
+            outgoing: b_tx,
+            current_buffer: Bytes::new(),
+        };
+        let b = Self {
+            incoming: b_rx,
+            outgoing: a_tx,
+            current_buffer: Bytes::new(),
+        };
+
+        (a, b)
+    }
+}
+
+impl AsyncRead for MemorySocket {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        if self.current_buffer.is_empty() {
+            match self.incoming.poll_next_unpin(cx) {
+                Poll::Ready(Some(bytes)) => {
+                    self.current_buffer = bytes;
+                },
+                // The other half of the socket was dropped or closed
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let len = std::cmp::min(buf.len(), self.current_buffer.len());
+        let chunk = self.current_buffer.split_to(len);
+        buf[..len].copy_from_slice(&chunk);
+
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl AsyncWrite for MemorySocket {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.outgoing.unbounded_send(Bytes::from(buf)) {
+            Ok(()) => Poll::Ready(Ok(buf.len())),
+            Err(_) => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.outgoing.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        io::{AsyncReadExt, AsyncWriteExt},
+    };
+
+    #[test]
+    fn read_write() {
+        let (mut a, mut b) = MemorySocket::new_pair();
+        block_on(async move {
+            a.write_all(b"Oathbringer").await.unwrap();
+            a.close().await.unwrap();
+
+            let mut buf = Vec::new();
+            b.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"Oathbringer");
+        });
+    }
+}
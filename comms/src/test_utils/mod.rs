@@ -24,6 +24,7 @@ pub mod dialers;
 pub mod node_id;
 
 cfg_next! {
+    pub mod memory_socket;
     pub mod node_identity;
     pub mod tcp;
     pub mod test_node;
@@ -72,7 +72,9 @@ pub fn build_connection_manager(
 ) -> ConnectionManagerRequester
 {
     // TODO: Once we have `comms::Builder@next` we can construct a whole "comms node" here for testing
-    let transport = NoiseTransport::new(TcpTransport::default(), NoiseConfig::new(config.node_identity.clone()));
+    let mut tcp_transport = TcpTransport::default();
+    tcp_transport.set_shutdown_signal(shutdown.clone());
+    let transport = NoiseTransport::new(tcp_transport, NoiseConfig::new(config.node_identity.clone()));
     let (request_tx, request_rx) = mpsc::channel(10);
     let requester = ConnectionManagerRequester::new(request_tx);
 
@@ -32,7 +32,8 @@ mod socks;
 mod tcp;
 
 pub use noise::NoiseTransport;
-pub use tcp::{TcpSocket, TcpTransport};
+pub use socks::{SocksConfig, SocksTransport};
+pub use tcp::{AddressFamilyPreference, TcpReadHalf, TcpSocket, TcpSocketMetrics, TcpTransport, TcpWriteHalf};
 
 pub trait Transport {
     /// The output of the transport after a connection is established
@@ -54,6 +55,12 @@ pub trait Transport {
 
     /// Connect (dial) to the given multiaddr
     fn dial(&self, addr: Multiaddr) -> Self::DialFuture;
+
+    /// Order the given addresses of a peer in the order in which they should be dialed. By default, the given order
+    /// is kept.
+    fn order_dial_addresses(&self, addresses: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        addresses
+    }
 }
 
 trait TransportExt: Transport {}
@@ -114,4 +114,8 @@ where
             Ok((socket, public_key, peer_addr))
         })
     }
+
+    fn order_dial_addresses(&self, addresses: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        self.transport.order_dial_addresses(addresses)
+    }
 }
@@ -29,12 +29,36 @@ use futures::{Future, FutureExt};
 use multiaddr::{AddrComponent, Multiaddr};
 use std::io;
 
+/// Configuration for the SOCKS5 proxy used by `SocksTransport`
 #[derive(Clone, Debug)]
 pub struct SocksConfig {
+    /// The address of the SOCKS5 proxy e.g. `/ip4/127.0.0.1/tcp/9050` for a local tor proxy
     pub proxy_address: Multiaddr,
+    /// The authentication to use with the proxy
     pub authentication: socks::Authentication,
 }
 
+impl SocksConfig {
+    /// Create a config for a proxy that does not require authentication
+    pub fn new(proxy_address: Multiaddr) -> Self {
+        Self {
+            proxy_address,
+            authentication: socks::Authentication::None,
+        }
+    }
+
+    /// Authenticate with the proxy using the given username and password
+    pub fn with_password<U: Into<String>, P: Into<String>>(mut self, username: U, password: P) -> Self {
+        self.authentication = socks::Authentication::Password {
+            username: username.into(),
+            password: password.into(),
+        };
+        self
+    }
+}
+
+/// Transport which dials peers through a SOCKS5 proxy, such as the tor SOCKS proxy. Inbound connections are accepted
+/// over plain TCP on the local address that the proxied (e.g. hidden service) address forwards to.
 #[derive(Clone, Debug)]
 pub struct SocksTransport {
     socks_config: SocksConfig,
@@ -145,6 +169,101 @@ impl Transport for SocksTransport {
 #[cfg(test)]
 mod test {
     use super::*;
+    use futures::{future, AsyncReadExt, AsyncWriteExt, StreamExt};
+    use tokio::runtime::Runtime;
+
+    /// A minimal SOCKS5 proxy which performs the handshake for a single CONNECT request to an IPv4 address, and then
+    /// echoes back everything it receives instead of connecting to the destination. Returns the credentials that the
+    /// client authenticated with (if any) and the requested destination.
+    async fn socks_stub(mut socket: TcpSocket) -> (Option<(String, String)>, Multiaddr) {
+        let mut buf = [0u8; 2];
+        socket.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf[0], 0x05);
+        let mut methods = vec![0u8; buf[1] as usize];
+        socket.read_exact(&mut methods).await.unwrap();
+
+        let credentials = if methods.contains(&0x02) {
+            socket.write_all(&[0x05, 0x02]).await.unwrap();
+            let mut buf = [0u8; 2];
+            socket.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf[0], 0x01);
+            let mut username = vec![0u8; buf[1] as usize];
+            socket.read_exact(&mut username).await.unwrap();
+            let mut len = [0u8; 1];
+            socket.read_exact(&mut len).await.unwrap();
+            let mut password = vec![0u8; len[0] as usize];
+            socket.read_exact(&mut password).await.unwrap();
+            socket.write_all(&[0x01, 0x00]).await.unwrap();
+            Some((
+                String::from_utf8(username).unwrap(),
+                String::from_utf8(password).unwrap(),
+            ))
+        } else {
+            socket.write_all(&[0x05, 0x00]).await.unwrap();
+            None
+        };
+
+        let mut request = [0u8; 10];
+        socket.read_exact(&mut request).await.unwrap();
+        // Version 5, CONNECT, reserved, IPv4
+        assert_eq!(&request[..4], &[0x05, 0x01, 0x00, 0x01]);
+        let ip = std::net::Ipv4Addr::new(request[4], request[5], request[6], request[7]);
+        let port = u16::from_be_bytes([request[8], request[9]]);
+        // Succeeded, bound to 127.0.0.1:9999
+        socket
+            .write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0x27, 0x0F])
+            .await
+            .unwrap();
+        socket.flush().await.unwrap();
+
+        let mut buf = [0u8; 4];
+        socket.read_exact(&mut buf).await.unwrap();
+        socket.write_all(&buf).await.unwrap();
+        socket.flush().await.unwrap();
+
+        (credentials, multiaddr_from_components!(IP4(ip), TCP(port)))
+    }
+
+    /// Dials through the stub proxy, checks that the requested destination reached the proxy and that bytes round-trip
+    /// through the socket, and returns the credentials the proxy received
+    fn dial_through_stub(socks_config: impl FnOnce(Multiaddr) -> SocksConfig) -> Option<(String, String)> {
+        let rt = Runtime::new().unwrap();
+        let (mut inbound, proxy_address) = rt
+            .block_on(TcpTransport::new().listen("/ip4/127.0.0.1/tcp/0".parse().unwrap()))
+            .unwrap();
+        let transport = SocksTransport::new(socks_config(proxy_address));
+        let dest_addr = "/ip4/10.0.0.1/tcp/1234".parse::<Multiaddr>().unwrap();
+
+        let proxy = async move {
+            let (socket, _) = inbound.next().await.unwrap().unwrap().await.unwrap();
+            socks_stub(socket).await
+        };
+        let client = async {
+            let (mut socket, bound_addr) = transport.dial(dest_addr.clone()).await.unwrap();
+            assert_eq!(bound_addr, "/ip4/127.0.0.1/tcp/9999".parse::<Multiaddr>().unwrap());
+            socket.write_all(b"ping").await.unwrap();
+            socket.flush().await.unwrap();
+            let mut buf = [0u8; 4];
+            socket.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        };
+
+        let ((credentials, requested_addr), _) = rt.block_on(future::join(proxy, client));
+        assert_eq!(requested_addr, dest_addr);
+        credentials
+    }
+
+    #[test]
+    fn dial_without_authentication() {
+        assert!(dial_through_stub(SocksConfig::new).is_none());
+    }
+
+    #[test]
+    fn dial_with_password() {
+        let credentials =
+            dial_through_stub(|proxy_address| SocksConfig::new(proxy_address).with_password("tari", "secret"));
+        assert_eq!(credentials, Some(("tari".to_string(), "secret".to_string())));
+    }
 
     #[test]
     fn extract_proxied_address() {
@@ -22,14 +22,127 @@
 
 use super::Transport;
 use crate::utils::multiaddr::{multiaddr_to_socketaddr, socketaddr_to_multiaddr};
-use futures::{future, io::Error, ready, stream::BoxStream, AsyncRead, AsyncWrite, Future, Poll, Stream, StreamExt};
-use multiaddr::Multiaddr;
-use std::{io, pin::Pin, task::Context, time::Duration};
+use futures::{
+    future::BoxFuture,
+    io::{Error, ReadHalf, WriteHalf},
+    ready,
+    stream::BoxStream,
+    AsyncRead,
+    AsyncReadExt,
+    AsyncWrite,
+    AsyncWriteExt,
+    Future,
+    FutureExt,
+    Poll,
+    Stream,
+    StreamExt,
+};
+use multiaddr::{AddrComponent, Multiaddr};
+use std::{
+    fmt,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::Context,
+    time::Duration,
+};
+use tari_common::retry::{retry_with_backoff, BackoffPolicy};
+use tari_shutdown::ShutdownSignal;
 use tokio::{
+    future::FutureExt as TokioFutureExt,
     io::{AsyncRead as TokioAsyncRead, AsyncWrite as TokioAsyncWrite},
     net::{TcpListener, TcpStream},
 };
 
+/// The IP address family to dial first when a peer has both IPv4 and IPv6 addresses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressFamilyPreference {
+    /// Dial IPv4 addresses before any other address
+    PreferIpv4,
+    /// Dial IPv6 addresses before any other address
+    PreferIpv6,
+    /// Dial addresses of either family in the order given
+    Any,
+}
+
+impl AddressFamilyPreference {
+    /// Returns true if the address is of the preferred family. If there is no preferred family, true is returned for
+    /// all addresses.
+    fn is_preferred(&self, addr: &Multiaddr) -> bool {
+        match (self, addr.iter().next()) {
+            (AddressFamilyPreference::PreferIpv4, Some(AddrComponent::IP4(_))) => true,
+            (AddressFamilyPreference::PreferIpv6, Some(AddrComponent::IP6(_))) => true,
+            (AddressFamilyPreference::Any, _) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for AddressFamilyPreference {
+    /// Addresses of either family are dialed, IPv6 addresses first
+    fn default() -> Self {
+        AddressFamilyPreference::PreferIpv6
+    }
+}
+
+/// A preamble that is exchanged on every connection before the socket is handed out. The dialing side sends `bytes`
+/// and requires `expected_response` in return. The listening side requires `bytes` and sends `expected_response`.
+#[derive(Debug, Clone, PartialEq)]
+struct TcpPreamble {
+    bytes: Vec<u8>,
+    expected_response: Vec<u8>,
+    timeout: Duration,
+}
+
+impl TcpPreamble {
+    /// Sends the preamble and waits for the expected response
+    async fn exchange_outbound(&self, socket: &mut TcpSocket) -> io::Result<()> {
+        let exchange = async {
+            socket.write_all(&self.bytes).await?;
+            socket.flush().await?;
+            let mut response = vec![0u8; self.expected_response.len()];
+            socket.read_exact(&mut response).await?;
+            Result::<_, io::Error>::Ok(response)
+        };
+        let response = exchange
+            .timeout(self.timeout)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for the preamble response"))??;
+        if response != self.expected_response {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The peer responded with an unexpected preamble",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Waits for the preamble and sends the response. Nothing is sent to a peer that does not send the preamble.
+    async fn exchange_inbound(&self, socket: &mut TcpSocket) -> io::Result<()> {
+        let exchange = async {
+            let mut preamble = vec![0u8; self.bytes.len()];
+            socket.read_exact(&mut preamble).await?;
+            Result::<_, io::Error>::Ok(preamble)
+        };
+        let preamble = exchange
+            .timeout(self.timeout)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for the preamble"))??;
+        if preamble != self.bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The peer sent an unexpected preamble",
+            ));
+        }
+        socket.write_all(&self.expected_response).await?;
+        socket.flush().await
+    }
+}
+
 /// Transport implementation for TCP
 #[derive(Debug, Clone, Default)]
 pub struct TcpTransport {
@@ -38,6 +151,22 @@ pub struct TcpTransport {
     ttl: Option<u32>,
     keepalive: Option<Option<Duration>>,
     nodelay: Option<bool>,
+    address_family_preference: AddressFamilyPreference,
+    preamble: Option<TcpPreamble>,
+    connect_timeout: Option<Duration>,
+    bind_address: Option<SocketAddr>,
+    dial_retry_policy: Option<BackoffPolicy>,
+    listener_shutdown_signal: Option<ListenerShutdownSignal>,
+}
+
+/// The signal that ends the inbound streams of a transport
+#[derive(Clone)]
+struct ListenerShutdownSignal(ShutdownSignal);
+
+impl fmt::Debug for ListenerShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ListenerShutdownSignal")
+    }
 }
 
 impl TcpTransport {
@@ -56,11 +185,99 @@ impl TcpTransport {
     /// Sets `TCP_NODELAY` i.e enable/disable Nagle's algorithm.
     setter_mut!(set_nodelay, nodelay, Option<bool>);
 
+    /// Sets the address family to dial first when a peer has addresses of more than one family.
+    setter_mut!(
+        set_address_family_preference,
+        address_family_preference,
+        AddressFamilyPreference
+    );
+
+    /// Sets the maximum time to wait for an outbound connection to be established. The timeout does not apply to
+    /// reads or writes on an established connection. By default, the dial waits until the OS gives up.
+    setter_mut!(set_connect_timeout, connect_timeout, Option<Duration>);
+
+    /// Sets the policy used to retry a connection attempt that failed with a transient error (the connection was
+    /// refused or timed out). By default, a dial is not retried.
+    setter_mut!(set_dial_retry_policy, dial_retry_policy, Option<BackoffPolicy>);
+
+    /// Sets the local address that `listen` binds to, instead of the address derived from the given `Multiaddr`. This
+    /// allows a multi-homed host to listen on a specific interface while advertising a different address.
+    setter_mut!(set_bind_address, bind_address, Option<SocketAddr>);
+
+    /// Sets the number of times a dial is retried after a transient failure. A dial makes at most
+    /// `max_dial_retries + 1` connection attempts, so zero disables retries. The other values of the dial retry policy
+    /// are kept.
+    pub fn set_max_dial_retries(&mut self, max_dial_retries: usize) -> &mut Self {
+        self.dial_retry_policy_mut().max_attempts = max_dial_retries.saturating_add(1);
+        self
+    }
+
+    /// Sets the delay before the first retry of a dial. The delay doubles for each subsequent retry, up to the maximum
+    /// delay of the dial retry policy, which is raised to `retry_backoff` if it is lower. Setting the delay does not
+    /// enable retries by itself.
+    pub fn set_retry_backoff(&mut self, retry_backoff: Duration) -> &mut Self {
+        let policy = self.dial_retry_policy_mut();
+        policy.base_delay = retry_backoff;
+        policy.max_delay = policy.max_delay.max(retry_backoff);
+        self
+    }
+
+    /// Requires a preamble exchange on every connection before it is handed out. A dialed connection sends `bytes` and
+    /// is dropped unless the peer responds with `expected_response` within `timeout`. An inbound connection is dropped
+    /// unless the peer sends `bytes` within `timeout`, after which `expected_response` is sent. By default, no
+    /// preamble is exchanged.
+    pub fn set_preamble(&mut self, bytes: Vec<u8>, expected_response: Vec<u8>, timeout: Duration) -> &mut Self {
+        self.preamble = Some(TcpPreamble {
+            bytes,
+            expected_response,
+            timeout,
+        });
+        self
+    }
+
+    /// Sets the signal which ends every inbound stream returned by `listen` once it is triggered. By default, an
+    /// inbound stream only ends when it is dropped.
+    pub fn set_shutdown_signal(&mut self, shutdown_signal: ShutdownSignal) -> &mut Self {
+        self.listener_shutdown_signal = Some(ListenerShutdownSignal(shutdown_signal));
+        self
+    }
+
     /// Create a new TcpTransport
     pub fn new() -> Self {
         Default::default()
     }
 
+    /// Returns the dial retry policy, setting a policy that makes a single attempt if there is none
+    fn dial_retry_policy_mut(&mut self) -> &mut BackoffPolicy {
+        self.dial_retry_policy.get_or_insert_with(|| BackoffPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        })
+    }
+
+    /// Connect to the given address, retrying transient failures according to the dial retry policy. The connect
+    /// timeout applies to each attempt. The error of the last attempt is returned if every attempt fails.
+    async fn connect(&self, socket_addr: &SocketAddr) -> io::Result<TcpStream> {
+        match self.dial_retry_policy {
+            Some(policy) => {
+                retry_with_backoff(policy, || self.connect_once(socket_addr), is_transient_dial_error).await
+            },
+            None => self.connect_once(socket_addr).await,
+        }
+    }
+
+    /// Make a single connection attempt, applying the connect timeout if one is set
+    async fn connect_once(&self, socket_addr: &SocketAddr) -> io::Result<TcpStream> {
+        match self.connect_timeout {
+            Some(timeout) => TcpStream::connect(socket_addr)
+                .timeout(timeout)
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Timed out establishing the connection"))
+                .and_then(|result| result),
+            None => TcpStream::connect(socket_addr).await,
+        }
+    }
+
     /// Apply socket options to `TcpStream`.
     fn configure(&self, socket: &TcpStream) -> io::Result<()> {
         if let Some(keepalive) = self.keepalive {
@@ -89,7 +306,7 @@ impl TcpTransport {
 
 impl Transport for TcpTransport {
     type Error = io::Error;
-    type Inbound = future::Ready<io::Result<(TcpSocket, Multiaddr)>>;
+    type Inbound = BoxFuture<'static, io::Result<(TcpSocket, Multiaddr)>>;
     type Listener = TcpInbound<'static>;
     type Output = (TcpSocket, Multiaddr);
 
@@ -99,16 +316,18 @@ impl Transport for TcpTransport {
     fn listen(&self, addr: Multiaddr) -> Self::ListenFuture {
         let config = self.clone();
         Box::pin(async move {
-            let socket_addr = multiaddr_to_socketaddr(&addr)?;
+            let socket_addr = match config.bind_address {
+                Some(bind_address) => bind_address,
+                None => multiaddr_to_socketaddr(&addr)?,
+            };
             let listener = TcpListener::bind(&socket_addr).await?;
             let local_addr = socketaddr_to_multiaddr(&listener.local_addr()?);
-            Ok((
-                TcpInbound {
-                    incoming: listener.incoming().boxed(),
-                    config,
-                },
-                local_addr,
-            ))
+            let shutdown_signal = config.listener_shutdown_signal.clone();
+            let mut inbound = TcpInbound::new(listener.incoming().boxed(), config);
+            if let Some(ListenerShutdownSignal(signal)) = shutdown_signal {
+                inbound = inbound.with_shutdown_signal(signal);
+            }
+            Ok((inbound, local_addr))
         })
     }
 
@@ -116,12 +335,31 @@ impl Transport for TcpTransport {
         let config = self.clone();
         Box::pin(async move {
             let socket_addr = multiaddr_to_socketaddr(&addr)?;
-            let stream = TcpStream::connect(&socket_addr).await?;
+            let stream = config.connect(&socket_addr).await?;
             config.configure(&stream)?;
             let peer_addr = socketaddr_to_multiaddr(&stream.peer_addr()?);
-            Ok((TcpSocket::new(stream), peer_addr))
+            let mut socket = TcpSocket::new(stream);
+            if let Some(preamble) = config.preamble.as_ref() {
+                preamble.exchange_outbound(&mut socket).await?;
+            }
+            Ok((socket, peer_addr))
         })
     }
+
+    /// Orders addresses of the preferred family first. The relative order of the addresses within each group is kept.
+    fn order_dial_addresses(&self, mut addresses: Vec<Multiaddr>) -> Vec<Multiaddr> {
+        let preference = self.address_family_preference;
+        addresses.sort_by_key(|addr| !preference.is_preferred(addr));
+        addresses
+    }
+}
+
+/// Returns true if a failed connection attempt may succeed if it is retried
+fn is_transient_dial_error(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::TimedOut => true,
+        _ => false,
+    }
 }
 
 /// Wrapper around an Inbound stream. This ensures that any connecting `TcpStream` is configured according to the
@@ -129,19 +367,58 @@ impl Transport for TcpTransport {
 pub struct TcpInbound<'a> {
     incoming: BoxStream<'a, io::Result<TcpStream>>,
     config: TcpTransport,
+    shutdown_signal: Option<ShutdownSignal>,
+    is_shutdown: bool,
+}
+
+impl<'a> TcpInbound<'a> {
+    pub fn new(incoming: BoxStream<'a, io::Result<TcpStream>>, config: TcpTransport) -> Self {
+        Self {
+            incoming,
+            config,
+            shutdown_signal: None,
+            is_shutdown: false,
+        }
+    }
+
+    /// Stop accepting inbound connections once the given `ShutdownSignal` is triggered. The stream will end when the
+    /// signal resolves.
+    pub fn with_shutdown_signal(mut self, shutdown_signal: ShutdownSignal) -> Self {
+        self.shutdown_signal = Some(shutdown_signal);
+        self
+    }
 }
 
 impl Stream for TcpInbound<'_> {
-    type Item = io::Result<future::Ready<io::Result<(TcpSocket, Multiaddr)>>>;
+    type Item = io::Result<BoxFuture<'static, io::Result<(TcpSocket, Multiaddr)>>>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.is_shutdown {
+            return Poll::Ready(None);
+        }
+
+        if let Some(signal) = self.shutdown_signal.as_mut() {
+            if signal.poll_unpin(cx).is_ready() {
+                self.is_shutdown = true;
+                self.shutdown_signal = None;
+                return Poll::Ready(None);
+            }
+        }
+
         match ready!(self.incoming.poll_next_unpin(cx)) {
             Some(Ok(stream)) => {
                 // Configure each socket
                 self.config.configure(&stream)?;
                 let peer_addr = socketaddr_to_multiaddr(&stream.peer_addr()?);
-                let result = future::ready(Ok((TcpSocket::new(stream), peer_addr)));
-                Poll::Ready(Some(Ok(result)))
+                let preamble = self.config.preamble.clone();
+                let result = async move {
+                    let mut socket = TcpSocket::new(stream);
+                    if let Some(preamble) = preamble {
+                        preamble.exchange_inbound(&mut socket).await?;
+                    }
+                    Ok((socket, peer_addr))
+                };
+                Poll::Ready(Some(Ok(result.boxed())))
             },
             Some(Err(err)) => Poll::Ready(Some(Err(err))),
             None => Poll::Ready(None),
@@ -153,17 +430,45 @@ impl Stream for TcpInbound<'_> {
 /// `futures-rs` AsyncRead/Write
 pub struct TcpSocket {
     inner: TcpStream,
+    metrics: TcpSocketMetrics,
 }
 
 impl TcpSocket {
     pub fn new(stream: TcpStream) -> Self {
-        Self { inner: stream }
+        Self {
+            inner: stream,
+            metrics: TcpSocketMetrics::default(),
+        }
+    }
+
+    /// The total number of bytes read from this socket
+    pub fn bytes_read(&self) -> u64 {
+        self.metrics.bytes_read()
+    }
+
+    /// The total number of bytes written to this socket
+    pub fn bytes_written(&self) -> u64 {
+        self.metrics.bytes_written()
+    }
+
+    /// Returns a handle to the byte counters of this socket, which can be sampled without holding the socket
+    pub fn metrics_handle(&self) -> TcpSocketMetrics {
+        self.metrics.clone()
+    }
+
+    /// Splits this socket into a read half and a write half which can be owned (and moved to different tasks)
+    /// independently of each other.
+    pub fn split(self) -> (TcpReadHalf, TcpWriteHalf) {
+        let (read_half, write_half) = AsyncReadExt::split(self);
+        (TcpReadHalf { inner: read_half }, TcpWriteHalf { inner: write_half })
     }
 }
 
 impl AsyncWrite for TcpSocket {
     fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
-        Pin::new(&mut self.inner).poll_write(cx, buf)
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.metrics.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(n))
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
@@ -177,19 +482,75 @@ impl AsyncWrite for TcpSocket {
 
 impl AsyncRead for TcpSocket {
     fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, Error>> {
-        Pin::new(&mut self.inner).poll_read(cx, buf)
+        let n = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.metrics.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(n))
     }
 }
 
 impl From<TcpStream> for TcpSocket {
     fn from(stream: TcpStream) -> Self {
-        Self { inner: stream }
+        Self::new(stream)
+    }
+}
+
+/// Byte counters for a `TcpSocket`. Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct TcpSocketMetrics {
+    bytes_read: Arc<AtomicU64>,
+    bytes_written: Arc<AtomicU64>,
+}
+
+impl TcpSocketMetrics {
+    /// The total number of bytes read from the socket
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// The total number of bytes written to the socket
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+}
+
+/// The read half of a `TcpSocket`, created by `TcpSocket::split`
+pub struct TcpReadHalf {
+    inner: ReadHalf<TcpSocket>,
+}
+
+impl AsyncRead for TcpReadHalf {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+/// The write half of a `TcpSocket`, created by `TcpSocket::split`. Closing the write half shuts down the write side
+/// of the underlying socket.
+pub struct TcpWriteHalf {
+    inner: WriteHalf<TcpSocket>,
+}
+
+impl AsyncWrite for TcpWriteHalf {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Pin::new(&mut self.inner).poll_close(cx)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use futures::{channel::oneshot, future};
+    use std::time::Instant;
+    use tari_shutdown::Shutdown;
+    use tokio::runtime::Runtime;
 
     #[test]
     fn configure() {
@@ -198,12 +559,302 @@ mod test {
             .set_recv_buffer_size(456)
             .set_nodelay(true)
             .set_ttl(789)
-            .set_keepalive(Some(Duration::from_millis(100)));
+            .set_keepalive(Some(Duration::from_millis(100)))
+            .set_connect_timeout(Duration::from_secs(5));
 
         assert_eq!(tcp.send_buffer_size, Some(123));
         assert_eq!(tcp.recv_buffer_size, Some(456));
         assert_eq!(tcp.nodelay, Some(true));
         assert_eq!(tcp.ttl, Some(789));
         assert_eq!(tcp.keepalive, Some(Some(Duration::from_millis(100))));
+        assert_eq!(tcp.connect_timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn dial_connect_timeout() {
+        let rt = Runtime::new().unwrap();
+        let mut transport = TcpTransport::new();
+        transport.set_connect_timeout(Duration::from_millis(200));
+        // A non-routable address which never answers, so the connection can only end by timing out
+        let addr = "/ip4/10.255.255.1/tcp/9999".parse::<Multiaddr>().unwrap();
+
+        let start = Instant::now();
+        let result = rt.block_on(transport.dial(addr));
+        match result {
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+            Ok(_) => panic!("Dial to a blackholed address succeeded"),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn dial_retry() {
+        let rt = Runtime::new().unwrap();
+        // Reserve a free port and release it so that the first connection attempt is refused
+        let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener_thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let listener = std::net::TcpListener::bind(listen_addr).unwrap();
+            listener.accept().unwrap();
+        });
+
+        let mut transport = TcpTransport::new();
+        transport
+            .set_dial_retry_policy(BackoffPolicy::new(
+                10,
+                Duration::from_millis(20),
+                Duration::from_millis(50),
+            ))
+            .set_connect_timeout(Duration::from_secs(1));
+        let result = rt.block_on(transport.dial(socketaddr_to_multiaddr(&listen_addr)));
+        assert!(result.is_ok());
+        listener_thread.join().unwrap();
+
+        // Without retries, a refused connection fails the dial
+        let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let result = rt.block_on(TcpTransport::new().dial(socketaddr_to_multiaddr(&listen_addr)));
+        match result {
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused),
+            Ok(_) => panic!("Dial to a closed port succeeded"),
+        }
+    }
+
+    #[test]
+    fn max_dial_retries() {
+        let rt = Runtime::new().unwrap();
+        let listen_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let listener_thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let listener = std::net::TcpListener::bind(listen_addr).unwrap();
+            listener.accept().unwrap();
+        });
+
+        // The first attempt is refused and the single retry is made once the listener is bound
+        let mut transport = TcpTransport::new();
+        transport
+            .set_max_dial_retries(1)
+            .set_retry_backoff(Duration::from_millis(500))
+            .set_connect_timeout(Duration::from_secs(1));
+        let policy = transport.dial_retry_policy.unwrap();
+        assert_eq!(policy.max_attempts, 2);
+        assert_eq!(policy.delay_for_retry(1), Duration::from_millis(500));
+        let result = rt.block_on(transport.dial(socketaddr_to_multiaddr(&listen_addr)));
+        assert!(result.is_ok());
+        listener_thread.join().unwrap();
+
+        // Setting the backoff alone does not enable retries
+        let mut transport = TcpTransport::new();
+        transport.set_retry_backoff(Duration::from_millis(500));
+        assert_eq!(transport.dial_retry_policy.unwrap().max_attempts, 1);
+        transport.set_max_dial_retries(0);
+        assert_eq!(transport.dial_retry_policy.unwrap().max_attempts, 1);
+    }
+
+    #[test]
+    fn only_transient_dial_errors_are_retried() {
+        assert!(is_transient_dial_error(&io::ErrorKind::ConnectionRefused.into()));
+        assert!(is_transient_dial_error(&io::ErrorKind::TimedOut.into()));
+        assert!(!is_transient_dial_error(&io::ErrorKind::PermissionDenied.into()));
+        assert!(!is_transient_dial_error(&io::ErrorKind::AddrNotAvailable.into()));
+        assert!(!is_transient_dial_error(&io::ErrorKind::InvalidInput.into()));
+    }
+
+    #[test]
+    fn order_dial_addresses() {
+        let addresses = vec![
+            "/ip4/127.0.0.1/tcp/8000",
+            "/ip6/::1/tcp/8001",
+            "/ip4/127.0.0.1/tcp/8002",
+            "/ip6/::1/tcp/8003",
+        ]
+        .into_iter()
+        .map(|addr| addr.parse::<Multiaddr>().unwrap())
+        .collect::<Vec<_>>();
+
+        let mut tcp = TcpTransport::new();
+        assert_eq!(tcp.address_family_preference, AddressFamilyPreference::PreferIpv6);
+        assert_eq!(tcp.order_dial_addresses(addresses.clone()), vec![
+            addresses[1].clone(),
+            addresses[3].clone(),
+            addresses[0].clone(),
+            addresses[2].clone(),
+        ]);
+
+        tcp.set_address_family_preference(AddressFamilyPreference::PreferIpv4);
+        assert_eq!(tcp.order_dial_addresses(addresses.clone()), vec![
+            addresses[0].clone(),
+            addresses[2].clone(),
+            addresses[1].clone(),
+            addresses[3].clone(),
+        ]);
+
+        tcp.set_address_family_preference(AddressFamilyPreference::Any);
+        assert_eq!(tcp.order_dial_addresses(addresses.clone()), addresses);
+    }
+
+    #[test]
+    fn listen_bind_address() {
+        let rt = Runtime::new().unwrap();
+        let mut transport = TcpTransport::new();
+        transport.set_bind_address("127.0.0.1:0".parse().unwrap());
+        // The bind address takes precedence over the given address
+        let addr = "/ip4/0.0.0.0/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let (mut inbound, listen_addr) = rt.block_on(transport.listen(addr)).unwrap();
+        let mut addr_iter = listen_addr.iter();
+        assert_eq!(addr_iter.next(), Some(AddrComponent::IP4("127.0.0.1".parse().unwrap())));
+        match addr_iter.next() {
+            Some(AddrComponent::TCP(port)) => assert_ne!(port, 0),
+            component => panic!("Unexpected address component {:?}", component),
+        }
+
+        let (dial_result, accept_result) = rt.block_on(future::join(TcpTransport::new().dial(listen_addr), async {
+            inbound.next().await.unwrap().unwrap().await
+        }));
+        assert!(dial_result.is_ok());
+        assert!(accept_result.is_ok());
+    }
+
+    #[test]
+    fn inbound_shutdown() {
+        let rt = Runtime::new().unwrap();
+        let mut shutdown = Shutdown::new();
+        let transport = TcpTransport::new();
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let (inbound1, _) = rt.block_on(transport.listen(addr.clone())).unwrap();
+        let (inbound2, _) = rt.block_on(transport.listen(addr)).unwrap();
+        let inbound1 = inbound1.with_shutdown_signal(shutdown.to_signal());
+        let inbound2 = inbound2.with_shutdown_signal(shutdown.to_signal());
+
+        shutdown.trigger().unwrap();
+
+        // Triggering the shutdown ends every inbound stream that was given a clone of the signal
+        let (next1, next2) = rt.block_on(future::join(inbound1.collect::<Vec<_>>(), inbound2.collect::<Vec<_>>()));
+        assert!(next1.is_empty());
+        assert!(next2.is_empty());
+    }
+
+    #[test]
+    fn listen_with_shutdown_signal() {
+        let rt = Runtime::new().unwrap();
+        let mut shutdown = Shutdown::new();
+        let mut transport = TcpTransport::new();
+        transport.set_shutdown_signal(shutdown.to_signal());
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let (inbound1, _) = rt.block_on(transport.listen(addr.clone())).unwrap();
+        let (inbound2, _) = rt.block_on(transport.listen(addr)).unwrap();
+
+        shutdown.trigger().unwrap();
+
+        // Every listener of the transport is given the signal
+        let (next1, next2) = rt.block_on(future::join(inbound1.collect::<Vec<_>>(), inbound2.collect::<Vec<_>>()));
+        assert!(next1.is_empty());
+        assert!(next2.is_empty());
+    }
+
+    #[test]
+    fn preamble_exchange() {
+        let rt = Runtime::new().unwrap();
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+        let mut listener_transport = TcpTransport::new();
+        listener_transport.set_preamble(b"tari".to_vec(), b"okay".to_vec(), Duration::from_secs(5));
+        let (mut inbound, listen_addr) = rt.block_on(listener_transport.listen(addr)).unwrap();
+
+        let mut dialer_transport = TcpTransport::new();
+        dialer_transport.set_preamble(b"tari".to_vec(), b"okay".to_vec(), Duration::from_secs(5));
+        let (dial_result, accept_result) = rt
+            .block_on(future::join(dialer_transport.dial(listen_addr.clone()), async {
+                inbound.next().await.unwrap().unwrap().await
+            }));
+        assert!(dial_result.is_ok());
+        assert!(accept_result.is_ok());
+
+        // The peer returns the wrong preamble response
+        dialer_transport.set_preamble(b"tari".to_vec(), b"nope".to_vec(), Duration::from_secs(5));
+        let (dial_result, accept_result) = rt.block_on(future::join(dialer_transport.dial(listen_addr), async {
+            inbound.next().await.unwrap().unwrap().await
+        }));
+        match dial_result {
+            Err(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            Ok(_) => panic!("Dial succeeded with the wrong preamble response"),
+        }
+        assert!(accept_result.is_ok());
+    }
+
+    #[test]
+    fn byte_count_metrics() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let (mut inbound, listen_addr) = rt.block_on(transport.listen(addr)).unwrap();
+        let (mut dialed, _) = rt.block_on(transport.dial(listen_addr)).unwrap();
+        let (mut accepted, _) = rt
+            .block_on(async move { inbound.next().await.unwrap().unwrap().await })
+            .unwrap();
+        let dialed_metrics = dialed.metrics_handle();
+
+        rt.block_on(async {
+            dialed.write_all(&[1u8; 100]).await.unwrap();
+            dialed.flush().await.unwrap();
+            let mut buf = [0u8; 100];
+            accepted.read_exact(&mut buf).await.unwrap();
+        });
+
+        assert_eq!(dialed.bytes_written(), 100);
+        assert_eq!(dialed_metrics.bytes_written(), 100);
+        assert_eq!(dialed.bytes_read(), 0);
+        assert_eq!(accepted.bytes_read(), 100);
+        assert_eq!(accepted.bytes_written(), 0);
+    }
+
+    #[test]
+    fn split_socket() {
+        let rt = Runtime::new().unwrap();
+        let transport = TcpTransport::new();
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let (mut inbound, listen_addr) = rt.block_on(transport.listen(addr)).unwrap();
+        let (mut dialed, _) = rt.block_on(transport.dial(listen_addr)).unwrap();
+        let (accepted, _) = rt
+            .block_on(async move { inbound.next().await.unwrap().unwrap().await })
+            .unwrap();
+
+        let (mut read_half, mut write_half) = accepted.split();
+
+        let (write_tx, write_rx) = oneshot::channel();
+        rt.spawn(async move {
+            write_half.write_all(b"ping").await.unwrap();
+            write_half.flush().await.unwrap();
+            write_tx.send(()).unwrap();
+        });
+
+        let (read_tx, read_rx) = oneshot::channel();
+        rt.spawn(async move {
+            let mut buf = [0u8; 4];
+            read_half.read_exact(&mut buf).await.unwrap();
+            read_tx.send(buf).unwrap();
+        });
+
+        rt.block_on(async move {
+            dialed.write_all(b"pong").await.unwrap();
+            let mut buf = [0u8; 4];
+            dialed.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            write_rx.await.unwrap();
+            assert_eq!(&read_rx.await.unwrap(), b"pong");
+        });
     }
 }
@@ -23,9 +23,76 @@
 use multiaddr::{AddrComponent, Multiaddr};
 use std::{
     io,
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
 };
 
+/// The reachability category of an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressReachability {
+    /// A loopback address, only reachable from the local host
+    Loopback,
+    /// A private (RFC1918 or IPv6 unique local) or link-local address, only reachable on the local network
+    Private,
+    /// An unspecified, broadcast, multicast, shared (RFC6598), documentation or otherwise reserved address, which is
+    /// never publicly routable
+    Reserved,
+    /// A publicly routable address
+    Public,
+    /// The address does not start with an IP component (e.g. a DNS or onion address), so it cannot be classified
+    Unknown,
+}
+
+/// Classify the reachability of the IP address at the start of the given multiaddr. A base node can use this to refuse
+/// to advertise loopback, private or reserved addresses.
+pub fn classify_multiaddr(addr: &Multiaddr) -> AddressReachability {
+    match addr.iter().next() {
+        Some(AddrComponent::IP4(ip)) => classify_ipv4(&ip),
+        Some(AddrComponent::IP6(ip)) => classify_ipv6(&ip),
+        _ => AddressReachability::Unknown,
+    }
+}
+
+fn classify_ipv4(ip: &Ipv4Addr) -> AddressReachability {
+    if ip.is_loopback() {
+        return AddressReachability::Loopback;
+    }
+    if ip.is_private() || ip.is_link_local() {
+        return AddressReachability::Private;
+    }
+    let octets = ip.octets();
+    // Shared address space (100.64.0.0/10) is used for carrier-grade NAT
+    let is_shared = octets[0] == 100 && octets[1] & 0xc0 == 64;
+    // 240.0.0.0/4 is reserved for future use, and includes the broadcast address
+    let is_future_use = octets[0] & 0xf0 == 240;
+    if ip.is_unspecified() || ip.is_multicast() || is_future_use || ip.is_documentation() || is_shared {
+        return AddressReachability::Reserved;
+    }
+    AddressReachability::Public
+}
+
+fn classify_ipv6(ip: &Ipv6Addr) -> AddressReachability {
+    if ip.is_loopback() {
+        return AddressReachability::Loopback;
+    }
+    if let Some(ipv4) = ip.to_ipv4() {
+        // IPv4-mapped and IPv4-compatible addresses are classified by their IPv4 address. `::` and `::1` are also
+        // IPv4-compatible, but `::1` has already been handled above.
+        if !ip.is_unspecified() {
+            return classify_ipv4(&ipv4);
+        }
+    }
+    let first_segment = ip.segments()[0];
+    // Unique local addresses (fc00::/7) and link-local addresses (fe80::/10)
+    if first_segment & 0xfe00 == 0xfc00 || first_segment & 0xffc0 == 0xfe80 {
+        return AddressReachability::Private;
+    }
+    // The unspecified address (::), multicast addresses (ff00::/8) and documentation addresses (2001:db8::/32)
+    if ip.is_unspecified() || ip.is_multicast() || (first_segment == 0x2001 && ip.segments()[1] == 0x0db8) {
+        return AddressReachability::Reserved;
+    }
+    AddressReachability::Public
+}
+
 /// Convert a socket address to a multiaddress
 pub fn socketaddr_to_multiaddr(socket_addr: &SocketAddr) -> Multiaddr {
     let mut addr: Multiaddr = match socket_addr.ip() {
@@ -107,6 +174,61 @@ mod test {
         expect_fail("/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC");
     }
 
+    #[test]
+    fn classify_multiaddr() {
+        fn classify(addr: &str) -> AddressReachability {
+            super::classify_multiaddr(&Multiaddr::from_str(addr).unwrap())
+        }
+
+        assert_eq!(classify("/ip4/127.0.0.1/tcp/1234"), AddressReachability::Loopback);
+        assert_eq!(classify("/ip6/::1/tcp/1234"), AddressReachability::Loopback);
+
+        assert_eq!(classify("/ip4/10.0.0.1/tcp/1234"), AddressReachability::Private);
+        assert_eq!(classify("/ip4/172.16.5.4/tcp/1234"), AddressReachability::Private);
+        assert_eq!(classify("/ip4/192.168.1.1/tcp/1234"), AddressReachability::Private);
+        assert_eq!(
+            classify("/ip6/fd12:3456:789a::1/tcp/1234"),
+            AddressReachability::Private
+        );
+        assert_eq!(classify("/ip6/fe80::1/tcp/1234"), AddressReachability::Private);
+        assert_eq!(
+            classify("/ip6/::ffff:192.168.1.1/tcp/1234"),
+            AddressReachability::Private
+        );
+
+        assert_eq!(classify("/ip4/0.0.0.0/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip6/::/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/255.255.255.255/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/100.64.0.1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/100.127.255.254/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/192.0.2.1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip6/2001:db8::1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/224.0.0.1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/239.255.255.250/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/240.0.0.1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip4/250.1.2.3/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip6/ff02::1/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(classify("/ip6/ff0e::101/tcp/1234"), AddressReachability::Reserved);
+        assert_eq!(
+            classify("/ip6/::ffff:100.64.0.1/tcp/1234"),
+            AddressReachability::Reserved
+        );
+
+        assert_eq!(classify("/ip4/8.8.8.8/tcp/1234"), AddressReachability::Public);
+        assert_eq!(classify("/ip4/100.128.0.1/tcp/1234"), AddressReachability::Public);
+        assert_eq!(classify("/ip4/172.32.0.1/tcp/1234"), AddressReachability::Public);
+        assert_eq!(classify("/ip4/223.255.255.254/tcp/1234"), AddressReachability::Public);
+        assert_eq!(
+            classify("/ip6/2001:4860:4860::8888/tcp/1234"),
+            AddressReachability::Public
+        );
+
+        assert_eq!(
+            classify("/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"),
+            AddressReachability::Unknown
+        );
+    }
+
     #[test]
     fn multiaddr_from_components() {
         let ip: Ipv4Addr = "127.0.0.1".parse().unwrap();